@@ -18,7 +18,5 @@ fn print_error(err: &Report) {
 }
 
 fn my_err() -> Report {
-    || -> eyre::Result<()> {
-        Err(eyre::eyre!("error: my error 1"))
-    }().context("my error 2").context("my error 3").unwrap_err()
+    Err::<(), _>(eyre::eyre!("error: my error 1")).context("my error 2").context("my error 3").unwrap_err()
 }
@@ -1,8 +1,8 @@
 use eyre::{Context, Report};
-use myutil::log::{LogMode, init_log};
+use myutil::log::{LogMode, init_log_or_panic};
 
 fn main() {
-    init_log(LogMode::General, tracing::Level::TRACE);
+    init_log_or_panic(LogMode::General, tracing::Level::TRACE);
     display1();
 }
 
@@ -19,7 +19,5 @@ fn display1() {
 }
 
 fn my_err() -> Report {
-    || -> eyre::Result<()> {
-        Err(eyre::eyre!("error: my error 1."))
-    }().context("my error 2.").context("my error 3.").unwrap_err()
+    Err::<(), _>(eyre::eyre!("error: my error 1.")).context("my error 2.").context("my error 3.").unwrap_err()
 }
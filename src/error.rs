@@ -1,9 +1,25 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+pub use backtrace::BacktraceExt;
+pub use category::ErrorCategory;
+pub use frames::set_collapse_frames;
+pub use multi::{combine_errors, MultiError};
+pub use redact::redact_report;
+
+mod backtrace;
+mod category;
+mod frames;
+mod multi;
+mod redact;
+
 /// 打印 eyre error 和 panic 时，美化输出
 /// 
 /// 打印调用栈时，只打印以`package_name`开头的记录，如果`package_name=""`则打印全部
+///
+/// 安装后可以用`set_error_verbosity`切换是否输出调用栈和位置信息
 /// 
 /// # Example
-/// ```
+/// ```should_panic
 /// let err = eyre::eyre!("error: test");
 /// panic!("1 {err}");
 /// panic!("2 {err:?}");
@@ -11,8 +27,20 @@
 /// panic!("4 {err:#?}");
 /// ```
 pub fn init_error_hook(package_name: &'static str) {
+    init_error_hook_with(package_name, Box::new(ColorEyreFormatter));
+}
+
+/// 与`init_error_hook`相同，但 eyre 错误报告由`formatter`输出
+///
+/// 调用栈过滤仍由本 crate 完成，`formatter`拿到的默认 handler 已应用过滤
+pub fn init_error_hook_with(package_name: &'static str, formatter: Box<dyn ErrorFormatter>) {
+    install_error_hook(package_name, formatter).expect("Failed to initialize color_eyre");
+}
+
+/// 先安装 panic hook 再安装 eyre hook：eyre 在第一次创建`Report`时会装上默认 hook，之后`eyre::set_hook`会失败，此时 panic hook 仍然有效
+fn install_error_hook(package_name: &'static str, formatter: Box<dyn ErrorFormatter>) -> eyre::Result<()> {
     // color_eyre::install().unwrap();
-    color_eyre::config::HookBuilder::default()
+    let builder = color_eyre::config::HookBuilder::default()
         .add_frame_filter(Box::new(move |frames| {
             let filters = &[package_name];
 
@@ -31,22 +59,371 @@ pub fn init_error_hook(package_name: &'static str) {
             });
         }))
         .display_location_section(false) //表示在错误报告中是否显示错误发生的具体代码位置信息，这不会禁用紧急消息中的位置部分。
-        .display_env_section(false) //表示在错误报告中是否显示环境信息部分。
-        .install()
-        .expect("Failed to initialize color_eyre");
+        .display_env_section(false); //表示在错误报告中是否显示环境信息部分。
+    #[cfg(feature = "log")]
+    let builder = builder.panic_section(CrashLogSection);
+    let (panic_hook, eyre_hook) = builder.try_into_hooks()?;
+
+    std::panic::set_hook(Box::new(move |panic_info| {
+        let mut report = if error_verbosity() {
+            panic_hook.panic_report(panic_info).to_string()
+        } else {
+            format!("The application panicked: {}", panic_message(panic_info.payload()))
+        };
+        if error_verbosity() && frames::collapse_frames_enabled() {
+            report = frames::collapse_repeated_frames(&report);
+        }
+        #[cfg(feature = "log")]
+        if !error_verbosity() {
+            if let Some(path) = crate::log::crash_log_path() {
+                report.push_str(&format!("\nLogs: {}", path.display()));
+            }
+        }
+        if let Some(report) = capture_panic(report) {
+            eprintln!("{report}");
+        }
+        #[cfg(feature = "log")]
+        if panic_to_log() {
+            log_panic(panic_info);
+        }
+    }));
+    HOOK_INSTALLED.store(true, Ordering::Relaxed);
+
+    // 与 HookBuilder::install() 相同，只是在外面包一层，由 formatter 决定输出内容
+    let eyre_hook = eyre_hook.into_eyre_hook();
+    let formatter: std::sync::Arc<dyn ErrorFormatter> = formatter.into();
+    eyre::set_hook(Box::new(move |error| Box::new(Handler {
+        formatter: formatter.clone(),
+        inner: eyre_hook(error),
+        sections: Vec::new(),
+    })))?;
+    EYRE_HOOK_INSTALLED.store(true, Ordering::Relaxed);
+    Ok(())
+}
+
+static HOOK_INSTALLED: AtomicBool = AtomicBool::new(false);
+static EYRE_HOOK_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// panic hook 和 eyre hook 是否已经安装，用于`init_report`
+#[cfg(feature = "log")]
+pub(crate) fn hooks_installed() -> (bool, bool) {
+    (HOOK_INSTALLED.load(Ordering::Relaxed), EYRE_HOOK_INSTALLED.load(Ordering::Relaxed))
+}
+
+thread_local! {
+    /// `with_error_hook_installed`执行期间，panic 报告写到这里而不是 stderr
+    static PANIC_CAPTURE: std::cell::RefCell<Option<String>> = const { std::cell::RefCell::new(None) };
+}
+
+/// 当前线程正在捕获时保存报告并返回`None`，否则原样返回
+fn capture_panic(report: String) -> Option<String> {
+    PANIC_CAPTURE.with_borrow_mut(|capture| match capture {
+        Some(output) => {
+            output.push_str(&report);
+            None
+        }
+        None => Some(report),
+    })
+}
+
+/// 测试用：确保 hook 已安装（只安装一次，已由`init_error_hook`安装时直接使用），执行`f`并返回其中 panic 的报告
+///
+/// `f`中的 panic 会被捕获，不会让测试失败；没有 panic 时返回空字符串。
+/// hook 是全局的，`package_name`只在第一次安装时生效。
+///
+/// # Example
+/// ```
+/// use myutil::error::with_error_hook_installed;
+///
+/// let output = with_error_hook_installed("myutil", || panic!("boom"));
+/// assert!(output.contains("boom"));
+/// ```
+pub fn with_error_hook_installed(package_name: &'static str, f: impl FnOnce() + std::panic::UnwindSafe) -> String {
+    static INSTALL: std::sync::OnceLock<()> = std::sync::OnceLock::new();
+    INSTALL.get_or_init(|| {
+        if !HOOK_INSTALLED.load(Ordering::Relaxed) {
+            // eyre hook 可能已被默认 hook 占用，panic hook 仍会安装
+            let _ = install_error_hook(package_name, Box::new(ColorEyreFormatter));
+        }
+    });
+
+    PANIC_CAPTURE.set(Some(String::new()));
+    let _ = std::panic::catch_unwind(f);
+    PANIC_CAPTURE.take().unwrap_or_default()
+}
+
+#[cfg(feature = "log")]
+static PANIC_TO_LOG: AtomicBool = AtomicBool::new(false);
+
+/// 设置`init_error_hook`安装的 panic hook 是否同时把 panic 写入日志（例如`init_log_crashfile`的日志文件），默认`false`
+///
+/// 在 stderr 输出之后以 error 级别写入并立即 flush，保证 panic 是进程退出前最后一条落盘的日志
+#[cfg(feature = "log")]
+pub fn set_panic_to_log(enabled: bool) {
+    PANIC_TO_LOG.store(enabled, Ordering::Relaxed);
+}
+
+/// 当前 panic 是否同时写入日志
+#[cfg(feature = "log")]
+pub fn panic_to_log() -> bool {
+    PANIC_TO_LOG.load(Ordering::Relaxed)
+}
+
+/// 日志里不需要 color_eyre 的颜色，只写 panic 消息、位置和 backtrace（设置了`RUST_BACKTRACE`时）
+#[cfg(feature = "log")]
+fn log_panic(panic_info: &std::panic::PanicHookInfo<'_>) {
+    let mut message = format!("The application panicked: {}", panic_message(panic_info.payload()));
+    if let Some(location) = panic_info.location() {
+        message.push_str(&format!("\nLocation: {location}"));
+    }
+    let backtrace = std::backtrace::Backtrace::capture();
+    if backtrace.status() == std::backtrace::BacktraceStatus::Captured {
+        message.push_str(&format!("\n{backtrace}"));
+    }
+    tracing::error!(flush = true, "{message}");
+}
+
+/// panic 报告中提示`init_log_crashfile`的日志文件路径，未启用时不输出
+#[cfg(feature = "log")]
+struct CrashLogSection;
+
+#[cfg(feature = "log")]
+impl std::fmt::Display for CrashLogSection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // 在 panic 时才读取路径，hook 可以早于 init_log_crashfile 安装
+        match crate::log::crash_log_path() {
+            Some(path) => write!(f, "Logs: {}", path.display()),
+            None => Ok(()),
+        }
+    }
+}
+
+static ERROR_VERBOSE: AtomicBool = AtomicBool::new(true);
+
+/// 设置`init_error_hook`安装的 hook 是否输出调用栈和位置信息，默认`true`
+///
+/// hook 是全局的且只能安装一次，这里在每次生成报告时读取，可在运行时切换，例如根据`--verbose`参数：
+///
+/// ```
+/// myutil::error::set_error_verbosity(false); // 只输出错误链
+/// ```
+pub fn set_error_verbosity(verbose: bool) {
+    ERROR_VERBOSE.store(verbose, Ordering::Relaxed);
+}
+
+/// 当前是否输出调用栈和位置信息
+pub fn error_verbosity() -> bool {
+    ERROR_VERBOSE.load(Ordering::Relaxed)
+}
+
+/// 自定义 eyre 错误报告（`{:?}`）的输出格式，配合`init_error_hook_with`使用
+///
+/// `default`是 color_eyre 的 handler，已应用调用栈过滤，可以直接委托给它输出默认报告
+///
+/// # Example
+/// ```
+/// use myutil::error::ErrorFormatter;
+///
+/// /// 只输出一行错误链
+/// struct OneLine;
+///
+/// impl ErrorFormatter for OneLine {
+///     fn debug(
+///         &self,
+///         error: &(dyn std::error::Error + 'static),
+///         _default: &dyn eyre::EyreHandler,
+///         f: &mut std::fmt::Formatter<'_>,
+///     ) -> std::fmt::Result {
+///         write!(f, "{error}")?;
+///         let mut source = error.source();
+///         while let Some(e) = source {
+///             write!(f, ": {e}")?;
+///             source = e.source();
+///         }
+///         Ok(())
+///     }
+/// }
+///
+/// myutil::error::init_error_hook_with("my_app", Box::new(OneLine));
+/// ```
+pub trait ErrorFormatter: Send + Sync + 'static {
+    fn debug(
+        &self,
+        error: &(dyn std::error::Error + 'static),
+        default: &dyn eyre::EyreHandler,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result;
+}
+
+/// 默认的`ErrorFormatter`，即 color_eyre 的报告，`set_error_verbosity(false)`时只输出错误链
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ColorEyreFormatter;
+
+impl ErrorFormatter for ColorEyreFormatter {
+    fn debug(
+        &self,
+        error: &(dyn std::error::Error + 'static),
+        default: &dyn eyre::EyreHandler,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        if error_verbosity() {
+            default.debug(error, f)
+        } else {
+            write!(f, "{}", TerseReport(error))
+        }
+    }
+}
+
+/// 包装 color_eyre 的 handler，`{:?}`交给 formatter 输出
+struct Handler {
+    formatter: std::sync::Arc<dyn ErrorFormatter>,
+    inner: Box<dyn eyre::EyreHandler>,
+    /// `with_backtrace`等追加的内容，详细模式下输出在报告之后
+    sections: Vec<String>,
+}
+
+impl Handler {
+    fn write_report(&self, error: &(dyn std::error::Error + 'static), f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.formatter.debug(error, self.inner.as_ref(), f)?;
+        if error_verbosity() {
+            for section in &self.sections {
+                write!(f, "\n\n{section}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 先把完整的报告写到字符串中，用于合并重复的调用帧
+struct RenderedReport<'a>(&'a Handler, &'a (dyn std::error::Error + 'static));
+
+impl std::fmt::Debug for RenderedReport<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.write_report(self.1, f)
+    }
+}
+
+impl eyre::EyreHandler for Handler {
+    fn debug(&self, error: &(dyn std::error::Error + 'static), f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !error_verbosity() || !frames::collapse_frames_enabled() {
+            return self.write_report(error, f);
+        }
+        let report = RenderedReport(self, error);
+        let report = if f.alternate() { format!("{report:#?}") } else { format!("{report:?}") };
+        f.write_str(&frames::collapse_repeated_frames(&report))
+    }
+
+    fn display(&self, error: &(dyn std::error::Error + 'static), f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.inner.display(error, f)
+    }
+
+    fn track_caller(&mut self, location: &'static std::panic::Location<'static>) {
+        self.inner.track_caller(location)
+    }
+}
+
+/// 简洁模式的错误报告：错误消息 + 编号的来源链
+struct TerseReport<'a>(&'a (dyn std::error::Error + 'static));
+
+impl std::fmt::Display for TerseReport<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0)?;
+
+        let mut sources = std::iter::successors(self.0.source(), |e| e.source()).peekable();
+        if sources.peek().is_some() {
+            write!(f, "\n\nCaused by:")?;
+            for (i, source) in sources.enumerate() {
+                write!(f, "\n{i:>4}: {source}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 取出 panic 携带的消息
+pub(crate) fn panic_message(payload: &(dyn std::any::Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "Box<dyn Any>"
+    }
+}
+
+/// 创建一个新的错误，可通过`with_source`把已有的`Report`作为来源挂到调用链上
+///
+/// 跨错误域转换时使用，父错误的调用链和 backtrace 都会被保留，`err.chain()`依次输出新消息和父错误链
+///
+/// # Example
+/// ```
+/// use myutil::error::new_error;
+///
+/// let parent = eyre::eyre!("connection refused");
+/// let err = new_error("failed to load config").with_source(parent);
+/// assert_eq!(err.chain().count(), 2);
+/// ```
+pub fn new_error<M>(msg: M) -> NewError<M>
+    where
+        M: std::fmt::Display + std::fmt::Debug + Send + Sync + 'static,
+{
+    NewError { msg }
+}
+
+/// `new_error`返回的构造器
+pub struct NewError<M> {
+    msg: M,
+}
+
+impl<M> NewError<M>
+    where
+        M: std::fmt::Display + std::fmt::Debug + Send + Sync + 'static,
+{
+    /// 以`source`为来源创建错误，新消息位于调用链最外层
+    pub fn with_source(self, source: eyre::Report) -> eyre::Report {
+        // wrap_err 复用父错误的 handler，原 backtrace 不会丢失
+        source.wrap_err(self.msg)
+    }
+
+    /// 不带来源，直接创建顶层错误
+    pub fn into_report(self) -> eyre::Report {
+        eyre::Report::msg(self.msg)
+    }
+}
+
+impl<M> From<NewError<M>> for eyre::Report
+    where
+        M: std::fmt::Display + std::fmt::Debug + Send + Sync + 'static,
+{
+    fn from(err: NewError<M>) -> Self {
+        err.into_report()
+    }
+}
+
+/// 按编号逐行输出错误链，与 color_eyre 报告中的格式一致
+///
+/// ```text
+///    0: my error 3
+///    1: my error 2
+///    2: error: my error 1
+/// ```
+pub fn numbered_chain(err: &eyre::Report) -> String {
+    err.chain()
+        .enumerate()
+        .map(|(i, e)| format!("{i:>4}: {e}"))
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 #[cfg(test)]
 mod tests {
-    use eyre::{Report, Result, WrapErr};
+    use eyre::{Report, WrapErr};
     use crate::error::*;
 
     /// 在Rust中，如果你想要在`println!`宏中输出花括号字符"{}"，你可以使用双花括号"{{"和"}}"来转义它们。这是因为在`println!`宏中，花括号"{}"用于格式化输出，而"{"和"}"被认为是特殊字符。因此，如果你想要输出花括号字符本身，你需要将它们用双花括号包裹起来，如下所示：
     ///
     /// ```rust
-    /// fn main() {
-    ///     println!("Hello, {{}}"); // 输出: Hello, {}
-    /// }
+    /// println!("Hello, {{}}"); // 输出: Hello, {}
     /// ```
     ///
     /// 这样做会使得`println!`宏输出的文本中包含实际的花括号字符"{}"，而不会被解释为格式化输出的一部分。
@@ -58,27 +435,109 @@ mod tests {
     }
 
     fn my_err() -> Report {
-        let err = || -> Result<()> {
-            Err(eyre::eyre!("error: my error 1"))
-        }().context("my error 2").context("my error 3").unwrap_err();
-
-        err
+        Err::<(), _>(eyre::eyre!("error: my error 1")).context("my error 2").context("my error 3").unwrap_err()
     }
 
     #[test]
+    #[should_panic(expected = "panic: my error 3")]
     fn error_no_hook_test() {
         let err = my_err();
         print_error(&err);
         panic!("panic: {err:?}");
     }
 
+    #[test]
+    fn new_error_with_source_test() {
+        let err = new_error("my error 4").with_source(my_err());
+        let chain: Vec<String> = err.chain().map(|e| e.to_string()).collect();
+        assert_eq!(chain, ["my error 4", "my error 3", "my error 2", "error: my error 1"]);
+
+        let err: Report = new_error("my error 5").into();
+        assert_eq!(err.chain().count(), 1);
+    }
+
+    #[test]
+    fn numbered_chain_test() {
+        assert_eq!(numbered_chain(&my_err()), "   0: my error 3\n   1: my error 2\n   2: error: my error 1");
+    }
+
+    #[test]
+    fn terse_report_test() {
+        let err = my_err();
+        let error: &(dyn std::error::Error + 'static) = err.as_ref();
+        assert_eq!(
+            TerseReport(error).to_string(),
+            "my error 3\n\nCaused by:\n   0: my error 2\n   1: error: my error 1",
+        );
+        assert_eq!(TerseReport(eyre::eyre!("boom").as_ref()).to_string(), "boom");
+    }
+
+    #[test]
+    fn error_formatter_test() {
+        struct Custom;
+
+        impl ErrorFormatter for Custom {
+            fn debug(&self, error: &(dyn std::error::Error + 'static), default: &dyn eyre::EyreHandler, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "custom: {error} | ")?;
+                default.debug(error, f)
+            }
+        }
+
+        struct Default;
+
+        impl eyre::EyreHandler for Default {
+            fn debug(&self, error: &(dyn std::error::Error + 'static), f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                write!(f, "default: {error}")
+            }
+        }
+
+        struct Render(Handler, Report);
+
+        impl std::fmt::Display for Render {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                eyre::EyreHandler::debug(&self.0, self.1.as_ref(), f)
+            }
+        }
+
+        let handler = Handler { formatter: std::sync::Arc::new(Custom), inner: Box::new(Default), sections: Vec::new() };
+        assert_eq!(Render(handler, my_err()).to_string(), "custom: my error 3 | default: my error 3");
+
+        // with_backtrace 追加的内容输出在报告之后
+        let sections = vec!["Attached backtrace:\n   1: my_app::load".to_string()];
+        let handler = Handler { formatter: std::sync::Arc::new(Custom), inner: Box::new(Default), sections };
+        assert_eq!(
+            Render(handler, my_err()).to_string(),
+            "custom: my error 3 | default: my error 3\n\nAttached backtrace:\n   1: my_app::load",
+        );
+    }
+
+    #[test]
+    fn panic_message_test() {
+        assert_eq!(panic_message(&"static"), "static");
+        assert_eq!(panic_message(&"owned".to_string()), "owned");
+        assert_eq!(panic_message(&1), "Box<dyn Any>");
+    }
+
     #[test]
     fn error_hook_test() {
         let package_name = "myutil";
-        init_error_hook(package_name);
+        let output = with_error_hook_installed(package_name, || {
+            let err = my_err();
+            print_error(&err);
+            panic!("panic: {err:?}");
+        });
+        // 报告带颜色，逐段检查
+        for part in ["panic: ", "my error 3", "my error 2", "error: my error 1"] {
+            assert!(output.contains(part), "{output}");
+        }
+    }
 
-        let err = my_err();
-        print_error(&err);
-        panic!("panic: {err:?}");
+    #[test]
+    fn error_hook_installed_twice() {
+        let first = with_error_hook_installed("myutil", || panic!("first"));
+        let second = with_error_hook_installed("myutil", || panic!("second"));
+        assert!(first.contains("first") && !first.contains("second"), "{first}");
+        assert!(second.contains("second"), "{second}");
+        assert_eq!(with_error_hook_installed("myutil", || {}), "");
     }
 }
@@ -0,0 +1,54 @@
+use std::backtrace::Backtrace;
+
+use eyre::Report;
+
+use super::redact::filter_backtrace;
+use super::Handler;
+
+/// 为没有 backtrace 的错误补上当前位置的 backtrace
+pub trait BacktraceExt {
+    /// 在调用处捕获 backtrace，只保留以`package_names`之一开头的调用帧，附加到报告中
+    ///
+    /// 用于第一次处理来自依赖库、本身不带 backtrace 的错误。安装了`init_error_hook`时，
+    /// 附加的 backtrace 在详细模式（`set_error_verbosity(true)`）下输出在报告末尾；直接使用 color_eyre 时作为其 section 输出。
+    ///
+    /// # Example
+    /// ```
+    /// use myutil::error::BacktraceExt;
+    ///
+    /// fn read_config() -> eyre::Result<String> {
+    ///     std::fs::read_to_string("/no/such/file").map_err(|err| eyre::Report::new(err).with_backtrace(&["my_app"]))
+    /// }
+    /// ```
+    fn with_backtrace(self, package_names: &[&str]) -> Report;
+}
+
+impl BacktraceExt for Report {
+    fn with_backtrace(mut self, package_names: &[&str]) -> Report {
+        let section = attached_backtrace(&Backtrace::force_capture().to_string(), package_names);
+        if let Some(handler) = self.handler_mut().downcast_mut::<Handler>() {
+            handler.sections.push(section);
+            self
+        } else {
+            color_eyre::Section::section(self, section)
+        }
+    }
+}
+
+fn attached_backtrace(backtrace: &str, package_names: &[&str]) -> String {
+    filter_backtrace(&format!("Attached backtrace:\n{backtrace}"), package_names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::attached_backtrace;
+
+    #[test]
+    fn attached_frames_filtered() {
+        let backtrace = "   0: std::backtrace::Backtrace::force_capture\n             at /rustc/backtrace.rs:1\n   1: my_app::load\n             at src/load.rs:7\n   2: my_lib::run\n   3: core::ops::call";
+        assert_eq!(
+            attached_backtrace(backtrace, &["my_app", "my_lib"]),
+            "Attached backtrace:\n   1: my_app::load\n             at src/load.rs:7\n   2: my_lib::run",
+        );
+    }
+}
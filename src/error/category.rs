@@ -0,0 +1,105 @@
+use std::fmt::Display;
+
+/// 错误分类，用于统一决定错误的严重程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorCategory {
+    NotFound,
+    InvalidInput,
+    PermissionDenied,
+    Timeout,
+    Unavailable,
+    Internal,
+}
+
+impl ErrorCategory {
+    /// 创建一个带分类的错误
+    ///
+    /// # Example
+    /// ```
+    /// use myutil::error::ErrorCategory;
+    ///
+    /// let err = ErrorCategory::NotFound.error("user 42 not found");
+    /// assert_eq!(ErrorCategory::of(&err), ErrorCategory::NotFound);
+    /// ```
+    pub fn error(self, msg: impl Display) -> eyre::Report {
+        eyre::Report::new(CategorizedError { category: self, msg: msg.to_string() })
+    }
+
+    /// 获取错误的分类
+    ///
+    /// 依次查找调用链上`ErrorCategory::error`创建的错误、`std::io::Error`的`ErrorKind`，都没有则为`Internal`
+    pub fn of(err: &eyre::Report) -> Self {
+        err.chain()
+            .find_map(|e| {
+                if let Some(categorized) = e.downcast_ref::<CategorizedError>() {
+                    return Some(categorized.category);
+                }
+                e.downcast_ref::<std::io::Error>().and_then(|e| Self::from_io_kind(e.kind()))
+            })
+            .unwrap_or(ErrorCategory::Internal)
+    }
+
+    fn from_io_kind(kind: std::io::ErrorKind) -> Option<Self> {
+        use std::io::ErrorKind;
+
+        match kind {
+            ErrorKind::NotFound => Some(ErrorCategory::NotFound),
+            ErrorKind::InvalidInput | ErrorKind::InvalidData => Some(ErrorCategory::InvalidInput),
+            ErrorKind::PermissionDenied => Some(ErrorCategory::PermissionDenied),
+            ErrorKind::TimedOut | ErrorKind::WouldBlock => Some(ErrorCategory::Timeout),
+            ErrorKind::ConnectionRefused | ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted
+            | ErrorKind::NotConnected | ErrorKind::BrokenPipe => Some(ErrorCategory::Unavailable),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ErrorCategory::NotFound => "not_found",
+            ErrorCategory::InvalidInput => "invalid_input",
+            ErrorCategory::PermissionDenied => "permission_denied",
+            ErrorCategory::Timeout => "timeout",
+            ErrorCategory::Unavailable => "unavailable",
+            ErrorCategory::Internal => "internal",
+        }
+    }
+}
+
+impl Display for ErrorCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// `ErrorCategory::error`创建的错误，显示时只输出消息
+#[derive(Debug)]
+struct CategorizedError {
+    category: ErrorCategory,
+    msg: String,
+}
+
+impl Display for CategorizedError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.msg)
+    }
+}
+
+impl std::error::Error for CategorizedError {}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorCategory;
+
+    #[test]
+    fn category_of() {
+        let err = ErrorCategory::Timeout.error("db timeout").wrap_err("load user");
+        assert_eq!(ErrorCategory::of(&err), ErrorCategory::Timeout);
+        assert_eq!(err.root_cause().to_string(), "db timeout");
+
+        let io = std::io::Error::new(std::io::ErrorKind::NotFound, "config.toml");
+        let err = eyre::Report::new(io).wrap_err("read config");
+        assert_eq!(ErrorCategory::of(&err), ErrorCategory::NotFound);
+
+        assert_eq!(ErrorCategory::of(&eyre::eyre!("boom")), ErrorCategory::Internal);
+    }
+}
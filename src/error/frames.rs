@@ -0,0 +1,132 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+
+use regex::Regex;
+
+static COLLAPSE_FRAMES: AtomicBool = AtomicBool::new(false);
+
+/// 设置`init_error_hook`安装的 hook 是否把连续重复的调用帧合并为一帧，默认`false`
+///
+/// 递归函数的 backtrace 中同一帧会重复几十次，开启后在包名过滤之后，函数名、文件和行号都相同的连续帧只保留第一帧，
+/// 并在帧名后面标出次数，如`my_app::parse (x24)`；重复帧之间被过滤掉的帧不再单独显示。
+/// 与`set_error_verbosity`一样在每次生成报告时读取：
+///
+/// ```
+/// myutil::error::set_collapse_frames(true);
+/// ```
+pub fn set_collapse_frames(enabled: bool) {
+    COLLAPSE_FRAMES.store(enabled, Ordering::Relaxed);
+}
+
+/// 当前是否合并重复的调用帧
+pub(super) fn collapse_frames_enabled() -> bool {
+    COLLAPSE_FRAMES.load(Ordering::Relaxed)
+}
+
+/// 合并报告中连续重复的调用帧（帧行、`at ...`行及其后的源码行），保留颜色
+pub(super) fn collapse_repeated_frames(report: &str) -> String {
+    static ANSI: OnceLock<Regex> = OnceLock::new();
+    let ansi = ANSI.get_or_init(|| Regex::new(r"\x1b\[[0-9;]*m").expect("valid ansi regex"));
+
+    let lines: Vec<&str> = report.lines().collect();
+    let plain: Vec<String> = lines.iter().map(|line| ansi.replace_all(line, "").into_owned()).collect();
+    let mut collapsed = Vec::with_capacity(lines.len());
+    let mut i = 0;
+    while i < lines.len() {
+        let Some(key) = frame_key(&plain, i) else {
+            collapsed.push(lines[i].to_string());
+            i += 1;
+            continue;
+        };
+        let end = frame_end(&plain, i);
+        let mut count = 1;
+        let mut next = end;
+        loop {
+            let mut j = next;
+            while j < plain.len() && is_hidden_frames(&plain[j]) {
+                j += 1;
+            }
+            if frame_key(&plain, j).as_ref() != Some(&key) {
+                break;
+            }
+            count += 1;
+            next = frame_end(&plain, j);
+        }
+        if count > 1 {
+            collapsed.push(format!("{} (x{count})", lines[i]));
+        } else {
+            collapsed.push(lines[i].to_string());
+        }
+        collapsed.extend(lines[i + 1..end].iter().map(|line| line.to_string()));
+        i = next;
+    }
+    collapsed.join("\n")
+}
+
+fn frame_regex() -> &'static Regex {
+    static FRAME: OnceLock<Regex> = OnceLock::new();
+    FRAME.get_or_init(|| Regex::new(r"^\s*\d+: (.+)$").expect("valid frame regex"))
+}
+
+/// 第`i`行是调用帧时返回帧名和位置，用于判断是否重复；没有`at ...`行的（如错误链中的编号）不是调用帧
+fn frame_key(plain: &[String], i: usize) -> Option<String> {
+    let name = frame_regex().captures(plain.get(i)?)?.get(1)?.as_str().trim_end();
+    let location = plain.get(i + 1)?.trim();
+    location.starts_with("at ").then(|| format!("{name}\n{location}"))
+}
+
+/// 从第`i`行开始的调用帧之后的第一行
+fn frame_end(plain: &[String], i: usize) -> usize {
+    let mut end = i + 1;
+    while end < plain.len()
+        && !plain[end].trim().is_empty()
+        && !frame_regex().is_match(&plain[end])
+        && !is_hidden_frames(&plain[end]) {
+        end += 1;
+    }
+    end
+}
+
+/// color_eyre 表示被过滤掉的帧的行，如`⋮ 3 frames hidden ⋮`
+fn is_hidden_frames(line: &str) -> bool {
+    line.trim_start().starts_with('⋮')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::collapse_repeated_frames;
+
+    #[test]
+    fn recursive_frames_collapsed() {
+        let report = concat!(
+            "parse failed\n\n",
+            "  ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━ BACKTRACE ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n",
+            "   3: my_app::parse::h0123456789abcdef\n      at src/parse.rs:10\n",
+            "                                ⋮ 1 frame hidden ⋮                                \n",
+            "   5: my_app::parse::h0123456789abcdef\n      at src/parse.rs:10\n",
+            "   6: my_app::parse::h0123456789abcdef\n      at src/parse.rs:10\n",
+            "   7: my_app::parse::h0123456789abcdef\n      at src/parse.rs:42\n",
+            "   8: my_app::main::h0123456789abcdef\n      at src/main.rs:3",
+        );
+        assert_eq!(
+            collapse_repeated_frames(report),
+            concat!(
+                "parse failed\n\n",
+                "  ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━ BACKTRACE ━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n",
+                "   3: my_app::parse::h0123456789abcdef (x3)\n      at src/parse.rs:10\n",
+                "   7: my_app::parse::h0123456789abcdef\n      at src/parse.rs:42\n",
+                "   8: my_app::main::h0123456789abcdef\n      at src/main.rs:3",
+            ),
+        );
+    }
+
+    #[test]
+    fn colors_and_chain_kept() {
+        let frame = "   1: \x1b[32mmy_app::walk\x1b[0m\n      at \x1b[35msrc/tree.rs\x1b[0m:\x1b[35m7\x1b[0m";
+        let report = format!("Caused by:\n   0: same\n   0: same\n\n{frame}\n{}", frame.replace("   1:", "   2:"));
+        assert_eq!(
+            collapse_repeated_frames(&report),
+            "Caused by:\n   0: same\n   0: same\n\n   1: \x1b[32mmy_app::walk\x1b[0m (x2)\n      at \x1b[35msrc/tree.rs\x1b[0m:\x1b[35m7\x1b[0m",
+        );
+    }
+}
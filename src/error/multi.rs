@@ -0,0 +1,89 @@
+use eyre::Report;
+
+/// `combine_errors`合并的多个错误
+///
+/// 消息的第一行为错误个数，之后每个错误一行，带有各自的错误链：
+///
+/// ```text
+/// 2 errors occurred:
+///    0: failed to import a.csv: file not found
+///    1: failed to import b.csv: invalid header
+/// ```
+#[derive(Debug)]
+pub struct MultiError {
+    errors: Vec<Report>,
+}
+
+impl MultiError {
+    /// 合并前的各个错误，顺序不变
+    pub fn errors(&self) -> &[Report] {
+        &self.errors
+    }
+
+    pub fn into_errors(self) -> Vec<Report> {
+        self.errors
+    }
+}
+
+impl std::fmt::Display for MultiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} errors occurred:", self.errors.len())?;
+        for (i, err) in self.errors.iter().enumerate() {
+            // 多行的消息缩进到编号之后
+            let message = format!("{err:#}").replace('\n', "\n      ");
+            write!(f, "\n{i:>4}: {message}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MultiError {}
+
+/// 把批量操作中互相独立的多个错误合并为一个返回，不在第一个错误处停止
+///
+/// 只有一个错误时原样返回；否则返回`MultiError`，可以通过`downcast_ref::<MultiError>()`取回各个错误。
+/// 安装了`init_error_hook`时报告中逐行列出每个错误及其错误链。
+///
+/// # Example
+/// ```
+/// use eyre::WrapErr;
+/// use myutil::error::{combine_errors, MultiError};
+///
+/// let errors: Vec<eyre::Report> = ["a.csv", "b.csv"]
+///     .into_iter()
+///     .filter_map(|file| std::fs::read(file).wrap_err(format!("failed to import {file}")).err())
+///     .collect();
+/// let err = combine_errors(errors);
+/// assert!(err.to_string().starts_with("2 errors occurred:"));
+/// assert_eq!(err.downcast_ref::<MultiError>().unwrap().errors().len(), 2);
+/// ```
+pub fn combine_errors(mut errors: Vec<Report>) -> Report {
+    if errors.len() == 1 {
+        return errors.remove(0);
+    }
+    Report::new(MultiError { errors })
+}
+
+#[cfg(test)]
+mod tests {
+    use eyre::WrapErr;
+
+    use crate::error::{combine_errors, MultiError};
+
+    #[test]
+    fn combined_message() {
+        let first = Err::<(), _>(eyre::eyre!("file not found")).wrap_err("failed to import a.csv").unwrap_err();
+        let second = eyre::eyre!("line 1\nline 2");
+        let err = combine_errors(vec![first, second]);
+        assert_eq!(
+            err.to_string(),
+            "2 errors occurred:\n   0: failed to import a.csv: file not found\n   1: line 1\n      line 2",
+        );
+        assert_eq!(err.downcast::<MultiError>().unwrap().into_errors().len(), 2);
+
+        let single = combine_errors(vec![eyre::eyre!("only")]);
+        assert!(single.downcast_ref::<MultiError>().is_none());
+        assert_eq!(single.to_string(), "only");
+        assert_eq!(combine_errors(Vec::new()).to_string(), "0 errors occurred:");
+    }
+}
@@ -0,0 +1,86 @@
+use std::sync::OnceLock;
+
+use eyre::Report;
+use regex::Regex;
+
+/// 渲染可以发给第三方的错误报告：错误链和 backtrace 中匹配`patterns`的部分替换为`***`
+///
+/// 报告与`{:?}`的输出相同（安装了`init_error_hook`时为其格式），去掉颜色，
+/// backtrace 中只保留以`package_name`开头的调用帧，`package_name=""`时保留全部。
+///
+/// # Example
+/// ```
+/// use regex::Regex;
+/// use myutil::error::redact_report;
+///
+/// let email = Regex::new(r"[\w.+-]+@[\w-]+\.[\w.]+").unwrap();
+/// let err = eyre::eyre!("cannot notify alice@example.com");
+/// let report = redact_report(&err, &[email], "my_app");
+/// assert!(report.starts_with("cannot notify ***"));
+/// ```
+pub fn redact_report(err: &Report, patterns: &[Regex], package_name: &str) -> String {
+    static ANSI: OnceLock<Regex> = OnceLock::new();
+    let ansi = ANSI.get_or_init(|| Regex::new(r"\x1b\[[0-9;]*m").expect("valid ansi regex"));
+
+    let rendered = format!("{err:?}");
+    let plain = ansi.replace_all(&rendered, "");
+    let mut report = filter_backtrace(&plain, &[package_name]);
+    for pattern in patterns {
+        report = pattern.replace_all(&report, "***").into_owned();
+    }
+    report
+}
+
+/// 只保留 backtrace 部分中以`package_names`之一开头的调用帧（帧行及其后的`at ...`行）
+pub(super) fn filter_backtrace(report: &str, package_names: &[&str]) -> String {
+    static FRAME: OnceLock<Regex> = OnceLock::new();
+    let frame = FRAME.get_or_init(|| Regex::new(r"^\s*\d+: (\S+)").expect("valid frame regex"));
+
+    let mut in_backtrace = false;
+    let mut keep_frame = true;
+    let mut lines = Vec::new();
+    for line in report.lines() {
+        let captures = frame.captures(line);
+        // 帧名和源码位置本身可能含有 backtrace（如 std::backtrace::Backtrace::capture），不能当作标题
+        let is_location = line.trim_start().starts_with("at ");
+        if captures.is_none() && !is_location && line.to_ascii_lowercase().contains("backtrace") {
+            in_backtrace = true;
+            keep_frame = true;
+        } else if in_backtrace {
+            if let Some(captures) = captures {
+                keep_frame = package_names.iter().any(|name| captures[1].starts_with(name));
+            }
+            if !keep_frame {
+                continue;
+            }
+        }
+        lines.push(line);
+    }
+    lines.join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Regex;
+
+    use super::{filter_backtrace, redact_report};
+
+    #[test]
+    fn redact_chain() {
+        let token = Regex::new(r"token=\w+").unwrap();
+        let err = eyre::eyre!("token=abc123 rejected").wrap_err("login for bob failed");
+        let report = redact_report(&err, &[token, Regex::new("bob").unwrap()], "");
+        assert!(report.starts_with("login for *** failed"), "{report}");
+        assert!(report.contains("*** rejected"), "{report}");
+        assert!(!report.contains("abc123") && !report.contains("bob"), "{report}");
+    }
+
+    #[test]
+    fn backtrace_frames_filtered() {
+        let report = "boom\n\nCaused by:\n   0: inner\n\nStack backtrace:\n   0: std::rt::lang_start\n             at /rustc/rt.rs:1\n   1: my_app::run\n             at src/main.rs:10\n   2: core::ops::call";
+        assert_eq!(
+            filter_backtrace(report, &["my_app"]),
+            "boom\n\nCaused by:\n   0: inner\n\nStack backtrace:\n   1: my_app::run\n             at src/main.rs:10",
+        );
+    }
+}
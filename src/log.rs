@@ -1,134 +1,741 @@
+use std::borrow::Cow;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+use nu_ansi_term::{Color, Style};
+use tracing::instrument::WithSubscriber;
 use tracing_core::{Event, Subscriber};
 use tracing_log::AsLog;
-use tracing_subscriber::fmt::{FmtContext, format, FormatEvent, FormatFields, FormattedFields};
-use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::fmt::{FmtContext, format, FormatEvent, FormatFields, FormattedFields, MakeWriter};
+use tracing_subscriber::fmt::format::DefaultFields;
+use tracing_subscriber::EnvFilter;
 use tracing_subscriber::registry::LookupSpan;
 
+use crate::log::init_report::Installed;
+
+pub use access::{ClfEntry, log_clf, log_combined};
+pub use capture::{assert_log_sequence, capture_logs, capture_target, CaptureLayer, CaptureLines, LogCapture};
+pub use config::{EventFormatter, LogConfig, LogFormat, LogSubscriber};
+pub use context::{context_window_layer, ContextWindowLayer, flush_context_window};
+pub use crashfile::{crash_log_path, init_log_crashfile};
+pub use environment::log_environment;
+pub use fields::{FieldFilter, FilteredVisitor};
+pub use filter::{sensible_filter, set_rust_log_hint};
+pub use flush::FlushLayer;
+pub use guard::{InitLogError, LogGuard, TryInitError};
+pub use heartbeat::{HeartbeatHandle, start_heartbeat};
+pub use id::batch_span;
+pub use init_report::{init_report, InitReport};
+pub use json::{JsonFields, JsonFormatter, span_timing_layer, SpanTimingLayer, TimestampFormat};
+pub use keyed::{keyed_file_layer, KeyedFileLayer, KeyedFilesGuard};
+pub use level::{level_to_string, parse_level, ParseLevelError};
+pub use minimal::MinimalSubscriber;
+pub use mirror::{add_mirror_layer, BoxedMirrorLayer, MirrorHandle, MirrorLayer, remove_mirror_layer};
+pub use non_blocking::{DEFAULT_BUFFERED_LINES_LIMIT, non_blocking, non_blocking_with_capacity, NonBlocking, WorkerGuard};
+pub use otlp::{otlp_json_layer, OtlpJsonLayer};
+pub use outcome::{record_span_outcome, span_outcome_layer, SpanOutcomeLayer};
+pub use parent::{parent_pid, PARENT_PID_ENV};
+pub use progress::{progress, ProgressStdout, ProgressStdoutWriter};
+#[cfg(feature = "error")]
+pub use report::{category_level, ffi_guard, http_error_body, log_boundary, log_error_auto, log_error_with_hint, main_scope};
+pub use reload::{LogReloadError, LogReloadHandle};
+pub use request_id::{current_request_id, request_id_layer, RequestIdLayer};
+pub use rolling::{init_log_to_file, RollingFileAppender, Rotation};
+#[cfg(feature = "error")]
+pub use route::ErrorRoutes;
+pub use sampling::{init_log_sampled_by_level, sampling_layer, SamplingLayer};
+pub use stats::{start_periodic_stats, stats_layer, StatsLayer};
+pub use strict::{error_count, exit_if_errors, strict_layer, StrictLayer};
+pub use summary::{error_summary, error_summary_layer, ErrorSummaryLayer, record_error_message};
+#[cfg(feature = "syslog")]
+pub use syslog::{SyslogFacility, SyslogFormatter, SyslogMessage, SyslogWriter};
+pub use tap::{log_caller, log_with_location, LogTap};
+#[cfg(feature = "error")]
+pub use thread::spawn_logged;
+pub use time::{TimeSource, TimeSourceTimer, uptime_ms, WithUptime};
+pub use timing::{log_slow_query, log_slow_query_redacted, PhaseTimer, Timed};
+#[cfg(feature = "vector")]
+pub use vector::{init_log_vector, VectorFormatter, VectorWriter};
+pub use write_error::{OnWriteError, OnWriteErrorWriter, WriteErrorPolicy};
+
+/// 以运行时确定的级别输出事件，tracing 的宏只接受常量级别
+macro_rules! event_at {
+    ($level:expr, $($arg:tt)+) => {
+        match $level {
+            tracing::Level::ERROR => tracing::error!($($arg)+),
+            tracing::Level::WARN => tracing::warn!($($arg)+),
+            tracing::Level::INFO => tracing::info!($($arg)+),
+            tracing::Level::DEBUG => tracing::debug!($($arg)+),
+            tracing::Level::TRACE => tracing::trace!($($arg)+),
+        }
+    };
+}
+
+/// 只在 debug 级别启用时才求值的字段，用于请求体这类体积大、计算贵的字段
+///
+/// 先检查调用处模块的 debug 级别是否启用，未启用时不会对表达式求值，字段也不会输出。
+/// tracing 的字段名不能由宏生成，所以字段名写在宏外面，值以`Debug`格式记录：
+/// 写成`name = debug_field!(expr)`，而不是`debug_field!(name, expr)`，后者会在编译时报错并提示正确的写法。
+///
+/// # Example
+/// ```
+/// fn dump_body() -> String {
+///     "...".repeat(1000)
+/// }
+///
+/// tracing::info!(body = myutil::debug_field!(dump_body()), "request received");
+/// ```
+#[macro_export]
+macro_rules! debug_field {
+    ($name:ident, $value:expr) => {
+        compile_error!(concat!(
+            "debug_field! only takes the value, write `", stringify!($name), " = debug_field!(",
+            stringify!($value), ")` inside the event"
+        ))
+    };
+    ($value:expr) => {
+        if tracing::enabled!(tracing::Level::DEBUG) {
+            Some(tracing::field::debug($value))
+        } else {
+            None
+        }
+    };
+}
+
+mod access;
+mod capture;
+mod config;
+mod context;
+mod crashfile;
+mod environment;
+mod fields;
+mod filter;
+mod flush;
+mod guard;
+mod heartbeat;
+mod id;
+mod init_report;
+mod json;
+mod keyed;
+mod level;
+mod minimal;
+mod mirror;
+mod non_blocking;
+mod otlp;
+mod outcome;
+mod parent;
+mod progress;
+#[cfg(feature = "error")]
+mod report;
+mod reload;
+mod request_id;
+mod rolling;
+#[cfg(feature = "error")]
+mod route;
+mod sampling;
+mod stats;
+mod strict;
+mod summary;
+#[cfg(feature = "syslog")]
+mod syslog;
+mod tap;
+#[cfg(feature = "error")]
+mod thread;
+mod time;
+mod timing;
+#[cfg(feature = "vector")]
+mod vector;
+mod write_error;
+
 pub enum LogMode {
     Original,
     Simple,
     General,
     Full,
     Custom,
+    /// 每行一个 JSON 对象，用于 Loki、CloudWatch 等日志聚合，见`JsonFormatter`
+    Json,
+    /// 不经过`tracing_subscriber`的 fmt，只按最大级别输出`LEVEL target: message`，见`MinimalSubscriber`
+    Minimal,
+    /// 通过本地 socket（`/dev/log`）发给 syslog 守护进程，不输出到 stdout，格式见`SyslogFormatter`；仅支持 Unix
+    ///
+    /// 无法连接 syslog 时`init_log`返回`InitLogError::Io`，调用方可以改用其它模式
+    #[cfg(feature = "syslog")]
+    Syslog {
+        facility: SyslogFacility,
+        /// 程序名，如`my-app`
+        ident: String,
+    },
+}
+
+impl LogMode {
+    /// 小写的模式名，用于`init_report`
+    fn name(&self) -> &'static str {
+        match self {
+            LogMode::Original => "original",
+            LogMode::Simple => "simple",
+            LogMode::General => "general",
+            LogMode::Full => "full",
+            LogMode::Custom => "custom",
+            LogMode::Json => "json",
+            LogMode::Minimal => "minimal",
+            #[cfg(feature = "syslog")]
+            LogMode::Syslog { .. } => "syslog",
+        }
+    }
+
+    /// 输出位置，用于`init_report`
+    fn writer_name(&self) -> &'static str {
+        match self {
+            #[cfg(feature = "syslog")]
+            LogMode::Syslog { .. } => "syslog",
+            _ => "stdout",
+        }
+    }
+}
+
+/// 初始化全局日志，返回的`LogGuard`需要保存到程序结束
+///
+/// 已经初始化过时返回错误，由调用方决定`unwrap`、记录还是忽略；沿用以前的 panic 行为可使用`init_log_or_panic`
+///
+/// # Example
+/// ```
+/// use myutil::log::{init_log, LogMode};
+///
+/// let _guard = init_log(LogMode::General, tracing::Level::INFO).expect("Failed to initialize log");
+/// ```
+pub fn init_log(log_mode: LogMode, log_level: tracing::Level) -> Result<LogGuard, InitLogError> {
+    let filter = default_filter(&log_mode, log_level);
+    init_log_with_filter(log_mode, filter)
+}
+
+/// 与`init_log`相同，但失败时 panic，守卫一直保留到进程结束
+pub fn init_log_or_panic(log_mode: LogMode, log_level: tracing::Level) {
+    let guard = init_log(log_mode, log_level).unwrap_or_else(|err| panic!("{err}"));
+    std::mem::forget(guard);
+}
+
+/// 与`init_log`相同，但已经设置过全局 subscriber 时直接返回`Ok(())`，可以重复调用
+///
+/// 适合同一个二进制中的多个`#[test]`各自初始化：只有第一次生效，之后的调用不改变已有的设置。
+/// 守卫一直保留到进程结束。已经设置过标准库`log`的 logger 等其它问题仍然返回错误。
+///
+/// # Example
+/// ```
+/// use myutil::log::{try_init_log, LogMode};
+///
+/// try_init_log(LogMode::General, tracing::Level::DEBUG).unwrap();
+/// try_init_log(LogMode::Json, tracing::Level::INFO).unwrap(); // 不生效，仍然是 General
+/// ```
+pub fn try_init_log(log_mode: LogMode, log_level: tracing::Level) -> Result<(), TryInitError> {
+    if tracing::dispatcher::has_been_set() {
+        return Ok(());
+    }
+    match init_log(log_mode, log_level) {
+        Ok(guard) => {
+            std::mem::forget(guard);
+            Ok(())
+        }
+        // 其它线程在检查之后抢先设置了
+        Err(InitLogError::Subscriber(_)) => Ok(()),
+        Err(err) => Err(TryInitError(err)),
+    }
 }
 
-pub fn init_log(log_mode: LogMode, log_level: tracing::Level) {
+fn default_filter(log_mode: &LogMode, log_level: tracing::Level) -> EnvFilter {
     match log_mode {
-        LogMode::Original => {
-            init_log_original(log_level);
-            return;
+        LogMode::Full => {
+            filter::hint_missing_rust_log(log_level);
+            EnvFilter::from_default_env().add_directive(log_level.into())
         }
-        LogMode::Simple => init_log_simple(log_level),
-        LogMode::General => init_log_general(log_level),
-        LogMode::Full => init_log_full(log_level),
-        LogMode::Custom => init_log_custom(log_level),
+        _ => EnvFilter::new(log_level.as_str()),
     }
+}
 
-    // 设置标准库 `log` 记录器，以便 `tracing` 可以接收 `log` 事件
-    // tracing_log::LogTracer::init().expect("Failed to set standard library logger");
-    tracing_log::LogTracer::builder()
-        .with_max_level(tracing_core::LevelFilter::current().as_log())
-        .init().expect("Failed to set standard library logger");
+/// 与`init_log`相同，但使用给定的过滤器，例如`sensible_filter`
+///
+/// # Example
+/// ```
+/// use myutil::log::{init_log_with_filter, LogMode, sensible_filter};
+///
+/// let _guard = init_log_with_filter(LogMode::General, sensible_filter("my_app", tracing::Level::DEBUG)).unwrap();
+/// ```
+pub fn init_log_with_filter(log_mode: LogMode, filter: EnvFilter) -> Result<LogGuard, InitLogError> {
+    init(log_mode, filter, None)
 }
 
-/// # runtime error:
+/// 与`init_log`相同，但从环境变量`var`（如`MYAPP_LOG`）读取过滤规则，没有设置或无法解析时使用`log_level`
+///
+/// # Example
 /// ```
-/// tracing_subscriber::fmt().init();
-/// tracing_log::LogTracer::init().expect("panic message");
-/// // tracing_subscriber::fmt().init() 内部已包含 tracing_log::LogTracer::init()，无需再次启动
-/// // 二者同时使用有冲突(使用tracing::subscriber::set_global_default()则没有问题)，运行时报错如下：
-/// // Message:  Unable to install global subscriber: SetLoggerError(())
+/// use myutil::log::{init_log_with_env_var, LogMode};
+///
+/// // MYAPP_LOG=my_app=debug,warn
+/// let _guard = init_log_with_env_var(LogMode::General, tracing::Level::INFO, "MYAPP_LOG").unwrap();
 /// ```
-fn init_log_original(log_level: tracing::Level) {
-    // tracing_subscriber::fmt::init(); //default Level::INFO
-    let _logger = tracing_subscriber::fmt()
-        .with_max_level(log_level)
-        .compact() //紧凑模式
-        // .pretty() //美观模式
-        .init();
+pub fn init_log_with_env_var(log_mode: LogMode, log_level: tracing::Level, var: &str) -> Result<LogGuard, InitLogError> {
+    let filter = filter::env_filter_or(var, EnvFilter::new(log_level.as_str()));
+    init_log_with_filter(log_mode, filter)
 }
 
-fn init_log_simple(log_level: tracing::Level) {
-    let subscriber = tracing_subscriber::FmtSubscriber::builder()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .with_max_level(log_level)
-        .with_writer(std::io::stdout)
-        .compact()
-        .finish();
-    tracing::subscriber::set_global_default(subscriber).expect("Could not set global default logger");
+/// 与`init_log`相同，但时间戳由`time_source`生成，见`TimeSource`
+///
+/// 只有带自定义时间格式的`LogMode::General`、`LogMode::Full`和`LogMode::Json`（作为`timestamp`字符串）使用它，其它模式不受影响
+pub fn init_log_with_time_source(
+    log_mode: LogMode,
+    log_level: tracing::Level,
+    time_source: impl TimeSource,
+) -> Result<LogGuard, InitLogError> {
+    let filter = default_filter(&log_mode, log_level);
+    init(log_mode, filter, Some(TimeSourceTimer::new(time_source)))
+}
+
+/// 与`init_log`相同，但输出到`make_writer`而不是 stdout，例如 stderr、管道或测试用的缓冲区
+///
+/// 各模式的格式不变，只改变输出位置
+///
+/// # Example
+/// ```
+/// use std::io::Write;
+/// use std::sync::{Arc, Mutex};
+/// use myutil::log::{init_log_with_writer, LogMode};
+///
+/// #[derive(Clone, Default)]
+/// struct Buffer(Arc<Mutex<Vec<u8>>>);
+///
+/// impl Write for Buffer {
+///     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+///         self.0.lock().unwrap().write(buf)
+///     }
+///
+///     fn flush(&mut self) -> std::io::Result<()> {
+///         Ok(())
+///     }
+/// }
+///
+/// let buffer = Buffer::default();
+/// let make_writer = buffer.clone();
+/// let _guard = init_log_with_writer(LogMode::Minimal, tracing::Level::INFO, move || make_writer.clone()).unwrap();
+/// tracing::info!("hello");
+/// assert_eq!(String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap(), "INFO rust_out: hello\n");
+/// ```
+pub fn init_log_with_writer<W>(log_mode: LogMode, log_level: tracing::Level, make_writer: W) -> Result<LogGuard, InitLogError>
+    where
+        W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let filter = default_filter(&log_mode, log_level);
+    match log_mode {
+        LogMode::Minimal => {
+            let installed = Installed { mode: LogMode::Minimal.name(), writer: "custom" };
+            install(Box::new(minimal_subscriber(filter, make_writer)), None, installed)
+        }
+        log_mode => LogConfig::for_mode(log_mode, filter, None).writer(make_writer).install(),
+    }
+}
+
+/// 与`init_log(LogMode::Custom, log_level)`相同，但事件由`formatter`格式化，代替默认的`CustomFormatter`
+///
+/// `formatter`需要实现`FormatEvent<LogSubscriber, DefaultFields>`，写法见`EventFormatter`。
+///
+/// # Example
+/// ```
+/// use tracing_subscriber::fmt::format::{Format, Full};
+/// use myutil::log::init_log_custom_with;
+///
+/// // tracing_subscriber 自带的格式也可以直接使用
+/// let _guard = init_log_custom_with(tracing::Level::INFO, Format::<Full>::default().without_time()).unwrap();
+/// tracing::info!("hello");
+/// ```
+pub fn init_log_custom_with<F>(log_level: tracing::Level, formatter: F) -> Result<LogGuard, InitLogError>
+    where
+        F: FormatEvent<LogSubscriber, DefaultFields> + Send + Sync + 'static,
+{
+    let filter = default_filter(&LogMode::Custom, log_level);
+    LogConfig::for_mode(LogMode::Custom, filter, None)
+        .format(LogFormat::Event(EventFormatter::new(formatter)))
+        .install()
+}
+
+/// 按`log_mode`创建 subscriber，但不设置为全局默认，用于`tracing::subscriber::with_default`等局部场景
+///
+/// 不会与已经设置的全局 subscriber 冲突，适合库、插件和测试；格式和过滤规则与`init_log`相同。
+/// 不接收标准库`log`的事件，需要时由调用方自行设置`tracing_log::LogTracer`。
+/// 无法连接 syslog 时改为`LogMode::Simple`输出到 stdout，并记录在`init_report`的`fallbacks`中。
+///
+/// # Example
+/// ```
+/// use myutil::log::{build_subscriber, LogMode};
+///
+/// let subscriber = build_subscriber(LogMode::General, tracing::Level::DEBUG);
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::debug!("only inside this closure");
+/// });
+/// ```
+pub fn build_subscriber(log_mode: LogMode, log_level: tracing::Level) -> Box<dyn Subscriber + Send + Sync> {
+    let filter = default_filter(&log_mode, log_level);
+    match build(log_mode, filter, None) {
+        Ok((subscriber, _)) => subscriber,
+        Err(err) => {
+            init_report::record_fallback(format!("{err}, logging to stdout instead"));
+            let filter = default_filter(&LogMode::Simple, log_level);
+            LogConfig::for_mode(LogMode::Simple, filter, None).build()
+        }
+    }
 }
 
-/// # tracing: local time print `<unknown time>`
+/// 在`fut`执行期间（包括每次被唤醒继续执行时）以`subscriber`为默认 subscriber，返回`fut`的结果
 ///
-/// tracing_subscriber 版本 0.3.* 中使用`time`输出自定义时间时错误打印`<unknown time>`，使用`chrono`则无此问题。
+/// 用于把某个任务的日志单独输出，例如后台作业写到自己的文件，不影响进程中其它任务和全局 subscriber。
+/// 任务中创建的 span 也属于`subscriber`；`tokio::spawn`等在任务内部再派生的任务需要各自包装。
 ///
-/// [subscriber: don't bail when timestamp formatting fails #1689](https://github.com/tokio-rs/tracing/pull/1689)
+/// # Example
+/// ```
+/// use myutil::log::{build_subscriber, LogMode, run_with_subscriber};
 ///
-/// [tracing_subscriber : The log CAN NOT display the time correctly in the LINUX with tracing_subscriber::fmt().with_timer(LocalTime::rfc_3339()) #2715](https://github.com/tokio-rs/tracing/issues/2715)
+/// async fn nightly_job() {
+///     tracing::info!("only in the job's log");
+/// }
 ///
-/// [tracing_subscriber::fmt::time::LocalTime not working when multiple threads #2004](https://github.com/tokio-rs/tracing/issues/2004)
+/// let subscriber = build_subscriber(LogMode::Json, tracing::Level::INFO);
+/// let job = run_with_subscriber(subscriber, nightly_job());
+/// // 交给运行时执行，如 tokio::spawn(job)
+/// # drop(job);
+/// ```
+pub async fn run_with_subscriber<S, F>(subscriber: S, fut: F) -> F::Output
+    where
+        S: Into<tracing::Dispatch>,
+        F: std::future::Future,
+{
+    fut.with_subscriber(subscriber).await
+}
+
+/// 按模式创建 subscriber，`LogMode::Minimal`没有`LogReloadHandle`；只有`LogMode::Syslog`会因为无法连接而失败
 ///
-/// [unable to get LocalTime on OpenBSD #2764](https://github.com/tokio-rs/tracing/issues/2764)
-fn init_log_general(log_level: tracing::Level) {
-    // let timer = tracing_subscriber::fmt::time::ChronoLocal::default();
-    let timer = tracing_subscriber::fmt::time::ChronoLocal::new("%Y-%m-%d %H:%M:%S%.3f %z".to_string());
+/// `timer`为`None`时使用各模式默认的时间格式
+fn build(
+    log_mode: LogMode,
+    filter: EnvFilter,
+    timer: Option<TimeSourceTimer>,
+) -> Result<(Box<dyn Subscriber + Send + Sync>, Option<LogReloadHandle>), InitLogError> {
+    let config = match log_mode {
+        LogMode::Minimal => return Ok((Box::new(minimal_subscriber(filter, std::io::stdout)), None)),
+        #[cfg(feature = "syslog")]
+        log_mode @ LogMode::Syslog { .. } => {
+            let writer = SyslogWriter::connect()?;
+            LogConfig::for_mode(log_mode, filter, timer).writer(writer)
+        }
+        log_mode => LogConfig::for_mode(log_mode, filter, timer),
+    };
+    let (subscriber, handle) = config.build_with_reload();
+    Ok((subscriber, Some(handle)))
+}
+
+fn init(log_mode: LogMode, filter: EnvFilter, timer: Option<TimeSourceTimer>) -> Result<LogGuard, InitLogError> {
+    let installed = Installed { mode: log_mode.name(), writer: log_mode.writer_name() };
+    let (subscriber, reload) = build(log_mode, filter, timer)?;
+    install(subscriber, reload, installed)
+}
+
+/// 设置为全局默认 subscriber，并接收标准库`log`的事件，结果记录到`init_report`
+fn install(
+    subscriber: Box<dyn Subscriber + Send + Sync>,
+    reload: Option<LogReloadHandle>,
+    installed: Installed,
+) -> Result<LogGuard, InitLogError> {
+    // 不使用 tracing_subscriber 的 .init()：它失败时 panic，且内部已包含 tracing_log::LogTracer::init()，
+    // 再次调用 LogTracer::init() 会报错 SetLoggerError(())
+    time::mark_start();
+    tracing::subscriber::set_global_default(subscriber)?;
+    init_report::record_installed(installed, reload.clone());
+    init_log_tracer()?;
+    init_report::record_log_bridge();
+    Ok(LogGuard::new(reload))
+}
+
+/// 只使用过滤器的最大级别
+fn minimal_subscriber<W>(filter: EnvFilter, make_writer: W) -> MinimalSubscriber<W>
+    where
+        W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let max_level = filter.max_level_hint().unwrap_or(tracing_core::LevelFilter::TRACE);
+    MinimalSubscriber::with_writer(max_level, make_writer)
+}
+
+fn init_log_tracer() -> Result<(), tracing_log::log::SetLoggerError> {
+    // 设置标准库 `log` 记录器，以便 `tracing` 可以接收 `log` 事件
+    // tracing_log::LogTracer::init().expect("Failed to set standard library logger");
+    tracing_log::LogTracer::builder()
+        .with_max_level(tracing_core::LevelFilter::current().as_log())
+        .init()
+}
 
-    let subscriber = tracing_subscriber::FmtSubscriber::builder()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .with_max_level(log_level)
-        .with_writer(std::io::stdout)
-        .with_target(true)
-        .with_line_number(true)
-        .with_timer(timer)
-        .compact()
-        .finish();
-    tracing::subscriber::set_global_default(subscriber).expect("Could not set global default logger");
+/// `LogMode::Custom`使用的日志格式
+///
+/// 也可以直接用于`tracing_subscriber::fmt().event_format(...)`
+///
+/// # Example
+/// ```
+/// use myutil::log::CustomFormatter;
+///
+/// let subscriber = tracing_subscriber::fmt()
+///     .event_format(CustomFormatter::default().with_fields_before_message(true))
+///     .finish();
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info!(user = "alice", "login"); // INFO rust_out: filename=main.rs:9 -> user="alice" login
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct CustomFormatter {
+    max_filename_len: usize,
+    full_path: bool,
+    colorize_full_line: bool,
+    fields_before_message: bool,
+    event_id: bool,
+    escape_newlines: bool,
+    max_span_depth: Option<usize>,
+    field_filter: Option<FieldFilter>,
+    field_delimiter: FieldDelimiter,
+    level_width: LevelWidth,
+    uptime: bool,
+    thread_ids: bool,
+    thread_names: bool,
+    prefix: Option<EventPrefix>,
+    max_field_value_len: Option<usize>,
 }
 
-fn init_log_full(log_level: tracing::Level) {
-    // 创建一个Tracing的事件过滤器
-    let filter_layer = tracing_subscriber::EnvFilter::from_default_env().add_directive(log_level.into());
+/// `CustomFormatter::with_prefix`设置的前缀函数
+#[derive(Clone)]
+struct EventPrefix(Arc<dyn Fn() -> String + Send + Sync>);
 
-    // 创建一个自定义的时间戳格式器
-    // let timer = tracing_subscriber::fmt::time::ChronoLocal::default();
-    let timer = tracing_subscriber::fmt::time::ChronoLocal::new("%Y-%m-%d %H:%M:%S%.3f %z".to_string());
+impl std::fmt::Debug for EventPrefix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EventPrefix(..)")
+    }
+}
 
-    // 创建一个Tracing的格式化器，并设置时间戳格式器
-    let fmt_layer = tracing_subscriber::fmt::layer()
-        .with_thread_names(true)
-        .with_thread_ids(true)
-        .with_timer(timer)
-        // .without_time() //不显示时间
-        .pretty();
+impl EventPrefix {
+    /// 调用前缀函数，panic 时返回`None`，该条日志不输出前缀
+    fn get(&self) -> Option<String> {
+        std::panic::catch_unwind(AssertUnwindSafe(|| (self.0)())).ok()
+    }
+}
 
-    // 创建一个Tracing订阅器，并将格式化器和事件过滤器添加到其中
-    let collector = tracing_subscriber::registry()
-        .with(filter_layer)
-        .with(fmt_layer)
-        .with(tracing_error::ErrorLayer::default());
+/// `CustomFormatter`中级别的显示宽度
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LevelWidth {
+    /// `TRACE` `DEBUG` `INFO` `WARN` `ERROR`，默认
+    #[default]
+    Full,
+    /// `TRC` `DBG` `INF` `WRN` `ERR`
+    Short3,
+    /// `TRCE` `DEBG` `INFO` `WARN` `ERRO`
+    Short4,
+}
 
-    // 使用Tracing订阅器
-    tracing::subscriber::set_global_default(collector).expect("Could not set global default logger");
+impl LevelWidth {
+    fn format(self, level: &tracing::Level) -> &'static str {
+        match (self, *level) {
+            (LevelWidth::Full, level) => level.as_str(),
+            (LevelWidth::Short3, tracing::Level::TRACE) => "TRC",
+            (LevelWidth::Short3, tracing::Level::DEBUG) => "DBG",
+            (LevelWidth::Short3, tracing::Level::INFO) => "INF",
+            (LevelWidth::Short3, tracing::Level::WARN) => "WRN",
+            (LevelWidth::Short3, tracing::Level::ERROR) => "ERR",
+            (LevelWidth::Short4, tracing::Level::TRACE) => "TRCE",
+            (LevelWidth::Short4, tracing::Level::DEBUG) => "DEBG",
+            (LevelWidth::Short4, tracing::Level::INFO) => "INFO",
+            (LevelWidth::Short4, tracing::Level::WARN) => "WARN",
+            (LevelWidth::Short4, tracing::Level::ERROR) => "ERRO",
+        }
+    }
 }
 
-fn init_log_custom(log_level: tracing::Level) {
-    let subscriber = tracing_subscriber::FmtSubscriber::builder()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .with_max_level(log_level)
-        .with_writer(std::io::stdout)
-        // .with_target(true)
-        // .with_file(true)
-        // .with_line_number(true)
-        // .with_thread_names(true)
-        // .with_thread_ids(true)
-        // .compact()
-        // .pretty()
-        .event_format(CustomFormatter)
-        .finish();
-    tracing::subscriber::set_global_default(subscriber).expect("Could not set global default logger");
+/// `CustomFormatter`中消息和各字段之间的分隔符
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FieldDelimiter {
+    /// 空格，默认
+    #[default]
+    Space,
+    /// 制表符，方便`cut -f`、awk 和导入表格；消息和字段值中的制表符会被替换为`\\t`
+    Tab,
 }
 
-struct CustomFormatter;
+impl FieldDelimiter {
+    fn as_str(self) -> &'static str {
+        match self {
+            FieldDelimiter::Space => " ",
+            FieldDelimiter::Tab => "\t",
+        }
+    }
+}
+
+/// `CustomFormatter::default()`中文件名的最大字符数
+pub const DEFAULT_MAX_FILENAME_LEN: usize = 20;
+
+impl Default for CustomFormatter {
+    fn default() -> Self {
+        CustomFormatter::new(DEFAULT_MAX_FILENAME_LEN)
+    }
+}
+
+impl CustomFormatter {
+    /// 文件名最多输出`max_filename_len`个字符，`0`表示不截断；其它设置为默认值
+    ///
+    /// 通过`LogConfig::format(LogFormat::Custom(CustomFormatter::new(40)))`用于全局日志
+    pub fn new(max_filename_len: usize) -> Self {
+        CustomFormatter {
+            max_filename_len,
+            full_path: false,
+            colorize_full_line: false,
+            fields_before_message: false,
+            event_id: false,
+            escape_newlines: false,
+            max_span_depth: None,
+            field_filter: None,
+            field_delimiter: FieldDelimiter::default(),
+            level_width: LevelWidth::default(),
+            uptime: false,
+            thread_ids: false,
+            thread_names: false,
+            prefix: None,
+            max_field_value_len: None,
+        }
+    }
+
+    /// 输出`metadata.file()`的完整路径而不是文件名，如`filename=src/db/mod.rs:12`，同名的`mod.rs`较多时便于区分；
+    /// 默认`false`。完整路径不受`max_filename_len`截断。`init_log(LogMode::Custom, ..)`使用默认设置，需要时通过
+    /// `LogConfig::format(LogFormat::Custom(CustomFormatter::default().with_full_path(true)))`安装
+    pub fn with_full_path(mut self, full_path: bool) -> Self {
+        self.full_path = full_path;
+        self
+    }
+
+    /// 按级别给整行着色：trace/debug 暗淡、info 不变、warn 黄色、error 红色，繁忙的终端中更容易看到错误；默认`false`
+    ///
+    /// 只在 writer 输出颜色时生效（`LogConfig`在`NO_COLOR`或输出不是终端时自动关闭颜色），
+    /// 此时行内 span 名和字段名不再单独加粗或倾斜
+    pub fn with_colorize_full_line(mut self, colorize_full_line: bool) -> Self {
+        self.colorize_full_line = colorize_full_line;
+        self
+    }
+
+    /// 事件字段输出在消息之前，默认`false`即消息在前、字段在后
+    pub fn with_fields_before_message(mut self, fields_before_message: bool) -> Self {
+        self.fields_before_message = fields_before_message;
+        self
+    }
+
+    /// 每条日志前输出一个随机的 UUIDv4 作为`event_id`，供下游去重；默认关闭，关闭时不产生额外开销
+    pub fn with_event_id(mut self, event_id: bool) -> Self {
+        self.event_id = event_id;
+        self
+    }
+
+    /// 把消息和字段中的换行替换为`\\n`，保证每条日志只占一行，方便按行处理的工具；默认关闭
+    pub fn with_escape_newlines(mut self, escape_newlines: bool) -> Self {
+        self.escape_newlines = escape_newlines;
+        self
+    }
+
+    /// 最多输出的 span 层数，超出时保留最外层和最内层的 span，省略的层数用一个`...: `代替，如`root: ...: current: `；
+    /// 只保留一层时为`...: current: `。默认`None`不限制，`Some(0)`同样视为不限制，不会把 span 全部省略
+    pub fn with_max_span_depth(mut self, max_span_depth: Option<usize>) -> Self {
+        self.max_span_depth = max_span_depth;
+        self
+    }
+
+    /// 按`FieldFilter`筛选事件字段；默认不筛选
+    pub fn with_field_filter(mut self, field_filter: FieldFilter) -> Self {
+        self.field_filter = Some(field_filter);
+        self
+    }
+
+    /// 消息和字段之间的分隔符，默认空格
+    pub fn with_field_delimiter(mut self, field_delimiter: FieldDelimiter) -> Self {
+        self.field_delimiter = field_delimiter;
+        self
+    }
+
+    /// 级别的显示宽度，窄终端可以用`Short3`/`Short4`；默认`Full`
+    pub fn with_level_width(mut self, level_width: LevelWidth) -> Self {
+        self.level_width = level_width;
+        self
+    }
+
+    /// 每条日志前输出初始化以来的毫秒数`+123456ms`（`uptime_ms`）；默认关闭，关闭时不产生额外开销
+    pub fn with_uptime(mut self, uptime: bool) -> Self {
+        self.uptime = uptime;
+        self
+    }
+
+    /// 在级别之后输出当前线程的名字，没有名字的线程输出`<unnamed>`，方便关联多线程的日志；默认关闭
+    pub fn with_thread_names(mut self, thread_names: bool) -> Self {
+        self.thread_names = thread_names;
+        self
+    }
+
+    /// 在级别（和线程名）之后输出当前线程的 id，如`ThreadId(2)`，线程没有名字或重名时也能区分；默认关闭
+    pub fn with_thread_ids(mut self, thread_ids: bool) -> Self {
+        self.thread_ids = thread_ids;
+        self
+    }
+
+    /// 每条日志输出时调用`prefix`，结果输出在行首，例如当前处理的分区`shard=3`；默认不输出
+    ///
+    /// `prefix`可以读取运行时变化的状态，panic 时该条日志省略前缀（panic hook 仍会输出 panic 信息）
+    ///
+    /// # Example
+    /// ```
+    /// use std::sync::Arc;
+    /// use std::sync::atomic::{AtomicU32, Ordering};
+    /// use myutil::log::CustomFormatter;
+    ///
+    /// static SHARD: AtomicU32 = AtomicU32::new(3);
+    ///
+    /// let formatter = CustomFormatter::default().with_prefix(Arc::new(|| format!("shard={}", SHARD.load(Ordering::Relaxed))));
+    /// let subscriber = tracing_subscriber::fmt().event_format(formatter).finish();
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     tracing::info!("processing"); // shard=3 INFO rust_out: filename=main.rs:12 -> processing
+    /// });
+    /// ```
+    pub fn with_prefix(mut self, prefix: Arc<dyn Fn() -> String + Send + Sync>) -> Self {
+        self.prefix = Some(EventPrefix(prefix));
+        self
+    }
+
+    /// 事件字段的值最多输出`max_field_value_len`个字符，超出时截断并追加`…`，例如限制很大的`body`字段，其它字段保持完整；
+    /// 默认`None`即不截断。只作用于事件字段，不截断消息；字符串的值按`Debug`格式计算，包含引号。
+    pub fn with_max_field_value_len(mut self, max_field_value_len: Option<usize>) -> Self {
+        self.max_field_value_len = max_field_value_len;
+        self
+    }
+
+    /// `with_colorize_full_line`使用的颜色
+    fn line_style(level: &tracing::Level) -> Style {
+        match *level {
+            tracing::Level::TRACE | tracing::Level::DEBUG => Style::new().dimmed(),
+            tracing::Level::INFO => Style::new(),
+            tracing::Level::WARN => Color::Yellow.normal(),
+            tracing::Level::ERROR => Color::Red.normal(),
+        }
+    }
+
+    fn escape<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        let newlines = self.escape_newlines && s.contains(['\r', '\n']);
+        let tabs = self.field_delimiter == FieldDelimiter::Tab && s.contains('\t');
+        if !newlines && !tabs {
+            return Cow::Borrowed(s);
+        }
+        let mut s = s.to_string();
+        if newlines {
+            s = s.replace('\r', "\\r").replace('\n', "\\n");
+        }
+        if tabs {
+            s = s.replace('\t', "\\t");
+        }
+        Cow::Owned(s)
+    }
+}
 
 /// 自定义 tracing 日志输出格式：
 /// https://docs.rs/tracing-subscriber/latest/tracing_subscriber/fmt/trait.FormatEvent.html
@@ -143,24 +750,101 @@ impl<S, N> FormatEvent<S, N> for CustomFormatter
         mut writer: format::Writer<'_>,
         event: &Event<'_>,
     ) -> std::fmt::Result {
+        if self.colorize_full_line && writer.has_ansi_escapes() {
+            // 先不带颜色写出整行，行内的样式会在中途重置颜色
+            let mut line = String::new();
+            self.format_line(ctx, format::Writer::new(&mut line), event)?;
+            let line = line.strip_suffix('\n').unwrap_or(&line);
+            return writeln!(writer, "{}", Self::line_style(event.metadata().level()).paint(line));
+        }
+        self.format_line(ctx, writer, event)
+    }
+}
+
+impl CustomFormatter {
+    /// 输出一条日志，包括末尾的换行
+    fn format_line<S, N>(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: format::Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result
+        where
+            S: Subscriber + for<'a> LookupSpan<'a>,
+            N: for<'a> FormatFields<'a> + 'static,
+    {
+        if let Some(prefix) = self.prefix.as_ref().and_then(EventPrefix::get) {
+            write!(writer, "{prefix} ")?;
+        }
+        if self.event_id {
+            write!(writer, "{} ", id::uuid_v4())?;
+        }
+        if self.uptime {
+            write!(writer, "+{}ms ", uptime_ms())?;
+        }
+
         // Format values from the event's's metadata:
         let metadata = event.metadata();
-        write!(&mut writer, "{} {}: ", metadata.level(), metadata.target())?;
+        write!(&mut writer, "{} ", self.level_width.format(metadata.level()))?;
+        if self.thread_names || self.thread_ids {
+            let thread = std::thread::current();
+            if self.thread_names {
+                write!(writer, "{} ", thread.name().unwrap_or("<unnamed>"))?;
+            }
+            if self.thread_ids {
+                write!(writer, "{:?} ", thread.id())?;
+            }
+        }
+        write!(&mut writer, "{}: ", metadata.target())?;
 
-        let line = metadata.line().unwrap_or(0);
-        let full_path = metadata.file().unwrap_or("unknown");
-        let filename = full_path.split('/').last().unwrap_or(full_path);
-        let filename_display = if filename.len() > 20 {
-            &filename[0..20]
-        } else {
-            filename
+        // 事件带有`caller.file`/`caller.line`（如`log_with_location`）时以它们作为位置，这两个字段不再单独输出
+        let explicit_location = metadata.fields().field("caller.file").is_some();
+        let visitor = (self.fields_before_message
+            || self.escape_newlines
+            || self.field_filter.is_some()
+            || self.field_delimiter != FieldDelimiter::Space
+            || self.max_field_value_len.is_some()
+            || explicit_location
+            // FlushLayer 使用的 flush 字段不输出
+            || metadata.fields().field("flush").is_some())
+            .then(|| {
+                let mut visitor = EventFieldVisitor::default();
+                event.record(&mut visitor);
+                visitor
+            });
+
+        let (full_path, line) = match visitor.as_ref().and_then(|visitor| Some((visitor.caller_file.as_deref()?, visitor.caller_line))) {
+            Some((file, line)) => (file, line.unwrap_or(0) as u32),
+            None => (metadata.file().unwrap_or("unknown"), metadata.line().unwrap_or(0)),
+        };
+        let filename_display = match self.max_filename_len {
+            _ if self.full_path => full_path,
+            0 => file_name(full_path),
+            max => truncate_chars(file_name(full_path), max),
         };
         write!(writer, "filename={filename_display}:{line} -> ")?;
 
         // Format all the spans in the event's span context.
         if let Some(scope) = ctx.event_scope() {
-            for span in scope.from_root() {
-                write!(writer, "{}", span.name())?;
+            let spans: Vec<_> = scope.from_root().collect();
+            // 截断时优先保留最内层的 span，其余层数留给最外层
+            let (outer, inner) = match self.max_span_depth {
+                Some(depth) if depth > 0 && spans.len() > depth => {
+                    let outer = usize::from(depth >= 2);
+                    (&spans[..outer], &spans[spans.len() - (depth - outer)..])
+                }
+                _ => (&spans[..], &spans[spans.len()..]),
+            };
+            let ansi = writer.has_ansi_escapes();
+            for (i, span) in outer.iter().chain(inner).enumerate() {
+                if i == outer.len() && !inner.is_empty() {
+                    write!(writer, "...: ")?;
+                }
+                if ansi {
+                    write!(writer, "{}", Style::new().bold().paint(span.name()))?;
+                } else {
+                    write!(writer, "{}", span.name())?;
+                }
 
                 // `FormattedFields` is a formatted representation of the span's
                 // fields, which is stored in its extensions by the `fmt` layer's
@@ -174,31 +858,374 @@ impl<S, N> FormatEvent<S, N> for CustomFormatter
 
                 // Skip formatting the fields if the span had no fields.
                 if !fields.is_empty() {
-                    write!(writer, "{{{}}}", fields)?;
+                    write!(writer, "{{{}}}", self.escape(fields))?;
                 }
                 write!(writer, ": ")?;
             }
         }
 
         // Write fields on the event
-        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        if let Some(mut visitor) = visitor {
+            if let Some(filter) = &self.field_filter {
+                visitor.fields.retain(|(name, _)| filter.allows(name));
+            }
+            let ansi = writer.has_ansi_escapes();
+            let mut parts: Vec<String> = visitor.fields.iter()
+                .map(|(name, value)| {
+                    let value = truncate_value(value, self.max_field_value_len);
+                    if ansi {
+                        format!("{}{}{}", Style::new().italic().paint(*name), Style::new().dimmed().paint("="), self.escape(&value))
+                    } else {
+                        format!("{name}={}", self.escape(&value))
+                    }
+                })
+                .collect();
+            if let Some(message) = &visitor.message {
+                let message = self.escape(message).into_owned();
+                if self.fields_before_message {
+                    parts.push(message);
+                } else {
+                    parts.insert(0, message);
+                }
+            }
+            write!(writer, "{}", parts.join(self.field_delimiter.as_str()))?;
+        } else {
+            ctx.field_format().format_fields(writer.by_ref(), event)?;
+        }
 
         writeln!(writer)
     }
 }
 
+/// 路径中的文件名，按平台的分隔符拆分
+///
+/// 在 Windows 上编译的程序`file!()`使用`\`分隔，在其它平台上运行时`Path`不认识它，所以再按`\`拆一次
+fn file_name(full_path: &str) -> &str {
+    let name = std::path::Path::new(full_path).file_name().and_then(|name| name.to_str()).unwrap_or(full_path);
+    name.rsplit('\\').next().unwrap_or(name)
+}
+
+/// 保留前`max_chars`个字符，按字符而不是字节截断，避免在多字节字符中间切开
+fn truncate_chars(s: &str, max_chars: usize) -> &str {
+    match s.char_indices().nth(max_chars) {
+        Some((end, _)) => &s[..end],
+        None => s,
+    }
+}
+
+/// 超过`max_chars`个字符时截断并追加`…`，`None`表示不截断
+pub(crate) fn truncate_value(value: &str, max_chars: Option<usize>) -> Cow<'_, str> {
+    match max_chars {
+        Some(max) if value.chars().nth(max).is_some() => Cow::Owned(format!("{}…", truncate_chars(value, max))),
+        _ => Cow::Borrowed(value),
+    }
+}
+
+/// 分开收集事件的`message`和其它字段，字段格式与`DefaultFields`保持一致
+#[derive(Default)]
+struct EventFieldVisitor {
+    message: Option<String>,
+    fields: Vec<(&'static str, String)>,
+    caller_file: Option<String>,
+    caller_line: Option<u64>,
+}
+
+impl tracing::field::Visit for EventFieldVisitor {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        match field.name() {
+            "caller.file" => self.caller_file = Some(value.to_string()),
+            _ => self.record_debug(field, &value),
+        }
+    }
+
+    fn record_u64(&mut self, field: &tracing::field::Field, value: u64) {
+        match field.name() {
+            "caller.line" => self.caller_line = Some(value),
+            _ => self.record_debug(field, &value),
+        }
+    }
+
+    fn record_bool(&mut self, field: &tracing::field::Field, value: bool) {
+        // `flush = true/false`只是给 FlushLayer 的指令
+        if field.name() != "flush" {
+            self.record_debug(field, &value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "message" => self.message = Some(format!("{value:?}")),
+            // tracing_log 桥接时附带的 log.* 字段，DefaultFields 同样会跳过
+            name if name.starts_with("log.") => {}
+            name => self.fields.push((name, format!("{value:?}"))),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use eyre::{Context, Report};
 
-    use crate::log::{init_log, LogMode};
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+    use std::sync::atomic::{AtomicU32, Ordering};
 
-    fn my_err() -> Report {
-        let err = || -> eyre::Result<()> {
-            Err(eyre::eyre!("error: my error 1"))
-        }().context("my error 2").context("my error 3").unwrap_err();
+    use tracing_subscriber::EnvFilter;
+
+    use crate::log::{build_subscriber, run_with_subscriber, CustomFormatter, FieldDelimiter, init_log, InitLogError, LevelWidth, LogConfig, LogMode, try_init_log};
+
+    #[derive(Clone, Default)]
+    pub(crate) struct TestWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for TestWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
 
-        err
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl TestWriter {
+        pub(crate) fn output(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    /// 用指定的 CustomFormatter 在当前线程内捕获`f`输出的日志
+    pub(crate) fn capture_custom(formatter: CustomFormatter, f: impl FnOnce()) -> String {
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_ansi(false)
+            .with_writer(move || make_writer.clone())
+            .event_format(formatter)
+            .finish();
+        tracing::subscriber::with_default(subscriber, f);
+        writer.output()
+    }
+
+    #[test]
+    fn custom_uptime() {
+        let output = capture_custom(CustomFormatter::default().with_uptime(true), || tracing::info!("tick"));
+        let (uptime, rest) = output.split_once(' ').unwrap();
+        assert!(uptime.starts_with('+') && uptime.ends_with("ms"), "{output}");
+        assert!(uptime[1..uptime.len() - 2].parse::<u64>().is_ok(), "{output}");
+        assert!(rest.starts_with("INFO "), "{output}");
+    }
+
+    #[test]
+    fn custom_max_filename_len() {
+        let emit = || tracing::info!(caller.file = "src/a_rather_long_module_name.rs", caller.line = 1u64, "x");
+        let filename = |formatter: CustomFormatter| {
+            let output = capture_custom(formatter, emit);
+            output.split("filename=").nth(1).and_then(|rest| rest.split(':').next()).unwrap().to_string()
+        };
+        assert_eq!(filename(CustomFormatter::default()), "a_rather_long_module");
+        assert_eq!(filename(CustomFormatter::new(8)), "a_rather");
+        assert_eq!(filename(CustomFormatter::new(40)), "a_rather_long_module_name.rs");
+        assert_eq!(filename(CustomFormatter::new(0)), "a_rather_long_module_name.rs");
+    }
+
+    #[test]
+    fn custom_colorize_full_line() {
+        let capture = |formatter: CustomFormatter, ansi: bool| {
+            let writer = TestWriter::default();
+            let make_writer = writer.clone();
+            let subscriber = tracing_subscriber::fmt()
+                .with_max_level(tracing::Level::TRACE)
+                .with_ansi(ansi)
+                .with_writer(move || make_writer.clone())
+                .event_format(formatter)
+                .finish();
+            tracing::subscriber::with_default(subscriber, || {
+                let _span = tracing::info_span!("job").entered();
+                tracing::error!(code = 7, "failed");
+                tracing::info!("done");
+            });
+            writer.output()
+        };
+
+        let output = capture(CustomFormatter::default().with_colorize_full_line(true), true);
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].starts_with("\x1b[31mERROR "), "{output}");
+        assert!(lines[0].ends_with("-> job: failed code=7\x1b[0m"), "{output}");
+        assert_eq!(lines[0].matches('\x1b').count(), 2, "{output}");
+        assert!(lines[1].starts_with("INFO "), "{output}");
+        assert!(!lines[1].contains('\x1b'), "{output}");
+
+        let output = capture(CustomFormatter::default().with_colorize_full_line(true), false);
+        assert!(!output.contains('\x1b'), "{output}");
+    }
+
+    #[test]
+    fn custom_full_path() {
+        let output = capture_custom(CustomFormatter::default(), || tracing::info!("short"));
+        assert!(output.contains(" filename=log.rs:"), "{output}");
+        let output = capture_custom(CustomFormatter::new(4).with_full_path(true), || tracing::info!("full"));
+        assert!(output.contains(" filename=src/log.rs:"), "{output}");
+        let output = capture_custom(CustomFormatter::default().with_full_path(true), || {
+            tracing::info!(caller.file = "src/db/mod.rs", caller.line = 12u64, "explicit");
+        });
+        assert!(output.contains(" filename=src/db/mod.rs:12 -> explicit"), "{output}");
+    }
+
+    #[test]
+    fn custom_thread_ids_and_names() {
+        let output = capture_custom(CustomFormatter::default(), || tracing::info!("main"));
+        assert!(output.starts_with("INFO myutil::log::tests: "), "{output}");
+
+        let formatter = CustomFormatter::default().with_thread_names(true).with_thread_ids(true);
+        let output = capture_custom(formatter, || {
+            let dispatch = tracing::dispatcher::get_default(|dispatch| dispatch.clone());
+            for name in ["worker-a", "worker-b"] {
+                let dispatch = dispatch.clone();
+                std::thread::Builder::new()
+                    .name(name.to_string())
+                    .spawn(move || tracing::dispatcher::with_default(&dispatch, || tracing::info!("working")))
+                    .unwrap()
+                    .join()
+                    .unwrap();
+            }
+            let dispatch = dispatch.clone();
+            std::thread::spawn(move || tracing::dispatcher::with_default(&dispatch, || tracing::info!("unnamed")))
+                .join()
+                .unwrap();
+        });
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3, "{output}");
+        let ids: Vec<&str> = lines.iter().map(|line| line.split(' ').nth(2).unwrap()).collect();
+        assert!(lines[0].starts_with("INFO worker-a ThreadId(") && lines[1].starts_with("INFO worker-b ThreadId("), "{output}");
+        assert!(lines[2].starts_with("INFO <unnamed> ThreadId("), "{output}");
+        assert!(ids[0] != ids[1] && ids[1] != ids[2] && ids[0] != ids[2], "{output}");
+        assert!(lines[0].contains(" ThreadId(") && lines[0].contains(") myutil::log::tests: filename="), "{output}");
+    }
+
+    #[test]
+    fn custom_max_field_value_len() {
+        let output = capture_custom(CustomFormatter::default().with_max_field_value_len(Some(6)), || {
+            tracing::info!(body = "0123456789", id = 7, "a long message is kept");
+        });
+        assert!(output.ends_with(" -> a long message is kept body=\"01234… id=7\n"), "{output}");
+    }
+
+    #[test]
+    fn custom_windows_path() {
+        let output = capture_custom(CustomFormatter::default(), || {
+            tracing::info!(caller.file = r"C:\Users\dev\app\src\main.rs", caller.line = 3u64, "hello");
+        });
+        assert!(output.contains(" filename=main.rs:3 -> hello"), "{output}");
+    }
+
+    #[test]
+    fn custom_prefix() {
+        let shard = Arc::new(AtomicU32::new(1));
+        let current = shard.clone();
+        let formatter = CustomFormatter::default().with_prefix(Arc::new(move || match current.load(Ordering::Relaxed) {
+            0 => panic!("no shard"),
+            shard => format!("shard={shard}"),
+        }));
+        let output = capture_custom(formatter, || {
+            tracing::info!("a");
+            shard.store(2, Ordering::Relaxed);
+            tracing::info!("b");
+            shard.store(0, Ordering::Relaxed);
+            tracing::info!("c");
+        });
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].starts_with("shard=1 INFO "), "{output}");
+        assert!(lines[1].starts_with("shard=2 INFO "), "{output}");
+        assert!(lines[2].starts_with("INFO "), "{output}");
+    }
+
+    #[test]
+    fn custom_multibyte_filename() {
+        let emit = || {
+            tracing::info!(caller.file = "src/源代码file.rs", caller.line = 7u64, "short");
+            tracing::info!(caller.file = "src/源代码目录里非常长的中文文件名称file.rs", caller.line = 8u64, "long");
+        };
+        let output = capture_custom(CustomFormatter::default(), emit);
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].ends_with("filename=源代码file.rs:7 -> short"), "{output}");
+        assert!(lines[1].ends_with("filename=源代码目录里非常长的中文文件名称file:8 -> long"), "{output}");
+    }
+
+    #[test]
+    fn custom_level_width() {
+        let emit = || {
+            tracing::debug!("a");
+            tracing::error!("b");
+        };
+        let output = capture_custom(CustomFormatter::default().with_level_width(LevelWidth::Short3), emit);
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].starts_with("DBG myutil::log::tests: "), "{output}");
+        assert!(lines[1].starts_with("ERR myutil::log::tests: "), "{output}");
+        let output = capture_custom(CustomFormatter::default().with_level_width(LevelWidth::Short4), emit);
+        assert!(output.lines().nth(1).unwrap().starts_with("ERRO "), "{output}");
+        let output = capture_custom(CustomFormatter::default(), emit);
+        assert!(output.lines().nth(1).unwrap().starts_with("ERROR "), "{output}");
+    }
+
+    #[test]
+    fn custom_tab_delimiter() {
+        let output = capture_custom(CustomFormatter::default().with_field_delimiter(FieldDelimiter::Tab), || {
+            tracing::info!(user = "a\tb", id = 7, "col\tumn");
+        });
+        assert!(output.ends_with(" -> col\\tumn\tuser=\"a\\tb\"\tid=7\n"), "{output:?}");
+    }
+
+    #[test]
+    fn custom_max_span_depth() {
+        let nested = || {
+            let _a = tracing::info_span!("a").entered();
+            let _b = tracing::info_span!("b", id = 1).entered();
+            let _c = tracing::info_span!("c").entered();
+            let _d = tracing::info_span!("d").entered();
+            tracing::info!("deep");
+        };
+
+        let output = capture_custom(CustomFormatter::default(), nested);
+        assert!(output.contains(" -> a: b{id=1}: c: d: deep"), "{output}");
+        let output = capture_custom(CustomFormatter::default().with_max_span_depth(Some(3)), nested);
+        assert!(output.contains(" -> a: ...: c: d: deep"), "{output}");
+        let output = capture_custom(CustomFormatter::default().with_max_span_depth(Some(1)), nested);
+        assert!(output.contains(" -> ...: d: deep"), "{output}");
+        let output = capture_custom(CustomFormatter::default().with_max_span_depth(Some(4)), nested);
+        assert!(output.contains(" -> a: b{id=1}: c: d: deep"), "{output}");
+        let output = capture_custom(CustomFormatter::default().with_max_span_depth(Some(0)), nested);
+        assert!(output.contains(" -> a: b{id=1}: c: d: deep"), "{output}");
+    }
+
+    #[test]
+    fn debug_field_only_at_debug() {
+        let evaluated = std::cell::Cell::new(0);
+        let expensive = || {
+            evaluated.set(evaluated.get() + 1);
+            "full body"
+        };
+        let emit = || tracing::info!(body = crate::debug_field!(expensive()), "request");
+
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::INFO)
+            .with_ansi(false)
+            .with_writer(move || make_writer.clone())
+            .event_format(CustomFormatter::default())
+            .finish();
+        tracing::subscriber::with_default(subscriber, emit);
+        assert!(!writer.output().contains("body"), "{}", writer.output());
+        assert_eq!(evaluated.get(), 0);
+
+        let output = capture_custom(CustomFormatter::default(), emit);
+        assert!(output.contains(" -> request body=\"full body\""), "{output}");
+        assert_eq!(evaluated.get(), 1);
+    }
+
+    fn my_err() -> Report {
+        Err::<(), _>(eyre::eyre!("error: my error 1")).context("my error 2").context("my error 3").unwrap_err()
     }
 
     fn display() {
@@ -213,33 +1240,184 @@ mod tests {
         tracing::error!("[error]-5.4 {:#?}", my_err());
     }
 
+    #[test]
+    fn scoped_subscriber() {
+        for log_mode in [LogMode::Minimal, LogMode::General, LogMode::Json] {
+            tracing::subscriber::with_default(build_subscriber(log_mode, tracing::Level::INFO), || {
+                assert!(tracing::enabled!(tracing::Level::INFO));
+                assert!(!tracing::enabled!(tracing::Level::DEBUG));
+            });
+        }
+    }
+
+    #[test]
+    fn task_subscriber() {
+        async fn yield_once() {
+            let mut yielded = false;
+            std::future::poll_fn(|cx| {
+                if std::mem::replace(&mut yielded, true) {
+                    std::task::Poll::Ready(())
+                } else {
+                    cx.waker().wake_by_ref();
+                    std::task::Poll::Pending
+                }
+            }).await
+        }
+
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = LogConfig::new().writer(move || make_writer.clone()).ansi(false).build();
+        let job = run_with_subscriber(subscriber, async {
+            let _job = tracing::info_span!("job", id = 3).entered();
+            tracing::info!("started");
+            yield_once().await;
+            tracing::info!("finished");
+            7
+        });
+
+        let mut job = std::pin::pin!(job);
+        let mut cx = std::task::Context::from_waker(std::task::Waker::noop());
+        let result = loop {
+            tracing::info!("between polls");
+            if let std::task::Poll::Ready(result) = std::future::Future::poll(job.as_mut(), &mut cx) {
+                break result;
+            }
+        };
+        assert_eq!(result, 7);
+        let output = writer.output();
+        assert_eq!(output.lines().count(), 2, "{output}");
+        assert!(output.contains("INFO job: myutil::log::tests: finished id=3"), "{output}");
+    }
+
+    #[test]
+    fn modes_write_to_writer() {
+        let modes = [LogMode::Original, LogMode::Simple, LogMode::General, LogMode::Full, LogMode::Custom, LogMode::Json];
+        for mode in modes {
+            let writer = TestWriter::default();
+            let make_writer = writer.clone();
+            let subscriber = LogConfig::for_mode(mode, EnvFilter::new("info"), None)
+                .writer(move || make_writer.clone())
+                .build();
+            tracing::subscriber::with_default(subscriber, || tracing::info!("to the writer"));
+            assert!(writer.output().contains("to the writer"), "{}", writer.output());
+        }
+    }
+
+    #[test]
+    fn custom_fields_after_message() {
+        let output = capture_custom(CustomFormatter::default(), || {
+            let _span = tracing::info_span!("req", id = 7).entered();
+            tracing::info!(user = "alice", count = 3, "login");
+        });
+        assert!(output.starts_with("INFO myutil::log::tests: filename=log.rs:"), "{output}");
+        assert!(output.ends_with(" -> req{id=7}: login user=\"alice\" count=3\n"), "{output}");
+    }
+
+    #[test]
+    fn custom_fields_before_message() {
+        let formatter = CustomFormatter::default().with_fields_before_message(true);
+        let output = capture_custom(formatter, || {
+            let _span = tracing::info_span!("req", id = 7).entered();
+            tracing::info!(user = "alice", count = 3, "login");
+            tracing::info!("no fields");
+        });
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].ends_with(" -> req{id=7}: user=\"alice\" count=3 login"), "{output}");
+        assert!(lines[1].ends_with(" -> req{id=7}: no fields"), "{output}");
+    }
+
+    #[test]
+    fn custom_escape_newlines() {
+        let formatter = CustomFormatter::default().with_escape_newlines(true);
+        let output = capture_custom(formatter, || {
+            let _span = tracing::info_span!("req", path = "a\nb").entered();
+            tracing::info!(body = %"{\n  \"id\": 1\n}", "multi\nline");
+        });
+        assert_eq!(output.lines().count(), 1, "{output}");
+        assert!(output.trim_end().ends_with(r#" -> req{path="a\nb"}: multi\nline body={\n  "id": 1\n}"#), "{output}");
+
+        let output = capture_custom(CustomFormatter::default(), || tracing::info!("multi\nline"));
+        assert_eq!(output.lines().count(), 2, "{output}");
+    }
+
+    #[test]
+    fn custom_event_id() {
+        let output = capture_custom(CustomFormatter::default().with_event_id(true), || {
+            tracing::info!("first");
+            tracing::info!("second");
+        });
+        let ids: Vec<&str> = output.lines().map(|line| line.split(' ').next().unwrap()).collect();
+        assert_eq!(ids.len(), 2);
+        assert_ne!(ids[0], ids[1]);
+        for id in ids {
+            assert_eq!(id.len(), 36, "{output}");
+            assert_eq!(&id[14..15], "4", "{output}");
+        }
+
+        let output = capture_custom(CustomFormatter::default(), || tracing::info!("first"));
+        assert!(output.starts_with("INFO "), "{output}");
+    }
+
+    #[test]
+    fn init_log_twice_is_error() {
+        let _first = init_log(LogMode::Custom, tracing::Level::TRACE);
+        let second = init_log(LogMode::Custom, tracing::Level::TRACE);
+        assert!(matches!(second, Err(InitLogError::Subscriber(_))));
+    }
+
+    #[test]
+    fn try_init_log_twice() {
+        try_init_log(LogMode::Custom, tracing::Level::TRACE).unwrap();
+        try_init_log(LogMode::Json, tracing::Level::INFO).unwrap();
+        assert!(tracing::dispatcher::has_been_set());
+    }
+
     #[test]
     fn display_original() {
-        init_log(LogMode::Original, tracing::Level::TRACE);
+        // 同一个测试进程只有第一次初始化成功
+        let _guard = init_log(LogMode::Original, tracing::Level::TRACE);
         display();
     }
 
     #[test]
     fn display_simple() {
-        init_log(LogMode::Simple, tracing::Level::TRACE);
+        // 同一个测试进程只有第一次初始化成功
+        let _guard = init_log(LogMode::Simple, tracing::Level::TRACE);
         display();
     }
 
     #[test]
     fn display_general() {
-        init_log(LogMode::General, tracing::Level::TRACE);
+        // 同一个测试进程只有第一次初始化成功
+        let _guard = init_log(LogMode::General, tracing::Level::TRACE);
         display();
     }
 
     #[test]
     fn display_full() {
-        init_log(LogMode::Full, tracing::Level::TRACE);
+        // 同一个测试进程只有第一次初始化成功
+        let _guard = init_log(LogMode::Full, tracing::Level::TRACE);
+        display();
+    }
+
+    #[test]
+    fn display_json() {
+        // 同一个测试进程只有第一次初始化成功
+        let _guard = init_log(LogMode::Json, tracing::Level::TRACE);
         display();
     }
 
     #[test]
     fn display_custom() {
-        init_log(LogMode::Custom, tracing::Level::TRACE);
+        // 同一个测试进程只有第一次初始化成功
+        let _guard = init_log(LogMode::Custom, tracing::Level::TRACE);
+        display();
+    }
+
+    #[test]
+    fn display_minimal() {
+        // 同一个测试进程只有第一次初始化成功
+        let _guard = init_log(LogMode::Minimal, tracing::Level::TRACE);
         display();
     }
 }
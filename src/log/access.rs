@@ -0,0 +1,130 @@
+use std::fmt::{Display, Formatter};
+
+use chrono::{DateTime, FixedOffset, Local};
+
+/// 一条 Apache Common Log Format / Combined Log Format 访问日志
+///
+/// `host ident authuser [date] "request" status bytes`，Combined 格式再追加`"referer" "user-agent"`，缺失的值输出`-`
+///
+/// # Example
+/// ```
+/// use myutil::log::ClfEntry;
+///
+/// let time = chrono::DateTime::parse_from_rfc3339("2000-10-10T13:55:36-07:00").unwrap();
+/// let mut entry = ClfEntry::new("127.0.0.1", "GET /apache_pb.gif HTTP/1.0", 200).time(time);
+/// entry.authuser = Some("frank".to_string());
+/// entry.bytes = Some(2326);
+/// assert_eq!(
+///     entry.to_string(),
+///     r#"127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /apache_pb.gif HTTP/1.0" 200 2326"#,
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct ClfEntry {
+    pub host: String,
+    pub ident: Option<String>,
+    pub authuser: Option<String>,
+    pub time: DateTime<FixedOffset>,
+    pub request: String,
+    pub status: u16,
+    pub bytes: Option<u64>,
+    pub referer: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+impl ClfEntry {
+    /// 时间取当前本地时间，其余可选字段为空
+    pub fn new(host: impl Into<String>, request: impl Into<String>, status: u16) -> Self {
+        ClfEntry {
+            host: host.into(),
+            ident: None,
+            authuser: None,
+            time: Local::now().fixed_offset(),
+            request: request.into(),
+            status,
+            bytes: None,
+            referer: None,
+            user_agent: None,
+        }
+    }
+
+    pub fn time(mut self, time: DateTime<FixedOffset>) -> Self {
+        self.time = time;
+        self
+    }
+
+    /// Combined Log Format
+    pub fn to_combined(&self) -> String {
+        format!(
+            "{self} \"{}\" \"{}\"",
+            self.referer.as_deref().unwrap_or("-"),
+            self.user_agent.as_deref().unwrap_or("-"),
+        )
+    }
+}
+
+/// Common Log Format
+impl Display for ClfEntry {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let bytes = self.bytes.map_or("-".to_string(), |bytes| bytes.to_string());
+        write!(
+            f,
+            "{} {} {} [{}] \"{}\" {} {bytes}",
+            self.host,
+            self.ident.as_deref().unwrap_or("-"),
+            self.authuser.as_deref().unwrap_or("-"),
+            self.time.format("%d/%b/%Y:%H:%M:%S %z"),
+            self.request,
+            self.status,
+        )
+    }
+}
+
+/// 以 info 级别、`access`为 target 输出 Common Log Format 访问日志
+pub fn log_clf(entry: &ClfEntry) {
+    tracing::info!(target: "access", "{entry}");
+}
+
+/// 以 info 级别、`access`为 target 输出 Combined Log Format 访问日志
+pub fn log_combined(entry: &ClfEntry) {
+    tracing::info!(target: "access", "{}", entry.to_combined());
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::log::{ClfEntry, CustomFormatter, log_clf, log_combined};
+    use crate::log::tests::capture_custom;
+
+    fn entry() -> ClfEntry {
+        let time = chrono::DateTime::parse_from_rfc3339("2000-10-10T13:55:36-07:00").unwrap();
+        let mut entry = ClfEntry::new("127.0.0.1", "GET /apache_pb.gif HTTP/1.0", 200).time(time);
+        entry.authuser = Some("frank".to_string());
+        entry.bytes = Some(2326);
+        entry
+    }
+
+    #[test]
+    fn clf_format() {
+        let mut entry = entry();
+        assert_eq!(entry.to_string(), r#"127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /apache_pb.gif HTTP/1.0" 200 2326"#);
+
+        entry.bytes = None;
+        entry.user_agent = Some("Mozilla/4.08".to_string());
+        assert_eq!(
+            entry.to_combined(),
+            r#"127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /apache_pb.gif HTTP/1.0" 200 - "-" "Mozilla/4.08""#,
+        );
+    }
+
+    #[test]
+    fn clf_log() {
+        let output = capture_custom(CustomFormatter::default(), || {
+            log_clf(&entry());
+            log_combined(&entry());
+        });
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].starts_with("INFO access: "), "{output}");
+        assert!(lines[0].ends_with(r#"-> 127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /apache_pb.gif HTTP/1.0" 200 2326"#), "{output}");
+        assert!(lines[1].ends_with(r#"200 2326 "-" "-""#), "{output}");
+    }
+}
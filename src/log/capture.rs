@@ -0,0 +1,255 @@
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use tracing::span::{Attributes, Id, Record};
+use tracing_core::{Event, Subscriber};
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::format::DefaultFields;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use crate::log::CustomFormatter;
+
+#[derive(Clone, Default)]
+struct CaptureBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for CaptureBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner()).write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// 在当前线程执行`f`，只捕获`target`（及其子模块）不低于`level`的日志，返回`f`的结果和捕获的日志行
+///
+/// 日志行使用`CustomFormatter`的格式，不含颜色。执行期间当前线程的日志只进入捕获，其它 target 的日志被丢弃，结束后恢复原来的 subscriber。
+/// 适合在测试中只检查某个模块的输出。
+///
+/// # Example
+/// ```
+/// use myutil::log::capture_target;
+///
+/// let (value, lines) = capture_target("my_app::db", tracing::Level::DEBUG, || {
+///     tracing::debug!(target: "my_app::db", "query");
+///     tracing::debug!(target: "my_app::http", "request");
+///     42
+/// });
+/// assert_eq!(value, 42);
+/// assert_eq!(lines.len(), 1);
+/// ```
+pub fn capture_target<R>(target: &str, level: tracing::Level, f: impl FnOnce() -> R) -> (R, Vec<String>) {
+    let buffer = CaptureBuffer::default();
+    let make_writer = buffer.clone();
+    let layer = tracing_subscriber::fmt::layer()
+        .with_ansi(false)
+        .with_writer(move || make_writer.clone())
+        .event_format(CustomFormatter::default())
+        .with_filter(Targets::new().with_target(target, level));
+    let subscriber = tracing_subscriber::registry().with(layer);
+
+    let result = tracing::subscriber::with_default(subscriber, f);
+    let output = buffer.0.lock().unwrap_or_else(|err| err.into_inner());
+    let lines = String::from_utf8_lossy(&output).lines().map(str::to_string).collect();
+    (result, lines)
+}
+
+/// `capture_logs`返回的已捕获日志，可以 clone 后在任意线程读取
+#[derive(Debug, Clone, Default)]
+pub struct LogCapture(Arc<Mutex<Vec<String>>>);
+
+impl LogCapture {
+    /// 到目前为止捕获的日志行，按输出顺序
+    pub fn lines(&self) -> Vec<String> {
+        self.0.lock().unwrap_or_else(|err| err.into_inner()).clone()
+    }
+
+    /// 清空已捕获的日志
+    pub fn clear(&self) {
+        self.0.lock().unwrap_or_else(|err| err.into_inner()).clear();
+    }
+}
+
+impl<'a> MakeWriter<'a> for LogCapture {
+    type Writer = CaptureLines<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        CaptureLines { capture: self, buf: Vec::new() }
+    }
+}
+
+/// 收集一条日志，drop 时按行加入`LogCapture`
+pub struct CaptureLines<'a> {
+    capture: &'a LogCapture,
+    buf: Vec<u8>,
+}
+
+impl Write for CaptureLines<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for CaptureLines<'_> {
+    fn drop(&mut self) {
+        let output = String::from_utf8_lossy(&self.buf);
+        let mut lines = self.capture.0.lock().unwrap_or_else(|err| err.into_inner());
+        lines.extend(output.lines().map(str::to_string));
+    }
+}
+
+/// 把日志按`CustomFormatter`的格式（不含颜色）保存到`LogCapture`的 layer，见`capture_logs`
+pub struct CaptureLayer<S> {
+    inner: tracing_subscriber::fmt::Layer<S, DefaultFields, CustomFormatter, LogCapture>,
+}
+
+impl<S> Layer<S> for CaptureLayer<S>
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_new_span(attrs, id, ctx)
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        self.inner.on_record(id, values, ctx)
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        self.inner.on_event(event, ctx)
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_enter(id, ctx)
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_exit(id, ctx)
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        self.inner.on_close(id, ctx)
+    }
+}
+
+/// 创建捕获日志的 layer，用于在测试中检查代码输出了哪些日志
+///
+/// 与`capture_target`不同，不限制 target 和级别，也不需要把被测代码放进闭包：layer 可以和其它 layer、过滤器组合，
+/// 之后随时通过`LogCapture::lines`读取。多个线程的日志按输出顺序保存。
+///
+/// # Example
+/// ```
+/// use tracing_subscriber::layer::SubscriberExt;
+/// use myutil::log::capture_logs;
+///
+/// let (layer, capture) = capture_logs();
+/// tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+///     tracing::info!("started");
+/// });
+/// assert!(capture.lines()[0].starts_with("INFO rust_out: "));
+/// ```
+pub fn capture_logs<S>() -> (CaptureLayer<S>, LogCapture)
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let capture = LogCapture::default();
+    let inner = tracing_subscriber::fmt::layer()
+        .with_ansi(false)
+        .with_writer(capture.clone())
+        .event_format(CustomFormatter::default());
+    (CaptureLayer { inner }, capture)
+}
+
+/// 断言`expected`中的子串按顺序出现在`lines`中（不要求相邻），例如检查`capture_target`捕获的日志
+///
+/// 失败时 panic，提示从哪一项开始找不到，并列出全部日志行。
+///
+/// # Example
+/// ```
+/// use myutil::log::{assert_log_sequence, capture_target};
+///
+/// let ((), lines) = capture_target("my_app", tracing::Level::INFO, || {
+///     tracing::info!(target: "my_app", "connecting");
+///     tracing::info!(target: "my_app", "authenticated");
+///     tracing::info!(target: "my_app", "ready");
+/// });
+/// assert_log_sequence(&lines, &["connecting", "authenticated", "ready"]);
+/// ```
+#[track_caller]
+pub fn assert_log_sequence<L: AsRef<str>>(lines: &[L], expected: &[&str]) {
+    let mut rest = lines.iter().enumerate();
+    let mut last_match = None;
+    for (step, pattern) in expected.iter().enumerate() {
+        match rest.by_ref().find(|(_, line)| line.as_ref().contains(pattern)) {
+            Some((index, _)) => last_match = Some(index),
+            None => {
+                let after = match last_match {
+                    Some(index) => format!("after line {index}"),
+                    None => "in any line".to_string(),
+                };
+                let actual: Vec<String> = lines.iter().enumerate()
+                    .map(|(index, line)| format!("{index:>4}: {}", line.as_ref()))
+                    .collect();
+                panic!("log sequence broken at step {step}: {pattern:?} not found {after}\ncaptured lines:\n{}", actual.join("\n"));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::log::{assert_log_sequence, capture_logs, capture_target};
+
+    #[test]
+    fn capture_only_target() {
+        let ((), lines) = capture_target("app::db", tracing::Level::DEBUG, || {
+            tracing::debug!(target: "app::db::pool", "checkout");
+            tracing::trace!(target: "app::db", "too verbose");
+            tracing::info!(target: "app::http", "request");
+            tracing::warn!(target: "app::db", rows = 0, "empty result");
+        });
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        assert!(lines[0].starts_with("DEBUG app::db::pool: ") && lines[0].ends_with(" -> checkout"), "{lines:?}");
+        assert!(lines[1].starts_with("WARN app::db: ") && lines[1].ends_with(" -> empty result rows=0"), "{lines:?}");
+    }
+
+    #[test]
+    fn capture_layer_lines() {
+        let (layer, capture) = capture_logs();
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            let _job = tracing::info_span!("job", id = 3).entered();
+            tracing::info!("first");
+            tracing::error!(code = 7, "second");
+        });
+
+        let lines = capture.lines();
+        assert_eq!(lines.len(), 2, "{lines:?}");
+        assert!(lines[0].starts_with("INFO myutil::log::capture::tests: ") && lines[0].ends_with(" -> job{id=3}: first"), "{lines:?}");
+        assert!(lines[1].starts_with("ERROR myutil::log::capture::tests: ") && lines[1].ends_with(" -> job{id=3}: second code=7"), "{lines:?}");
+        capture.clear();
+        assert!(capture.lines().is_empty());
+    }
+
+    #[test]
+    fn log_sequence() {
+        let lines = ["INFO connecting", "DEBUG retry", "INFO authenticated", "INFO ready"];
+        assert_log_sequence(&lines, &["connecting", "authenticated", "ready"]);
+        assert_log_sequence(&lines, &[]);
+
+        let err = std::panic::catch_unwind(|| assert_log_sequence(&lines, &["ready", "connecting"])).unwrap_err();
+        let message = err.downcast_ref::<String>().unwrap();
+        assert!(message.starts_with("log sequence broken at step 1: \"connecting\" not found after line 3\n"), "{message}");
+        assert!(message.ends_with("   3: INFO ready"), "{message}");
+    }
+}
@@ -0,0 +1,1115 @@
+use std::io::{IsTerminal, Write};
+use std::sync::Arc;
+
+use tracing_core::{Metadata, Subscriber};
+use tracing_core::Event;
+use tracing_subscriber::fmt::format::{self, DefaultFields, FmtSpan};
+use tracing_subscriber::fmt::time::{ChronoLocal, ChronoUtc};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::fmt::{self, FmtContext, FormatEvent, FormatFields, MakeWriter};
+use tracing_subscriber::layer::{Layered, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{EnvFilter, Layer, reload};
+
+use crate::log::filter;
+use crate::log::init_report::Installed;
+use crate::log::parent::WithStaticFields;
+use crate::log::reload::FilterBase;
+use crate::log::time::{DEFAULT_TIME_FORMAT, RFC3339_UTC_FORMAT};
+use crate::log::{CustomFormatter, FieldFilter, FlushLayer, InitLogError, JsonFields, JsonFormatter, level_to_string, LogGuard, LogMode, LogReloadHandle, non_blocking, NonBlocking, ProgressStdout, TimeSourceTimer, TimestampFormat, WorkerGuard};
+
+/// `LogConfig`的事件格式
+#[derive(Debug, Clone, Default)]
+pub enum LogFormat {
+    /// 单行紧凑格式，默认
+    #[default]
+    Compact,
+    /// 多行美观格式
+    Pretty,
+    /// 见`CustomFormatter`，不使用`LogConfig`中的时间、target、行号等设置
+    Custom(CustomFormatter),
+    /// 每行一个 JSON 对象，见`JsonFormatter`；字段格式见`JsonFields`
+    Json(JsonFields),
+    /// 自定义的`FormatEvent`，见`EventFormatter`
+    Event(EventFormatter),
+    /// Vector 的`native_json`编码，见`VectorFormatter`，需要`vector` feature
+    #[cfg(feature = "vector")]
+    Vector,
+}
+
+/// `LogConfig`中格式化 layer 所在的 subscriber，即`EventFormatter`需要支持的`FormatEvent<S, N>`中的`S`
+pub type LogSubscriber = Layered<reload::Layer<EnvFilter, FilterBase>, FilterBase>;
+
+/// 用户提供的事件格式，用于`LogFormat::Event`和`init_log_custom_with`
+///
+/// 字段使用`tracing_subscriber`默认的`DefaultFields`格式化，所以格式需要实现`FormatEvent<LogSubscriber, DefaultFields>`。
+/// `LogSubscriber`的类型很长且可能变化，推荐像`tracing_subscriber`自带的格式一样对任意`S`和`N`实现：
+///
+/// ```
+/// use tracing_core::{Event, Subscriber};
+/// use tracing_subscriber::fmt::{format, FmtContext, FormatEvent, FormatFields};
+/// use tracing_subscriber::registry::LookupSpan;
+/// use myutil::log::{EventFormatter, LogConfig, LogFormat};
+///
+/// /// 输出`[INFO] message key=value`
+/// struct Bracketed;
+///
+/// impl<S, N> FormatEvent<S, N> for Bracketed
+///     where
+///         S: Subscriber + for<'a> LookupSpan<'a>,
+///         N: for<'a> FormatFields<'a> + 'static,
+/// {
+///     fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: format::Writer<'_>, event: &Event<'_>) -> std::fmt::Result {
+///         write!(writer, "[{}] ", event.metadata().level())?;
+///         ctx.field_format().format_fields(writer.by_ref(), event)?;
+///         writeln!(writer)
+///     }
+/// }
+///
+/// let subscriber = LogConfig::new().format(LogFormat::Event(EventFormatter::new(Bracketed))).build();
+/// tracing::subscriber::with_default(subscriber, || tracing::info!(user = "alice", "login")); // [INFO] login user="alice"
+/// ```
+#[derive(Clone)]
+pub struct EventFormatter(Arc<dyn FormatEvent<LogSubscriber, DefaultFields> + Send + Sync>);
+
+impl EventFormatter {
+    pub fn new<F>(formatter: F) -> Self
+        where
+            F: FormatEvent<LogSubscriber, DefaultFields> + Send + Sync + 'static,
+    {
+        EventFormatter(Arc::new(formatter))
+    }
+}
+
+impl std::fmt::Debug for EventFormatter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("EventFormatter(..)")
+    }
+}
+
+impl FormatEvent<LogSubscriber, DefaultFields> for EventFormatter {
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, LogSubscriber, DefaultFields>,
+        writer: format::Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        self.0.format_event(ctx, writer, event)
+    }
+}
+
+/// 可自由组合的日志配置，`init_log`的各个`LogMode`都是它的预设
+///
+/// `build`返回 subscriber，`install`设置为全局默认
+///
+/// # Example
+/// ```
+/// use myutil::log::{LogConfig, LogFormat};
+///
+/// let _guard = LogConfig::new()
+///     .level(tracing::Level::DEBUG)
+///     .with_line_number(true)
+///     .writer(std::io::stderr)
+///     .ansi(false)
+///     .format(LogFormat::Compact)
+///     .install()
+///     .expect("Failed to initialize log");
+/// tracing::debug!("hello");
+/// ```
+pub struct LogConfig {
+    filter: EnvFilter,
+    target_levels: Vec<String>,
+    /// 构建时追加其中规则的环境变量，`None`时不读取
+    env_var: Option<String>,
+    floor_level: Option<tracing::Level>,
+    target: bool,
+    line_number: bool,
+    thread_names: bool,
+    thread_ids: bool,
+    span_events: FmtSpan,
+    timer: TimeSourceTimer,
+    /// 调用过`timer`或`time_format`，JSON 格式只在此时使用`timer`
+    custom_timer: bool,
+    time_format: Option<String>,
+    utc: bool,
+    json_timestamp_format: TimestampFormat,
+    json_span_elapsed: bool,
+    json_date_buckets: bool,
+    json_uptime: bool,
+    writer: SharedMakeWriter,
+    /// 输出位置：`stdout`、`non_blocking_stdout`或`custom`，用于自动判断是否输出颜色和`init_report`
+    writer_kind: &'static str,
+    /// 预设的`LogMode`名称，用于`init_report`
+    mode: &'static str,
+    ansi: Option<bool>,
+    format: LogFormat,
+    field_filter: Option<FieldFilter>,
+    file: Option<NonBlocking>,
+    /// `non_blocking_writer`和`copy_to_file`的后台线程，`install`时交给`LogGuard`
+    workers: Vec<WorkerGuard>,
+    request_id: Option<&'static str>,
+    parent_pid: bool,
+    mirror: bool,
+    error_layer: bool,
+}
+
+/// 同一个 writer 同时给格式化 layer 和`FlushLayer`使用
+#[derive(Clone)]
+struct SharedMakeWriter(Arc<BoxMakeWriter>);
+
+impl<'a> MakeWriter<'a> for SharedMakeWriter {
+    type Writer = Box<dyn Write + 'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.0.make_writer()
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        self.0.make_writer_for(meta)
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig::new()
+    }
+}
+
+impl LogConfig {
+    /// 默认 info 级别、输出到 stdout、带 target、紧凑格式；`RUST_LOG`中的规则覆盖代码中的设置，见`env_var`
+    ///
+    /// stdout 经过`ProgressStdout`，调用过`progress`后日志行不会与进度行交错，否则与直接写 stdout 相同
+    pub fn new() -> Self {
+        LogConfig {
+            filter: EnvFilter::new(tracing::Level::INFO.as_str()),
+            target_levels: Vec::new(),
+            env_var: Some(EnvFilter::DEFAULT_ENV.to_string()),
+            floor_level: None,
+            target: true,
+            line_number: false,
+            thread_names: false,
+            thread_ids: false,
+            span_events: FmtSpan::NONE,
+            timer: TimeSourceTimer::new(tracing_subscriber::fmt::time::SystemTime),
+            custom_timer: false,
+            time_format: None,
+            utc: false,
+            json_timestamp_format: TimestampFormat::default(),
+            json_span_elapsed: false,
+            json_date_buckets: false,
+            json_uptime: false,
+            writer: SharedMakeWriter(Arc::new(BoxMakeWriter::new(ProgressStdout))),
+            writer_kind: "stdout",
+            mode: "config",
+            ansi: None,
+            format: LogFormat::default(),
+            field_filter: None,
+            file: None,
+            workers: Vec::new(),
+            request_id: None,
+            parent_pid: false,
+            mirror: false,
+            error_layer: false,
+        }
+    }
+
+    /// `log_mode`对应的预设
+    ///
+    /// `timer`为`None`时使用各模式默认的时间格式
+    pub(crate) fn for_mode(log_mode: LogMode, filter: EnvFilter, timer: Option<TimeSourceTimer>) -> Self {
+        // 各模式是否读取环境变量已体现在`filter`中，不再重复追加
+        let config = LogConfig { mode: log_mode.name(), env_var: None, ..LogConfig::new().filter(filter) };
+        match log_mode {
+            // Minimal 不使用 LogConfig，由 init_log 单独安装
+            LogMode::Original | LogMode::Simple | LogMode::Minimal => config,
+            LogMode::General => config.with_line_number(true).timer(timer.unwrap_or_default()),
+            LogMode::Full => {
+                let mut config = config
+                    .with_thread_names(true)
+                    .with_thread_ids(true)
+                    .timer(timer.unwrap_or_default())
+                    .format(LogFormat::Pretty);
+                // 最底层是镜像位置，运行时可通过 add_mirror_layer 临时挂载 layer
+                config.mirror = true;
+                config.error_layer = true;
+                config
+            }
+            LogMode::Custom => config.format(LogFormat::Custom(CustomFormatter::default())),
+            LogMode::Json => {
+                let config = match timer {
+                    Some(timer) => config.timer(timer),
+                    None => config,
+                };
+                config.ansi(false).format(LogFormat::Json(JsonFields::default()))
+            }
+            #[cfg(feature = "syslog")]
+            LogMode::Syslog { facility, ident } => {
+                let formatter = super::SyslogFormatter::new(facility, ident);
+                config.ansi(false).format(LogFormat::Event(EventFormatter::new(formatter)))
+            }
+        }
+    }
+
+    /// 只输出`level`及以上的日志，覆盖之前的`filter`
+    pub fn level(self, level: tracing::Level) -> Self {
+        self.filter(EnvFilter::new(level.as_str()))
+    }
+
+    /// 从环境变量`var`（如`MYAPP_LOG`）读取过滤规则，代替`RUST_LOG`，避免与进程内其它库冲突
+    ///
+    /// 构建时读取，其中的规则追加在`level`/`filter`和各`target_level`之后，覆盖同一 target 的设置；
+    /// 变量没有设置或无法解析时只使用代码中的设置。是否设置了`target_level`不影响是否读取。
+    pub fn env_var(self, var: &str) -> Self {
+        LogConfig { env_var: Some(var.to_string()), ..self }
+    }
+
+    /// `target`（如`my_app`、`hyper`）单独使用`level`，可多次调用，不需要设置环境变量
+    ///
+    /// 合并顺序为`level`/`filter`、各`target_level`、环境变量`RUST_LOG`（或`env_var`指定的变量），后面的覆盖前面同一 target 的设置
+    ///
+    /// # Example
+    /// ```
+    /// use tracing::Level;
+    /// use myutil::log::LogConfig;
+    ///
+    /// let _guard = LogConfig::new()
+    ///     .level(Level::INFO)
+    ///     .target_level("my_app", Level::DEBUG)
+    ///     .target_level("hyper", Level::WARN)
+    ///     .install()
+    ///     .unwrap();
+    /// ```
+    pub fn target_level(mut self, target: &str, level: tracing::Level) -> Self {
+        self.target_levels.push(format!("{target}={}", level_to_string(level)));
+        self
+    }
+
+    /// 使用给定的过滤器，例如`sensible_filter`，覆盖之前的`level`
+    pub fn filter(mut self, filter: EnvFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_target(mut self, target: bool) -> Self {
+        self.target = target;
+        self
+    }
+
+    pub fn with_line_number(mut self, line_number: bool) -> Self {
+        self.line_number = line_number;
+        self
+    }
+
+    pub fn with_thread_names(mut self, thread_names: bool) -> Self {
+        self.thread_names = thread_names;
+        self
+    }
+
+    pub fn with_thread_ids(mut self, thread_ids: bool) -> Self {
+        self.thread_ids = thread_ids;
+        self
+    }
+
+    /// span 的创建、进入、退出和关闭时输出的事件，默认`FmtSpan::NONE`即不输出；同时作用于`copy_to_file`的文件
+    ///
+    /// `FmtSpan::CLOSE`在 span 关闭时输出一条`close`事件，带有`time.busy`（span 被进入的总时间）和`time.idle`字段
+    ///
+    /// # Example
+    /// ```
+    /// use tracing_subscriber::fmt::format::FmtSpan;
+    /// use myutil::log::LogConfig;
+    ///
+    /// let subscriber = LogConfig::new().span_events(FmtSpan::CLOSE).build();
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let _load = tracing::info_span!("load").entered();
+    /// }); // INFO load: rust_out: close time.busy=12.3µs time.idle=4.10µs
+    /// ```
+    pub fn span_events(mut self, span_events: FmtSpan) -> Self {
+        self.span_events = span_events;
+        self
+    }
+
+    /// 运行时通过`LogReloadHandle`修改级别时不能低于`level`，例如始终保留 info 及以上的审计日志；默认不限制
+    ///
+    /// 只约束之后的修改，初始化时的过滤器照常使用
+    pub fn floor_level(mut self, level: tracing::Level) -> Self {
+        self.floor_level = Some(level);
+        self
+    }
+
+    /// 时间戳格式，例如`TimeSourceTimer::default()`
+    ///
+    /// # tracing: local time print `<unknown time>`
+    ///
+    /// tracing_subscriber 版本 0.3.* 中使用`time`输出自定义时间时错误打印`<unknown time>`，使用`chrono`则无此问题。
+    ///
+    /// [subscriber: don't bail when timestamp formatting fails #1689](https://github.com/tokio-rs/tracing/pull/1689)
+    ///
+    /// [tracing_subscriber : The log CAN NOT display the time correctly in the LINUX with tracing_subscriber::fmt().with_timer(LocalTime::rfc_3339()) #2715](https://github.com/tokio-rs/tracing/issues/2715)
+    ///
+    /// [tracing_subscriber::fmt::time::LocalTime not working when multiple threads #2004](https://github.com/tokio-rs/tracing/issues/2004)
+    ///
+    /// [unable to get LocalTime on OpenBSD #2764](https://github.com/tokio-rs/tracing/issues/2764)
+    pub fn timer(mut self, timer: TimeSourceTimer) -> Self {
+        self.timer = timer;
+        self.custom_timer = true;
+        self.time_format = None;
+        self.utc = false;
+        self
+    }
+
+    /// 使用本地时间和 chrono 格式`format`输出时间戳，覆盖之前的`timer`；`LogMode::General`和`LogMode::Full`默认为`%Y-%m-%d %H:%M:%S%.3f %z`
+    ///
+    /// 例如`%H:%M:%S%.3f`，或 Unix 时间戳`%s%.3f`。格式为空或只有空白时`install`返回`InitLogError::EmptyTimeFormat`，
+    /// `build`则 panic；无法识别的格式不会 panic，只是时间戳为空。
+    ///
+    /// # Example
+    /// ```
+    /// use myutil::log::LogConfig;
+    ///
+    /// let _guard = LogConfig::new().time_format("%H:%M:%S%.3f").install().unwrap();
+    /// tracing::info!("hello"); // 12:00:00.000  INFO rust_out: hello
+    /// ```
+    pub fn time_format(mut self, format: &str) -> Self {
+        self.timer = TimeSourceTimer::new(ChronoLocal::new(format.to_string()));
+        self.custom_timer = true;
+        self.time_format = Some(format.to_string());
+        self
+    }
+
+    /// 以 UTC 输出时间戳，格式与`time_format`相同（没有设置时为`%Y-%m-%d %H:%M:%S%.3f %z`，
+    /// `LogFormat::Json`中为`%Y-%m-%dT%H:%M:%S%.3fZ`），默认`false`即本地时间
+    ///
+    /// 分布在不同时区的服务器输出一致的时间，也不受`timer`中提到的本地时间`<unknown time>`问题的影响。之后调用`timer`时取消。
+    ///
+    /// # Example
+    /// ```
+    /// use myutil::log::LogConfig;
+    ///
+    /// let _guard = LogConfig::new().time_format("%Y-%m-%dT%H:%M:%S%.3fZ").utc(true).install().unwrap();
+    /// tracing::info!("hello"); // 2024-05-01T04:00:00.000Z  INFO rust_out: hello
+    /// ```
+    pub fn utc(mut self, utc: bool) -> Self {
+        self.utc = utc;
+        self
+    }
+
+    /// `LogFormat::Json`中`timestamp`的格式，默认`TimestampFormat::Rfc3339`，见`JsonFormatter::with_timestamp_format`
+    ///
+    /// # Example
+    /// ```
+    /// use myutil::log::{JsonFields, LogConfig, LogFormat, TimestampFormat};
+    ///
+    /// let _guard = LogConfig::new()
+    ///     .format(LogFormat::Json(JsonFields::default()))
+    ///     .json_timestamp_format(TimestampFormat::EpochMillis)
+    ///     .install()
+    ///     .unwrap();
+    /// tracing::info!("hello"); // {"timestamp":1714536000000,"level":"INFO",...}
+    /// ```
+    pub fn json_timestamp_format(mut self, format: TimestampFormat) -> Self {
+        self.json_timestamp_format = format;
+        self
+    }
+
+    /// `LogFormat::Json`中每个 span 对象带上从创建到现在的`elapsed_ms`，见`JsonFormatter::with_span_elapsed`；默认关闭
+    ///
+    /// 开启时才添加记录 span 创建时刻的`span_timing_layer`
+    pub fn json_span_elapsed(mut self, span_elapsed: bool) -> Self {
+        self.json_span_elapsed = span_elapsed;
+        self
+    }
+
+    /// `LogFormat::Json`中输出`year`、`month`、`day`、`hour`分桶字段，见`JsonFormatter::with_date_buckets`；默认关闭
+    ///
+    /// 时区与`timestamp`一致：设置了`utc`时为 UTC，否则为本地时间或`timer`的时区
+    pub fn json_date_buckets(mut self, date_buckets: bool) -> Self {
+        self.json_date_buckets = date_buckets;
+        self
+    }
+
+    /// `LogFormat::Json`中输出初始化以来的毫秒数`uptime_ms`，见`JsonFormatter::with_uptime`；默认关闭
+    pub fn json_uptime(mut self, uptime: bool) -> Self {
+        self.json_uptime = uptime;
+        self
+    }
+
+    /// 日志输出位置，默认 stdout（`ProgressStdout`，见`new`）；带`flush = true`字段的事件也 flush 这个 writer
+    pub fn writer<W>(mut self, make_writer: W) -> Self
+        where
+            W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+    {
+        self.writer = SharedMakeWriter(Arc::new(BoxMakeWriter::new(make_writer)));
+        self.writer_kind = "custom";
+        self
+    }
+
+    /// 改为由后台线程写 stdout，输出日志的线程不再等待 stdout；默认同步写入
+    ///
+    /// 这种方式不与`progress`协同，进度行可能与日志交错。后台线程的生命周期见`non_blocking_writer`。
+    ///
+    /// # Example
+    /// ```
+    /// use myutil::log::LogConfig;
+    ///
+    /// let _guard = LogConfig::new().non_blocking_stdout().install().unwrap();
+    /// tracing::info!("written by the worker thread");
+    /// ```
+    pub fn non_blocking_stdout(self) -> Self {
+        LogConfig { writer_kind: "non_blocking_stdout", ..self.non_blocking_writer(std::io::stdout()) }
+    }
+
+    /// 日志输出到`writer`，由后台线程写入，见`non_blocking`
+    ///
+    /// 后台线程由`install`返回的`LogGuard`持有，drop 守卫时写完剩余的日志；
+    /// 使用`build`时由返回的 subscriber 持有，drop subscriber 时写完。
+    pub fn non_blocking_writer<W: std::io::Write + Send + 'static>(mut self, writer: W) -> Self {
+        let (writer, guard) = non_blocking(writer);
+        self.workers.push(guard);
+        self.writer(writer)
+    }
+
+    /// 同时把日志写一份到`file`（通常是`RollingFileAppender`），由后台线程写入
+    ///
+    /// 文件中为不含颜色的紧凑格式，target、行号、线程和时间设置与主输出相同，与主输出使用同一个过滤器，
+    /// 例如开发时终端输出彩色的`LogFormat::Pretty`，文件保留紧凑格式。后台线程的生命周期与`non_blocking_writer`相同。
+    ///
+    /// # Example
+    /// ```no_run
+    /// use myutil::log::{LogConfig, LogFormat, RollingFileAppender, Rotation};
+    ///
+    /// let appender = RollingFileAppender::new(Rotation::Daily, "logs", "app.log").expect("Failed to open log file");
+    /// let _guard = LogConfig::new()
+    ///     .ansi(true)
+    ///     .format(LogFormat::Pretty)
+    ///     .copy_to_file(appender)
+    ///     .install()
+    ///     .expect("Failed to initialize log");
+    /// tracing::info!("written to the terminal and logs/app.log.2024-01-31");
+    /// ```
+    pub fn copy_to_file<W: std::io::Write + Send + 'static>(mut self, file: W) -> Self {
+        let (writer, guard) = non_blocking(file);
+        self.file = Some(writer);
+        self.workers.push(guard);
+        self
+    }
+
+    /// 把 span 的`field`字段（如`request_id`）传给所有子孙 span，见`request_id_layer`；默认不传递
+    pub fn with_request_id(mut self, field: &'static str) -> Self {
+        self.request_id = Some(field);
+        self
+    }
+
+    /// 每条日志带上`parent_pid`字段（见`parent_pid`），把多进程流水线中子进程的日志关联到父进程；取不到时不输出。默认`false`
+    ///
+    /// 文本格式输出在行首，JSON 格式为顶层字段。父进程 id 在第一次使用时计算，之后不变。
+    pub fn with_parent_pid(mut self, parent_pid: bool) -> Self {
+        self.parent_pid = parent_pid;
+        self
+    }
+
+    /// 是否输出颜色，覆盖自动判断
+    ///
+    /// 默认自动判断：设置了`NO_COLOR`环境变量时不输出；输出到 stdout 时只在 stdout 是终端时输出，
+    /// 重定向到文件或管道时不输出；其它`writer`沿用`tracing_subscriber`的设置
+    pub fn ansi(mut self, ansi: bool) -> Self {
+        self.ansi = Some(ansi);
+        self
+    }
+
+    pub fn format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    /// 按`FieldFilter`筛选输出的字段，文本和 JSON 使用同一个规则；默认不筛选
+    ///
+    /// 作用于`Compact`、`Custom`、`Json`格式和`copy_to_file`的文件，包括事件字段和 span 字段，
+    /// 覆盖`CustomFormatter::with_field_filter`和`JsonFields::with_field_filter`的设置；`Pretty`和`Event`格式不受影响。
+    ///
+    /// # Example
+    /// ```
+    /// use myutil::log::{FieldFilter, JsonFields, LogConfig, LogFormat};
+    ///
+    /// let _guard = LogConfig::new()
+    ///     .format(LogFormat::Json(JsonFields::default()))
+    ///     .field_filter(FieldFilter::default().exclude(["password"]))
+    ///     .install()
+    ///     .unwrap();
+    /// tracing::info!(user = "alice", password = "secret", "login"); // {...,"message":"login","user":"alice"}
+    /// ```
+    pub fn field_filter(mut self, field_filter: FieldFilter) -> Self {
+        self.field_filter = Some(field_filter);
+        self
+    }
+
+    /// 按配置创建 subscriber，可用于`tracing::subscriber::with_default`
+    ///
+    /// # Panics
+    ///
+    /// 配置无效时 panic，即`install`会返回错误的情况，目前只有`time_format`为空
+    pub fn build(self) -> Box<dyn Subscriber + Send + Sync> {
+        self.build_with_reload().0
+    }
+
+    /// 与`build`相同，同时返回运行时修改过滤器的句柄；配置无效时同样 panic
+    pub fn build_with_reload(mut self) -> (Box<dyn Subscriber + Send + Sync>, LogReloadHandle) {
+        if let Err(err) = self.validate() {
+            panic!("invalid log config: {err}");
+        }
+        let workers = KeepWorkers { _workers: std::mem::take(&mut self.workers) };
+        let fmt_layer = self.fmt_layer();
+        let file_layer = self.file_layer();
+        let mirror = self.mirror.then(super::mirror::global_mirror_layer);
+        let filter = filter::with_target_levels(self.filter, &self.target_levels, self.env_var.as_deref());
+        let (filter, handle) = reload::Layer::new(filter);
+        let handle = LogReloadHandle::new(handle, self.floor_level);
+        let registry = tracing_subscriber::registry().with(mirror).with(filter);
+        let subscriber = Box::new(
+            registry
+                .with(fmt_layer)
+                .with(file_layer)
+                .with(self.request_id.map(super::request_id_layer))
+                .with(self.error_layer.then(tracing_error::ErrorLayer::default))
+                .with(self.json_span_elapsed.then(super::span_timing_layer))
+                .with(FlushLayer::new(FlushTargets { writer: self.writer, file: self.file }))
+                .with(workers),
+        );
+        (subscriber, handle)
+    }
+
+    fn fmt_layer(&self) -> Box<dyn Layer<LogSubscriber> + Send + Sync> {
+        let layer = tracing_subscriber::fmt::layer()
+            .with_writer(self.writer.clone())
+            .with_target(self.target)
+            .with_line_number(self.line_number)
+            .with_thread_names(self.thread_names)
+            .with_thread_ids(self.thread_ids)
+            .with_span_events(self.span_events.clone())
+            .with_timer(self.effective_timer());
+        let layer = match self.ansi.or_else(|| self.auto_ansi()) {
+            Some(ansi) => layer.with_ansi(ansi),
+            None => layer,
+        };
+        let parent_pid = self.parent_pid.then(super::parent_pid).flatten();
+        let field_filter = self.field_filter.clone();
+        match &self.format {
+            LogFormat::Compact => match field_filter {
+                Some(filter) => with_parent_pid(layer.compact().fmt_fields(filter), parent_pid),
+                None => with_parent_pid(layer.compact(), parent_pid),
+            },
+            LogFormat::Pretty => with_parent_pid(layer.pretty(), parent_pid),
+            LogFormat::Custom(formatter) => match field_filter {
+                Some(filter) => {
+                    let formatter = formatter.clone().with_field_filter(filter.clone());
+                    with_parent_pid(layer.fmt_fields(filter).event_format(formatter), parent_pid)
+                }
+                None => with_parent_pid(layer.event_format(formatter.clone()), parent_pid),
+            },
+            LogFormat::Event(formatter) => with_parent_pid(layer.event_format(formatter.clone()), parent_pid),
+            LogFormat::Json(fields) => {
+                let mut fields = fields.clone().with_parent_pid(parent_pid);
+                if let Some(filter) = field_filter {
+                    fields = fields.with_field_filter(filter);
+                }
+                let mut formatter = JsonFormatter::default()
+                    .with_timestamp_format(self.json_timestamp_format)
+                    .with_span_elapsed(self.json_span_elapsed)
+                    .with_date_buckets(self.json_date_buckets)
+                    .with_uptime(self.json_uptime);
+                if let Some(timer) = self.json_timer() {
+                    formatter = formatter.with_timer(timer);
+                }
+                Box::new(layer.fmt_fields(fields).event_format(formatter))
+            }
+            #[cfg(feature = "vector")]
+            LogFormat::Vector => {
+                let mut fields = JsonFields::default().with_typed_values(true).with_parent_pid(parent_pid);
+                if let Some(filter) = field_filter {
+                    fields = fields.with_field_filter(filter);
+                }
+                Box::new(layer.fmt_fields(fields).event_format(super::VectorFormatter::default()))
+            }
+        }
+    }
+
+    /// `utc`时按`time_format`改用`ChronoUtc`
+    fn effective_timer(&self) -> TimeSourceTimer {
+        if !self.utc {
+            return self.timer.clone();
+        }
+        let format = self.time_format.as_deref().unwrap_or(DEFAULT_TIME_FORMAT);
+        TimeSourceTimer::new(ChronoUtc::new(format.to_string()))
+    }
+
+    /// `build`和`install`共用的检查
+    fn validate(&self) -> Result<(), InitLogError> {
+        if self.time_format.as_deref().is_some_and(|format| format.trim().is_empty()) {
+            return Err(InitLogError::EmptyTimeFormat);
+        }
+        Ok(())
+    }
+
+    /// JSON 的`timestamp`：没有设置`timer`、`time_format`或`utc`时为`None`，即`JsonFormatter`默认的本地 RFC 3339
+    fn json_timer(&self) -> Option<TimeSourceTimer> {
+        if self.utc {
+            let format = self.time_format.as_deref().unwrap_or(RFC3339_UTC_FORMAT);
+            return Some(TimeSourceTimer::new(ChronoUtc::new(format.to_string())));
+        }
+        self.custom_timer.then(|| self.timer.clone())
+    }
+
+    fn auto_ansi(&self) -> Option<bool> {
+        let no_color = std::env::var_os("NO_COLOR");
+        auto_ansi(no_color.as_deref(), (self.writer_kind != "custom").then(|| std::io::stdout().is_terminal()))
+    }
+
+    fn file_layer<S>(&self) -> Option<impl Layer<S> + Send + Sync>
+        where
+            S: Subscriber + for<'span> LookupSpan<'span>,
+    {
+        let file = self.file.clone()?;
+        let layer = tracing_subscriber::fmt::layer()
+            .with_writer(file)
+            .with_ansi(false)
+            .with_target(self.target)
+            .with_line_number(self.line_number)
+            .with_thread_names(self.thread_names)
+            .with_thread_ids(self.thread_ids)
+            .with_span_events(self.span_events.clone())
+            .with_timer(self.effective_timer())
+            .compact()
+            // 默认的 FieldFilter 不筛选，格式与 DefaultFields 相同
+            .fmt_fields(self.field_filter.clone().unwrap_or_default());
+        Some(layer)
+    }
+
+    /// 设置为全局默认 subscriber，并接收标准库`log`的事件；返回的`LogGuard`需要保存到程序结束，可从中取得`LogReloadHandle`
+    pub fn install(mut self) -> Result<LogGuard, InitLogError> {
+        self.validate()?;
+        let installed = Installed { mode: self.mode, writer: self.writer_kind };
+        // 全局 subscriber 不会被 drop，后台线程改由守卫持有
+        let workers = std::mem::take(&mut self.workers);
+        let (subscriber, handle) = self.build_with_reload();
+        Ok(super::install(subscriber, Some(handle), installed)?.with_workers(workers))
+    }
+}
+
+/// `FlushLayer`使用的 writer：flush 时 flush 主输出和`copy_to_file`的文件
+struct FlushTargets {
+    writer: SharedMakeWriter,
+    file: Option<NonBlocking>,
+}
+
+impl<'a> MakeWriter<'a> for FlushTargets {
+    type Writer = FlushAll<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        FlushAll { writer: self.writer.make_writer(), file: self.file.clone() }
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        FlushAll { writer: self.writer.make_writer_for(meta), file: self.file.clone() }
+    }
+}
+
+/// 只用于 flush，写入的内容交给主输出
+struct FlushAll<'a> {
+    writer: Box<dyn Write + 'a>,
+    file: Option<NonBlocking>,
+}
+
+impl Write for FlushAll<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let result = self.writer.flush();
+        if let Some(file) = &mut self.file {
+            file.flush()?;
+        }
+        result
+    }
+}
+
+/// 不处理事件，只让`build`返回的 subscriber 持有后台写入线程，drop 时写完剩余的日志
+struct KeepWorkers {
+    _workers: Vec<WorkerGuard>,
+}
+
+impl<S: Subscriber> Layer<S> for KeepWorkers {}
+
+/// 文本格式在每行开头输出`parent_pid=42`
+fn with_parent_pid<N, E, W>(layer: fmt::Layer<LogSubscriber, N, E, W>, parent_pid: Option<u32>) -> Box<dyn Layer<LogSubscriber> + Send + Sync>
+    where
+        N: for<'writer> FormatFields<'writer> + Send + Sync + 'static,
+        E: FormatEvent<LogSubscriber, N> + Send + Sync + 'static,
+        W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    match parent_pid {
+        Some(pid) => Box::new(layer.map_event_format(|inner| WithStaticFields { fields: format!("parent_pid={pid}"), inner })),
+        None => Box::new(layer),
+    }
+}
+
+/// `NO_COLOR`为非空时不输出颜色，否则在已知输出是否为终端时以此决定，未知（`None`）时不干预
+fn auto_ansi(no_color: Option<&std::ffi::OsStr>, is_terminal: Option<bool>) -> Option<bool> {
+    if no_color.is_some_and(|value| !value.is_empty()) {
+        return Some(false);
+    }
+    is_terminal
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use chrono::{Datelike, Timelike};
+    use tracing_core::{Event, Subscriber};
+    use tracing_subscriber::fmt::{format, FmtContext, FormatEvent, FormatFields};
+    use tracing_subscriber::fmt::format::FmtSpan;
+    use tracing_subscriber::registry::LookupSpan;
+
+    use crate::log::{CustomFormatter, EventFormatter, FieldFilter, InitLogError, JsonFields, LogConfig, LogFormat, TimeSource, TimeSourceTimer, TimestampFormat};
+    use crate::log::tests::TestWriter;
+
+    struct Bracketed;
+
+    impl<S, N> FormatEvent<S, N> for Bracketed
+        where
+            S: Subscriber + for<'a> LookupSpan<'a>,
+            N: for<'a> FormatFields<'a> + 'static,
+    {
+        fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: format::Writer<'_>, event: &Event<'_>) -> std::fmt::Result {
+            write!(writer, "[{}] ", event.metadata().level())?;
+            ctx.field_format().format_fields(writer.by_ref(), event)?;
+            writeln!(writer)
+        }
+    }
+
+    #[test]
+    fn user_event_format() {
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = LogConfig::new()
+            .writer(move || make_writer.clone())
+            .ansi(false)
+            .format(LogFormat::Event(EventFormatter::new(Bracketed)))
+            .build();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(user = "alice", "login");
+        });
+        assert_eq!(writer.output(), "[INFO] login user=\"alice\"\n");
+    }
+
+    #[test]
+    fn span_close_events() {
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = LogConfig::new()
+            .writer(move || make_writer.clone())
+            .ansi(false)
+            .span_events(FmtSpan::CLOSE)
+            .build();
+        tracing::subscriber::with_default(subscriber, || {
+            let _load = tracing::info_span!("load", file = "a.csv").entered();
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        });
+
+        let output = writer.output();
+        assert_eq!(output.lines().count(), 1, "{output}");
+        assert!(output.contains("INFO load: myutil::log::config::tests: close time.busy="), "{output}");
+        let busy = output.split("time.busy=").nth(1).and_then(|rest| rest.split(' ').next()).unwrap();
+        assert!(busy.ends_with("ms"), "{output}");
+    }
+
+    #[test]
+    fn custom_time_format() {
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = LogConfig::new()
+            .time_format("at %Y")
+            .writer(move || make_writer.clone())
+            .ansi(false)
+            .build();
+        tracing::subscriber::with_default(subscriber, || tracing::info!("hello"));
+
+        let output = writer.output();
+        let year = chrono::Local::now().format("%Y").to_string();
+        assert!(output.starts_with(&format!("at {year}  INFO ")), "{output}");
+
+        assert!(matches!(LogConfig::new().time_format(" ").install(), Err(InitLogError::EmptyTimeFormat)));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid log config: log time format is empty")]
+    fn build_rejects_empty_time_format() {
+        let _ = LogConfig::new().time_format("  ").build();
+    }
+
+    #[test]
+    fn utc_timestamp() {
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = LogConfig::new()
+            .utc(true)
+            .time_format("%Y-%m-%dT%H:%M:%S%.3f %z")
+            .writer(move || make_writer.clone())
+            .ansi(false)
+            .build();
+        let before = chrono::Utc::now();
+        tracing::subscriber::with_default(subscriber, || tracing::info!("hello"));
+        let after = chrono::Utc::now();
+
+        let output = writer.output();
+        let (timestamp, _) = output.split_once("  INFO ").unwrap();
+        let logged = chrono::DateTime::parse_from_str(timestamp, "%Y-%m-%dT%H:%M:%S%.3f %z").unwrap();
+        assert_eq!(logged.offset().local_minus_utc(), 0, "{output}");
+        assert!(before.timestamp_millis() <= logged.timestamp_millis() && logged.timestamp_millis() <= after.timestamp_millis(), "{output}");
+    }
+
+    #[test]
+    fn json_utc_and_time_source() {
+        struct FixedClock;
+
+        impl TimeSource for FixedClock {
+            fn now_formatted(&self) -> String {
+                "hlc-42".to_string()
+            }
+        }
+
+        let capture = |config: LogConfig| {
+            let writer = TestWriter::default();
+            let make_writer = writer.clone();
+            let subscriber = config.format(LogFormat::Json(JsonFields::default())).writer(move || make_writer.clone()).build();
+            tracing::subscriber::with_default(subscriber, || tracing::info!("hello"));
+            let output = writer.output();
+            let timestamp = output.strip_prefix(r#"{"timestamp":""#).and_then(|rest| rest.split_once('"')).unwrap().0.to_string();
+            (timestamp, output)
+        };
+
+        let (timestamp, output) = capture(LogConfig::new().utc(true));
+        assert!(timestamp.ends_with('Z'), "{output}");
+        assert!(chrono::DateTime::parse_from_rfc3339(&timestamp).is_ok(), "{output}");
+        let (timestamp, output) = capture(LogConfig::new().time_format("%Y-%m-%dT%H:%M:%S%:z").utc(true));
+        assert!(timestamp.ends_with("+00:00"), "{output}");
+        let (timestamp, _) = capture(LogConfig::new().timer(TimeSourceTimer::new(FixedClock)));
+        assert_eq!(timestamp, "hlc-42");
+
+        // 分桶字段与 UTC 时间戳使用同一时区
+        let (timestamp, output) = capture(LogConfig::new().utc(true).json_date_buckets(true));
+        let utc = chrono::DateTime::parse_from_rfc3339(&timestamp).unwrap();
+        let buckets = format!(r#","year":{},"month":{},"day":{},"hour":{},"#, utc.year(), utc.month(), utc.day(), utc.hour());
+        assert!(output.contains(&buckets), "{output}");
+        let (timestamp, output) = capture(LogConfig::new());
+        assert!(chrono::DateTime::parse_from_rfc3339(&timestamp).is_ok(), "{output}");
+    }
+
+    #[test]
+    fn json_and_text_field_filter() {
+        let json = TestWriter::default();
+        let make_writer = json.clone();
+        let file = TestWriter::default();
+        let filter = FieldFilter::default().exclude(["password"]);
+        let subscriber = LogConfig::new()
+            .format(LogFormat::Json(JsonFields::default()))
+            .field_filter(filter)
+            .writer(move || make_writer.clone())
+            .copy_to_file(file.clone())
+            .build();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(user = "alice", password = "secret", "login");
+        });
+
+        let json = json.output();
+        assert!(json.contains(r#""message":"login","user":"alice"}"#), "{json}");
+        let file = file.output();
+        assert!(file.trim_end().ends_with(" login user=\"alice\""), "{file}");
+        assert!(!json.contains("secret") && !file.contains("secret"), "{json}{file}");
+    }
+
+    #[test]
+    fn flush_field_flushes_file_and_is_hidden() {
+        #[derive(Clone, Default)]
+        struct FlushCount(Arc<AtomicUsize>);
+
+        impl std::io::Write for FlushCount {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            }
+        }
+
+        for format in [LogFormat::Custom(CustomFormatter::default()), LogFormat::Json(JsonFields::default())] {
+            let writer = TestWriter::default();
+            let make_writer = writer.clone();
+            let file = FlushCount::default();
+            let subscriber = LogConfig::new()
+                .format(format)
+                .ansi(false)
+                .writer(move || make_writer.clone())
+                .copy_to_file(file.clone())
+                .build();
+            tracing::subscriber::with_default(subscriber, || {
+                tracing::info!(order_id = 42, "created");
+                tracing::info!(flush = true, order_id = 42, "payment completed");
+                // flush 等待后台线程写完，返回时文件已经 flush
+                assert_eq!(file.0.load(Ordering::SeqCst), 1);
+            });
+
+            let output = writer.output();
+            assert!(output.contains("payment completed") && !output.contains("flush"), "{output}");
+        }
+    }
+
+    #[test]
+    fn json_span_elapsed() {
+        let capture = |span_elapsed| {
+            let writer = TestWriter::default();
+            let make_writer = writer.clone();
+            let subscriber = LogConfig::new()
+                .format(LogFormat::Json(JsonFields::default()))
+                .json_span_elapsed(span_elapsed)
+                .writer(move || make_writer.clone())
+                .build();
+            tracing::subscriber::with_default(subscriber, || {
+                let _span = tracing::info_span!("request").entered();
+                tracing::info!("hello");
+            });
+            writer.output()
+        };
+        let output = capture(true);
+        assert!(output.contains(r#""span":{"name":"request","elapsed_ms":"#), "{output}");
+        let output = capture(false);
+        assert!(!output.contains("elapsed_ms"), "{output}");
+    }
+
+    #[test]
+    fn json_epoch_timestamp() {
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = LogConfig::new()
+            .format(LogFormat::Json(JsonFields::default()))
+            .json_timestamp_format(TimestampFormat::EpochSeconds)
+            .writer(move || make_writer.clone())
+            .build();
+        tracing::subscriber::with_default(subscriber, || tracing::info!("hello"));
+
+        let output = writer.output();
+        let seconds = output.strip_prefix(r#"{"timestamp":"#).and_then(|rest| rest.split_once(',')).unwrap().0;
+        assert!(seconds.contains('.'), "{output}");
+        assert!((seconds.parse::<f64>().unwrap() - chrono::Utc::now().timestamp() as f64).abs() <= 1.0, "{output}");
+    }
+
+    #[test]
+    fn ansi_disabled() {
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = LogConfig::new()
+            .with_line_number(true)
+            .writer(move || make_writer.clone())
+            .ansi(false)
+            .format(LogFormat::Pretty)
+            .build();
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = tracing::info_span!("request", id = 7).entered();
+            tracing::warn!(user = "alice", "login");
+        });
+
+        let output = writer.output();
+        assert!(output.contains("login"), "{output}");
+        assert!(!output.contains('\x1b'), "{output}");
+    }
+
+    #[test]
+    fn ansi_auto_detection() {
+        use std::ffi::OsStr;
+
+        use super::auto_ansi;
+
+        assert_eq!(auto_ansi(None, Some(true)), Some(true));
+        assert_eq!(auto_ansi(None, Some(false)), Some(false));
+        assert_eq!(auto_ansi(None, None), None);
+        assert_eq!(auto_ansi(Some(OsStr::new("1")), Some(true)), Some(false));
+        assert_eq!(auto_ansi(Some(OsStr::new("1")), None), Some(false));
+        // 空的 NO_COLOR 视为没有设置
+        assert_eq!(auto_ansi(Some(OsStr::new("")), Some(true)), Some(true));
+    }
+
+    #[test]
+    fn per_target_levels() {
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = LogConfig::new()
+            .level(tracing::Level::WARN)
+            .target_level("myutil::log::config", tracing::Level::DEBUG)
+            .target_level("noisy", tracing::Level::ERROR)
+            .writer(move || make_writer.clone())
+            .ansi(false)
+            .build();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("shown");
+            tracing::info!(target: "other", "hidden");
+            tracing::warn!(target: "noisy", "hidden");
+            tracing::warn!(target: "other", "also shown");
+        });
+
+        let output = writer.output();
+        assert_eq!(output.lines().count(), 2, "{output}");
+        assert!(output.contains("shown") && output.contains("also shown"), "{output}");
+    }
+
+    #[test]
+    fn console_and_file() {
+        let console = TestWriter::default();
+        let make_writer = console.clone();
+        let file = TestWriter::default();
+        let config = LogConfig::new()
+            .level(tracing::Level::DEBUG)
+            .writer(move || make_writer.clone())
+            .ansi(true)
+            .format(LogFormat::Pretty)
+            .copy_to_file(file.clone());
+        // subscriber 持有写文件的后台线程，离开 with_default 时 drop 并写完
+        tracing::subscriber::with_default(config.build(), || {
+            tracing::debug!(user = "alice", "login");
+            tracing::trace!("hidden");
+        });
+
+        let console = console.output();
+        assert!(console.contains("\x1b["), "{console}");
+        assert!(console.contains("login"), "{console}");
+        let file = file.output();
+        assert_eq!(file.lines().count(), 1, "{file}");
+        assert!(!file.contains("\x1b["), "{file}");
+        assert!(file.trim_end().ends_with(" DEBUG myutil::log::config::tests: login user=\"alice\""), "{file}");
+    }
+
+    #[test]
+    fn config_build() {
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = LogConfig::new()
+            .level(tracing::Level::DEBUG)
+            .with_target(false)
+            .with_line_number(true)
+            .writer(move || make_writer.clone())
+            .ansi(false)
+            .format(LogFormat::Compact)
+            .build();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!(user = "alice", "login");
+            tracing::trace!("hidden");
+        });
+
+        let output = writer.output();
+        assert_eq!(output.lines().count(), 1, "{output}");
+        assert!(output.trim_end().ends_with(&format!("DEBUG {}: login user=\"alice\"", line!() - 6)), "{output}");
+    }
+}
@@ -0,0 +1,130 @@
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard};
+
+use tracing::Level;
+use tracing_core::{Event, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+use super::EventFieldVisitor;
+
+struct Window {
+    capacity: usize,
+    lines: VecDeque<String>,
+}
+
+static WINDOW: Mutex<Window> = Mutex::new(Window { capacity: 0, lines: VecDeque::new() });
+
+fn window() -> MutexGuard<'static, Window> {
+    WINDOW.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// 始终缓存最近的 debug/trace 日志，调用`flush_context_window`时再输出，见`context_window_layer`
+pub struct ContextWindowLayer;
+
+/// 创建缓存最近`capacity`条 debug/trace 日志的 layer
+///
+/// 平时不输出 debug 日志，处理错误前调用`flush_context_window`即可看到出错前发生了什么。
+/// layer 需要收到 debug 事件，所以输出 layer 要用自己的过滤器（`with_filter`），不能使用全局过滤器。
+/// 缓存是进程内共享的，多次创建只保留最后一次的`capacity`。
+///
+/// # Example
+/// ```
+/// use tracing_subscriber::filter::LevelFilter;
+/// use tracing_subscriber::layer::SubscriberExt;
+/// use tracing_subscriber::Layer;
+/// use myutil::log::{context_window_layer, flush_context_window};
+///
+/// let subscriber = tracing_subscriber::registry()
+///     .with(context_window_layer(100))
+///     .with(tracing_subscriber::fmt::layer().with_filter(LevelFilter::INFO));
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::debug!("connecting");
+///     tracing::info!("start"); // 正常输出
+///     flush_context_window(); // 以 info 级别输出: connecting
+///     tracing::error!("failed");
+/// });
+/// ```
+pub fn context_window_layer(capacity: usize) -> ContextWindowLayer {
+    let mut window = window();
+    window.capacity = capacity;
+    while window.lines.len() > capacity {
+        window.lines.pop_front();
+    }
+    ContextWindowLayer
+}
+
+/// 以 info 级别按顺序输出缓存的 debug/trace 日志并清空缓存，附带`context_window = true`字段
+pub fn flush_context_window() {
+    // 先取出再输出，输出时不持有锁
+    let lines = std::mem::take(&mut window().lines);
+    for line in lines {
+        tracing::info!(context_window = true, "{line}");
+    }
+}
+
+impl<S: Subscriber> Layer<S> for ContextWindowLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        if *metadata.level() < Level::DEBUG {
+            return;
+        }
+
+        let mut visitor = EventFieldVisitor::default();
+        event.record(&mut visitor);
+        let mut line = format!("{} {}: {}", metadata.level(), metadata.target(), visitor.message.unwrap_or_default());
+        for (name, value) in &visitor.fields {
+            line.push_str(&format!(" {name}={value}"));
+        }
+
+        let mut window = window();
+        if window.capacity == 0 {
+            return;
+        }
+        if window.lines.len() == window.capacity {
+            window.lines.pop_front();
+        }
+        window.lines.push_back(line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::filter::LevelFilter;
+    use tracing_subscriber::layer::SubscriberExt;
+    use tracing_subscriber::Layer;
+
+    use crate::log::{context_window_layer, CustomFormatter, flush_context_window};
+    use crate::log::tests::TestWriter;
+
+    #[test]
+    fn context_window_keeps_last_lines() {
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let fmt_layer = tracing_subscriber::fmt::layer()
+            .with_ansi(false)
+            .with_writer(move || make_writer.clone())
+            .event_format(CustomFormatter::default())
+            .with_filter(LevelFilter::INFO);
+        let subscriber = tracing_subscriber::registry()
+            .with(context_window_layer(2))
+            .with(fmt_layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::debug!("step 1");
+            tracing::trace!(id = 2, "step 2");
+            tracing::debug!("step 3");
+            tracing::info!("before error");
+            flush_context_window();
+            tracing::error!("failed");
+            flush_context_window();
+        });
+
+        let output = writer.output();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 4, "{output}");
+        assert!(lines[0].ends_with(" -> before error"), "{output}");
+        assert!(lines[1].ends_with(" -> TRACE myutil::log::context::tests: step 2 id=2 context_window=true"), "{output}");
+        assert!(lines[2].ends_with(" -> DEBUG myutil::log::context::tests: step 3 context_window=true"), "{output}");
+        assert!(lines[3].ends_with(" -> failed"), "{output}");
+    }
+}
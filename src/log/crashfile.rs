@@ -0,0 +1,74 @@
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use crate::log::{LogConfig, TimeSourceTimer};
+
+static CRASH_LOG_PATH: OnceLock<PathBuf> = OnceLock::new();
+
+/// 桌面程序用：日志写入临时目录下的`<程序名>.log`，返回文件路径，便于界面提供“打开日志”
+///
+/// 上一次运行的日志保留为`<程序名>.prev.log`。安装了`init_error_hook`时，panic 报告中会提示该文件路径，方便用户附到 bug 报告里。
+/// 格式与`LogMode::General`相同，不含颜色。
+///
+/// # Example
+/// ```no_run
+/// let path = myutil::log::init_log_crashfile(tracing::Level::INFO).expect("Failed to create log file");
+/// println!("logs: {}", path.display());
+/// ```
+pub fn init_log_crashfile(log_level: tracing::Level) -> std::io::Result<PathBuf> {
+    let name = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.file_stem().map(|stem| stem.to_string_lossy().into_owned()))
+        .unwrap_or_else(|| "myutil".to_string());
+    let dir = std::env::temp_dir();
+    let path = dir.join(format!("{name}.log"));
+    rotate_previous(&path, &dir.join(format!("{name}.prev.log")));
+
+    let file = Arc::new(File::create(&path)?);
+    let config = LogConfig::new()
+        .level(log_level)
+        .with_line_number(true)
+        .timer(TimeSourceTimer::default())
+        .writer(file)
+        .ansi(false);
+    drop(config.install().expect("Could not set global default logger"));
+
+    let _ = CRASH_LOG_PATH.set(path.clone());
+    Ok(path)
+}
+
+/// `init_log_crashfile`写入的日志文件路径
+pub fn crash_log_path() -> Option<&'static Path> {
+    CRASH_LOG_PATH.get().map(PathBuf::as_path)
+}
+
+fn rotate_previous(path: &Path, previous: &Path) {
+    if path.exists() {
+        let _ = std::fs::rename(path, previous);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::rotate_previous;
+
+    #[test]
+    fn rotate_keeps_last_session() {
+        let dir = std::env::temp_dir().join(format!("myutil-crashfile-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        let previous = dir.join("app.prev.log");
+
+        std::fs::write(&path, "first session").unwrap();
+        rotate_previous(&path, &previous);
+        assert!(!path.exists());
+        assert_eq!(std::fs::read_to_string(&previous).unwrap(), "first session");
+
+        // 当前没有日志文件时保留旧的 prev
+        rotate_previous(&path, &previous);
+        assert_eq!(std::fs::read_to_string(&previous).unwrap(), "first session");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
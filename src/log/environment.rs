@@ -0,0 +1,89 @@
+/// 名称中含有这些词的环境变量视为敏感信息
+const SECRET_MARKERS: &[&str] = &["KEY", "SECRET", "TOKEN", "PASSWORD", "PASSWD", "CREDENTIAL", "PRIVATE", "AUTH"];
+
+/// 以一条 info 事件输出启动环境：os、arch、cpus、hostname、cwd 和允许列表中的环境变量
+///
+/// `allowlist`中的名称精确匹配时总会输出；以`*`结尾的表示前缀匹配，匹配到的敏感变量（名称含 KEY、TOKEN、PASSWORD 等）会被跳过，
+/// 敏感变量只有被精确列出时才会输出
+///
+/// # Example
+/// ```
+/// myutil::log::log_environment(&["RUST_LOG", "RUST_BACKTRACE", "APP_*"]);
+/// ```
+pub fn log_environment(allowlist: &[&str]) {
+    let cwd = std::env::current_dir().map(|dir| dir.display().to_string()).unwrap_or_default();
+    let cpus = std::thread::available_parallelism().map(|n| n.get()).unwrap_or(1);
+    let env = select_env(std::env::vars(), allowlist)
+        .into_iter()
+        .map(|(name, value)| format!("{name}={value}"))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    tracing::info!(
+        os = std::env::consts::OS,
+        arch = std::env::consts::ARCH,
+        cpus,
+        hostname = %hostname(),
+        cwd = %cwd,
+        env = %env,
+        "environment",
+    );
+}
+
+fn hostname() -> String {
+    std::env::var("HOSTNAME")
+        .or_else(|_| std::env::var("COMPUTERNAME"))
+        .ok()
+        .or_else(|| std::fs::read_to_string("/proc/sys/kernel/hostname").ok().map(|name| name.trim().to_string()))
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn is_secret(name: &str) -> bool {
+    let name = name.to_ascii_uppercase();
+    SECRET_MARKERS.iter().any(|marker| name.contains(marker))
+}
+
+/// 按允许列表筛选环境变量，结果按名称排序
+fn select_env(vars: impl Iterator<Item = (String, String)>, allowlist: &[&str]) -> Vec<(String, String)> {
+    let mut selected: Vec<(String, String)> = vars
+        .filter(|(name, _)| {
+            allowlist.iter().any(|pattern| match pattern.strip_suffix('*') {
+                Some(prefix) => name.starts_with(prefix) && !is_secret(name),
+                None => name == pattern,
+            })
+        })
+        .collect();
+    selected.sort();
+    selected
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::log::environment::select_env;
+    use crate::log::{CustomFormatter, log_environment};
+    use crate::log::tests::capture_custom;
+
+    #[test]
+    fn select_env_skips_secrets() {
+        let vars = [
+            ("APP_NAME", "demo"),
+            ("APP_TOKEN", "t0ken"),
+            ("APP_DB_PASSWORD", "hunter2"),
+            ("DEPLOY_KEY", "k"),
+            ("HOME", "/root"),
+        ]
+        .into_iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()));
+
+        let selected = select_env(vars, &["APP_*", "DEPLOY_KEY"]);
+        let names: Vec<&str> = selected.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["APP_NAME", "DEPLOY_KEY"]);
+    }
+
+    #[test]
+    fn log_environment_fields() {
+        let output = capture_custom(CustomFormatter::default(), || log_environment(&[]));
+        assert!(output.contains(&format!(" -> environment os=\"{}\" arch=\"{}\" cpus=", std::env::consts::OS, std::env::consts::ARCH)), "{output}");
+        assert!(output.contains(" hostname=") && output.contains(" cwd=") && output.contains(" env="), "{output}");
+    }
+}
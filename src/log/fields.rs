@@ -0,0 +1,150 @@
+use std::sync::Arc;
+
+use tracing::field::{Field, Visit};
+use tracing_subscriber::field::{MakeVisitor, VisitFmt, VisitOutput};
+use tracing_subscriber::fmt::format::{DefaultVisitor, Writer};
+
+#[derive(Debug, Clone, Default)]
+struct FieldLists {
+    include: Option<Vec<String>>,
+    exclude: Vec<String>,
+}
+
+/// 按字段名筛选输出的字段，用于只把安全的字段子集输出到某些目标
+///
+/// 设置了`include`时只输出其中的字段，`exclude`中的字段总是不输出；`message`不受影响。
+/// 作为`fmt_fields`使用时格式与`DefaultFields`相同，同时作用于 span 和事件的字段；
+/// `CustomFormatter::with_field_filter`只作用于事件字段，span 字段仍需通过`fmt_fields`筛选；
+/// JSON 格式使用`JsonFields::with_field_filter`，`LogConfig::field_filter`同时设置文本和 JSON 格式。
+///
+/// # Example
+/// ```
+/// use myutil::log::{CustomFormatter, FieldFilter};
+///
+/// let filter = FieldFilter::default().exclude(["password"]);
+/// let subscriber = tracing_subscriber::fmt()
+///     .fmt_fields(filter.clone())
+///     .event_format(CustomFormatter::default().with_field_filter(filter))
+///     .finish();
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info!(user = "alice", password = "secret", "login"); // ... -> login user="alice"
+/// });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FieldFilter(Arc<FieldLists>);
+
+impl FieldFilter {
+    /// 只输出这些字段
+    pub fn include<I: IntoIterator<Item = S>, S: Into<String>>(mut self, fields: I) -> Self {
+        Arc::make_mut(&mut self.0).include = Some(fields.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// 不输出这些字段
+    pub fn exclude<I: IntoIterator<Item = S>, S: Into<String>>(mut self, fields: I) -> Self {
+        Arc::make_mut(&mut self.0).exclude.extend(fields.into_iter().map(Into::into));
+        self
+    }
+
+    /// 字段是否输出
+    pub fn allows(&self, name: &str) -> bool {
+        if name == "message" {
+            return true;
+        }
+        let lists = &self.0;
+        if lists.exclude.iter().any(|field| field == name) {
+            return false;
+        }
+        match &lists.include {
+            Some(include) => include.iter().any(|field| field == name),
+            None => true,
+        }
+    }
+}
+
+impl<'a> MakeVisitor<Writer<'a>> for FieldFilter {
+    type Visitor = FilteredVisitor<'a>;
+
+    fn make_visitor(&self, target: Writer<'a>) -> Self::Visitor {
+        FilteredVisitor {
+            filter: self.clone(),
+            inner: DefaultVisitor::new(target, true),
+        }
+    }
+}
+
+/// 跳过被筛掉的字段，其余交给`DefaultVisitor`
+pub struct FilteredVisitor<'a> {
+    filter: FieldFilter,
+    inner: DefaultVisitor<'a>,
+}
+
+impl Visit for FilteredVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if self.filter.allows(field.name()) {
+            self.inner.record_str(field, value);
+        }
+    }
+
+    fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+        if self.filter.allows(field.name()) {
+            self.inner.record_error(field, value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if self.filter.allows(field.name()) {
+            self.inner.record_debug(field, value);
+        }
+    }
+}
+
+impl VisitOutput<std::fmt::Result> for FilteredVisitor<'_> {
+    fn finish(self) -> std::fmt::Result {
+        self.inner.finish()
+    }
+}
+
+impl VisitFmt for FilteredVisitor<'_> {
+    fn writer(&mut self) -> &mut dyn std::fmt::Write {
+        self.inner.writer()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::log::{CustomFormatter, FieldFilter};
+    use crate::log::tests::TestWriter;
+
+    fn capture(filter: FieldFilter) -> String {
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || make_writer.clone())
+            .fmt_fields(filter.clone())
+            .event_format(CustomFormatter::default())
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            let _span = tracing::info_span!("req", tenant = "a", token = "t").entered();
+            tracing::info!(user = "alice", password = "secret", "login");
+        });
+        writer.output()
+    }
+
+    #[test]
+    fn field_filter() {
+        let output = capture(FieldFilter::default().exclude(["password", "token"]));
+        assert!(output.contains(" -> req{tenant=\"a\"}: login user=\"alice\""), "{output}");
+
+        let output = capture(FieldFilter::default().include(["user", "password"]).exclude(["password"]));
+        assert!(output.contains(" -> req: login user=\"alice\""), "{output}");
+
+        let filter = FieldFilter::default().include(["user"]);
+        let output = crate::log::tests::capture_custom(
+            CustomFormatter::default().with_field_filter(filter).with_fields_before_message(true),
+            || tracing::info!(user = "alice", password = "secret", "login"),
+        );
+        assert!(output.ends_with(" -> user=\"alice\" login\n"), "{output}");
+    }
+}
@@ -0,0 +1,177 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use tracing::Level;
+use tracing_subscriber::EnvFilter;
+
+use crate::log::init_report;
+use crate::log::level_to_string;
+
+static RUST_LOG_HINT: AtomicBool = AtomicBool::new(true);
+static HINTED: AtomicBool = AtomicBool::new(false);
+
+/// 只关心自己日志的常用过滤器：`my_crate`使用`level`，其它 target（依赖库）只输出 warn 及以上
+///
+/// 设置了`RUST_LOG`时，其中的规则追加在后面，可覆盖以上默认值
+///
+/// # Example
+/// ```
+/// use myutil::log::sensible_filter;
+///
+/// let filter = sensible_filter("my_app", tracing::Level::DEBUG);
+/// assert_eq!(filter.max_level_hint(), Some(tracing::level_filters::LevelFilter::DEBUG));
+/// ```
+pub fn sensible_filter(my_crate: &str, level: Level) -> EnvFilter {
+    let env = std::env::var(EnvFilter::DEFAULT_ENV).unwrap_or_default();
+    sensible_filter_with(my_crate, level, &env)
+}
+
+fn sensible_filter_with(my_crate: &str, level: Level, env: &str) -> EnvFilter {
+    let mut directives = format!("warn,{}={}", my_crate.replace('-', "_"), level_to_string(level));
+    if !env.trim().is_empty() {
+        directives.push(',');
+        directives.push_str(env);
+    }
+    EnvFilter::builder().parse_lossy(directives)
+}
+
+/// 从环境变量`var`读取过滤规则，没有设置或无法解析时使用`fallback`
+pub(crate) fn env_filter_or(var: &str, fallback: EnvFilter) -> EnvFilter {
+    env_filter_from(var, std::env::var(var).ok().as_deref(), fallback)
+}
+
+fn env_filter_from(var: &str, value: Option<&str>, fallback: EnvFilter) -> EnvFilter {
+    match value.filter(|value| !value.trim().is_empty()) {
+        Some(value) => EnvFilter::builder().parse(value).unwrap_or_else(|err| {
+            init_report::record_fallback(format!("invalid {var} `{value}` ({err}), using `{fallback}`"));
+            fallback
+        }),
+        None => fallback,
+    }
+}
+
+/// 在`base`之后追加按 target 设置的级别（`target=level`），再追加环境变量`var`中的规则，后面的规则覆盖前面同一 target 的设置
+///
+/// `var`为`None`时不读取环境变量；变量中的规则无法解析时忽略整个变量并记录原因
+pub(crate) fn with_target_levels(base: EnvFilter, target_levels: &[String], var: Option<&str>) -> EnvFilter {
+    let env = var.and_then(|var| {
+        let value = std::env::var(var).ok()?;
+        match EnvFilter::builder().parse(&value) {
+            Ok(_) => Some(value),
+            Err(err) => {
+                init_report::record_fallback(format!("invalid {var} `{value}` ({err}), ignoring it"));
+                None
+            }
+        }
+    });
+    with_target_levels_from(base, target_levels, env.as_deref())
+}
+
+fn with_target_levels_from(base: EnvFilter, target_levels: &[String], env: Option<&str>) -> EnvFilter {
+    let env = env.filter(|env| !env.trim().is_empty());
+    if target_levels.is_empty() && env.is_none() {
+        return base;
+    }
+    let mut directives = base.to_string();
+    for directive in target_levels {
+        directives.push(',');
+        directives.push_str(directive);
+    }
+    if let Some(env) = env {
+        directives.push(',');
+        directives.push_str(env);
+    }
+    EnvFilter::builder().parse_lossy(directives)
+}
+
+/// 是否在没有设置`RUST_LOG`且大部分日志被隐藏时提示如何设置级别，默认开启
+///
+/// 提示只在读取`RUST_LOG`的模式（`LogMode::Full`）初始化时输出一次，写到 stderr，因为此时 info 日志本身就不会输出
+pub fn set_rust_log_hint(enabled: bool) {
+    RUST_LOG_HINT.store(enabled, Ordering::Relaxed);
+}
+
+/// 没有设置`RUST_LOG`且级别高于 info 时输出一次提示
+pub(crate) fn hint_missing_rust_log(level: Level) {
+    if !RUST_LOG_HINT.load(Ordering::Relaxed) {
+        return;
+    }
+    let env = std::env::var(EnvFilter::DEFAULT_ENV).ok();
+    if let Some(hint) = rust_log_hint(env.as_deref(), level) {
+        if !HINTED.swap(true, Ordering::Relaxed) {
+            eprintln!("{hint}");
+        }
+    }
+}
+
+fn rust_log_hint(env: Option<&str>, level: Level) -> Option<String> {
+    if env.is_some_and(|env| !env.trim().is_empty()) || level >= Level::INFO {
+        return None;
+    }
+    Some(format!(
+        "note: only {} and above are logged because {} is not set; run with `{}=info` (or debug, trace) to see more",
+        level_to_string(level),
+        EnvFilter::DEFAULT_ENV,
+        EnvFilter::DEFAULT_ENV,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::level_filters::LevelFilter;
+    use tracing::Level;
+
+    use tracing_subscriber::EnvFilter;
+
+    use crate::log::filter::{env_filter_from, rust_log_hint, sensible_filter_with, with_target_levels_from};
+
+    #[test]
+    fn sensible_filter_directives() {
+        let filter = sensible_filter_with("my-app", Level::DEBUG, "");
+        assert_eq!(filter.to_string(), "my_app=debug,warn");
+        assert_eq!(filter.max_level_hint(), Some(LevelFilter::DEBUG));
+
+        let filter = sensible_filter_with("my_app", Level::DEBUG, "my_app=trace,hyper=info");
+        assert_eq!(filter.max_level_hint(), Some(LevelFilter::TRACE));
+        assert!(filter.to_string().contains("hyper=info"), "{filter}");
+    }
+
+    #[test]
+    fn rust_log_hint_only_when_hidden() {
+        let hint = rust_log_hint(None, Level::WARN).unwrap();
+        assert!(hint.starts_with("note: only warn and above are logged because RUST_LOG is not set"), "{hint}");
+        assert!(rust_log_hint(Some(" "), Level::ERROR).is_some());
+        assert!(rust_log_hint(None, Level::INFO).is_none());
+        assert!(rust_log_hint(Some("debug"), Level::WARN).is_none());
+    }
+
+    #[test]
+    fn env_filter_fallback() {
+        let fallback = || EnvFilter::new("warn");
+        assert_eq!(env_filter_from("MYAPP_LOG", Some("my_app=debug"), fallback()).to_string(), "my_app=debug");
+        assert_eq!(env_filter_from("MYAPP_LOG", None, fallback()).to_string(), "warn");
+        assert_eq!(env_filter_from("MYAPP_LOG", Some(""), fallback()).to_string(), "warn");
+        assert_eq!(env_filter_from("MYAPP_LOG", Some("my_app=loud"), fallback()).to_string(), "warn");
+        let fallbacks = crate::log::init_report().fallbacks;
+        assert!(fallbacks.iter().any(|fallback| fallback.starts_with("invalid MYAPP_LOG `my_app=loud`")), "{fallbacks:?}");
+    }
+
+    #[test]
+    fn target_levels_merged() {
+        let targets = ["my_app=debug".to_string(), "hyper=warn".to_string()];
+        let filter = with_target_levels_from(EnvFilter::new("info"), &targets, None);
+        assert_eq!(filter.max_level_hint(), Some(LevelFilter::DEBUG));
+        assert!(filter.to_string().contains("hyper=warn"), "{filter}");
+
+        // 环境变量中的规则覆盖代码中的设置
+        let filter = with_target_levels_from(EnvFilter::new("info"), &targets, Some("my_app=trace"));
+        assert_eq!(filter.max_level_hint(), Some(LevelFilter::TRACE));
+        assert!(!filter.to_string().contains("my_app=debug"), "{filter}");
+
+        // 没有按 target 设置级别时环境变量同样生效
+        let filter = with_target_levels_from(EnvFilter::new("warn"), &[], Some("my_app=trace"));
+        assert_eq!(filter.max_level_hint(), Some(LevelFilter::TRACE));
+        assert!(filter.to_string().contains("my_app=trace"), "{filter}");
+        assert_eq!(with_target_levels_from(EnvFilter::new("warn"), &[], None).to_string(), "warn");
+        assert_eq!(with_target_levels_from(EnvFilter::new("warn"), &[], Some(" ")).to_string(), "warn");
+    }
+}
@@ -0,0 +1,98 @@
+use std::io::Write;
+
+use tracing::field::{Field, Visit};
+use tracing_core::{Event, Subscriber};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+/// 带`flush = true`字段的事件输出后立即 flush writer，用于“支付完成”这类不能丢在缓冲区里的关键日志
+///
+/// 需要放在输出事件的 layer 之后（外层），layer 的`on_event`按从内到外的顺序调用。
+/// `LogConfig`中同时 flush 主输出和`copy_to_file`的文件；`CustomFormatter`和`JsonFields`不输出`flush`字段。
+/// 强制 flush 会让该条日志同步等待写入完成，只应用在少数关键事件上。
+///
+/// # Example
+/// ```
+/// tracing::info!(flush = true, order_id = 42, "payment completed");
+/// ```
+pub struct FlushLayer<W> {
+    make_writer: W,
+}
+
+impl<W> FlushLayer<W> {
+    pub fn new(make_writer: W) -> Self {
+        FlushLayer { make_writer }
+    }
+}
+
+impl<S, W> Layer<S> for FlushLayer<W>
+    where
+        S: Subscriber,
+        W: for<'a> MakeWriter<'a> + 'static,
+{
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        // 先按字段名判断，没有 flush 字段的事件不需要遍历字段值
+        if event.metadata().fields().field("flush").is_none() {
+            return;
+        }
+
+        let mut visitor = FlushVisitor(false);
+        event.record(&mut visitor);
+        if visitor.0 {
+            let _ = self.make_writer.make_writer_for(event.metadata()).flush();
+        }
+    }
+}
+
+struct FlushVisitor(bool);
+
+impl Visit for FlushVisitor {
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        if field.name() == "flush" {
+            self.0 = value;
+        }
+    }
+
+    fn record_debug(&mut self, _field: &Field, _value: &dyn std::fmt::Debug) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::log::FlushLayer;
+
+    #[derive(Clone, Default)]
+    struct CountingWriter(Arc<AtomicUsize>);
+
+    impl Write for CountingWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn flush_on_field() {
+        let writer = CountingWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = tracing_subscriber::registry()
+            .with(FlushLayer::new(move || make_writer.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("normal");
+            tracing::info!(flush = false, "not flushed");
+            tracing::info!(flush = true, "payment completed");
+        });
+        assert_eq!(writer.0.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,180 @@
+use std::io::Write;
+use std::panic::AssertUnwindSafe;
+
+use crate::log::{LogReloadHandle, WorkerGuard};
+
+/// `init_log`返回的守卫，drop 时 flush 缓冲的日志输出
+///
+/// 同时持有`LogConfig::non_blocking_stdout`、`copy_to_file`等创建的后台写入线程，drop 时写完它们缓冲的日志。
+/// 需要保存到程序结束，例如放在`main`开头：`let _guard = init_log(...)?;`，
+/// 写成`let _ = ...`会立即 drop。
+#[must_use = "dropping the guard flushes and stops buffered log output"]
+pub struct LogGuard {
+    reload: Option<LogReloadHandle>,
+    on_drop: Vec<Box<dyn FnOnce() + Send>>,
+    workers: Vec<WorkerGuard>,
+}
+
+impl LogGuard {
+    pub(crate) fn new(reload: Option<LogReloadHandle>) -> Self {
+        LogGuard { reload, on_drop: Vec::new(), workers: Vec::new() }
+    }
+
+    /// 由守卫持有后台写入线程
+    pub(crate) fn with_workers(mut self, workers: Vec<WorkerGuard>) -> Self {
+        self.workers.extend(workers);
+        self
+    }
+
+    /// 添加 drop 时执行的回调，例如输出最后的统计或关闭其它资源；按添加的相反顺序执行，都在写完和 flush 日志输出之前
+    ///
+    /// 某个回调 panic 时不影响其余回调执行。回调中仍可以输出日志。
+    ///
+    /// # Example
+    /// ```
+    /// use myutil::log::{init_log, LogMode};
+    ///
+    /// let mut guard = init_log(LogMode::General, tracing::Level::INFO).unwrap();
+    /// guard.on_drop(|| tracing::info!("shutting down"));
+    /// ```
+    pub fn on_drop(&mut self, callback: impl FnOnce() + Send + 'static) -> &mut Self {
+        self.on_drop.push(Box::new(callback));
+        self
+    }
+
+    /// 运行时修改日志级别的句柄；`LogMode::Minimal`不支持修改，返回`None`
+    pub fn reload_handle(&self) -> Option<&LogReloadHandle> {
+        self.reload.as_ref()
+    }
+}
+
+impl Drop for LogGuard {
+    fn drop(&mut self) {
+        while let Some(callback) = self.on_drop.pop() {
+            let _ = std::panic::catch_unwind(AssertUnwindSafe(callback));
+        }
+        // 按创建顺序结束后台线程，每个都写完剩余的日志
+        self.workers.clear();
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// 初始化日志失败的原因
+#[derive(Debug)]
+pub enum InitLogError {
+    /// 已经设置过全局 subscriber
+    Subscriber(tracing::subscriber::SetGlobalDefaultError),
+    /// 已经设置过标准库`log`的 logger
+    Logger(tracing_log::log::SetLoggerError),
+    /// 无法创建日志文件或目录
+    Io(std::io::Error),
+    /// `LogConfig::time_format`的格式为空
+    EmptyTimeFormat,
+}
+
+impl std::fmt::Display for InitLogError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InitLogError::Subscriber(err) => write!(f, "could not set global default logger: {err}"),
+            InitLogError::Logger(err) => write!(f, "failed to set standard library logger: {err}"),
+            InitLogError::Io(err) => write!(f, "failed to open log file: {err}"),
+            InitLogError::EmptyTimeFormat => write!(f, "log time format is empty"),
+        }
+    }
+}
+
+impl std::error::Error for InitLogError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InitLogError::Subscriber(err) => Some(err),
+            InitLogError::Logger(err) => Some(err),
+            InitLogError::Io(err) => Some(err),
+            InitLogError::EmptyTimeFormat => None,
+        }
+    }
+}
+
+impl From<tracing::subscriber::SetGlobalDefaultError> for InitLogError {
+    fn from(err: tracing::subscriber::SetGlobalDefaultError) -> Self {
+        InitLogError::Subscriber(err)
+    }
+}
+
+impl From<tracing_log::log::SetLoggerError> for InitLogError {
+    fn from(err: tracing_log::log::SetLoggerError) -> Self {
+        InitLogError::Logger(err)
+    }
+}
+
+impl From<std::io::Error> for InitLogError {
+    fn from(err: std::io::Error) -> Self {
+        InitLogError::Io(err)
+    }
+}
+
+/// `try_init_log`失败的原因；已经设置过全局 subscriber 不算失败
+#[derive(Debug)]
+pub struct TryInitError(pub(crate) InitLogError);
+
+impl TryInitError {
+    /// 底层的初始化错误，目前只可能是`InitLogError::Logger`
+    pub fn into_inner(self) -> InitLogError {
+        self.0
+    }
+}
+
+impl std::fmt::Display for TryInitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl std::error::Error for TryInitError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        self.0.source()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    use crate::log::non_blocking;
+    use crate::log::tests::TestWriter;
+
+    use super::LogGuard;
+
+    #[test]
+    fn on_drop_lifo_and_isolated() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let mut guard = LogGuard::new(None);
+        for i in 1..=3 {
+            let calls = calls.clone();
+            guard.on_drop(move || calls.lock().unwrap().push(i));
+        }
+        guard.on_drop(|| panic!("callback failed"));
+        let last = calls.clone();
+        guard.on_drop(move || last.lock().unwrap().push(4));
+        drop(guard);
+        assert_eq!(*calls.lock().unwrap(), [4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn drop_finishes_workers() {
+        let output = TestWriter::default();
+        let (mut writer, worker) = non_blocking(output.clone());
+        let mut guard = LogGuard::new(None).with_workers(vec![worker]);
+        let mut last = writer.clone();
+        // 回调在后台线程结束之前执行，写出的内容不会丢失
+        guard.on_drop(move || writeln!(last, "last").unwrap());
+        for i in 0..50 {
+            writeln!(writer, "line {i}").unwrap();
+        }
+        drop(guard);
+
+        let text = output.output();
+        assert_eq!(text.lines().count(), 51, "{text}");
+        assert!(text.ends_with("line 49\nlast\n"), "{text}");
+    }
+}
@@ -0,0 +1,92 @@
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// `start_heartbeat`返回的句柄，drop 或`stop`时通知线程退出并等待其结束
+pub struct HeartbeatHandle {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HeartbeatHandle {
+    pub fn stop(self) {}
+}
+
+impl Drop for HeartbeatHandle {
+    fn drop(&mut self) {
+        // 关闭 channel 即可唤醒等待中的线程
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// 启动一个线程，每隔`interval`以 debug 级别输出一次`message`，用于确认进程和日志管道在空闲时仍然存活
+///
+/// 线程使用调用时当前的 subscriber
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use myutil::log::start_heartbeat;
+///
+/// let heartbeat = start_heartbeat(Duration::from_secs(60), "alive");
+/// // ...
+/// drop(heartbeat); // 线程退出
+/// ```
+pub fn start_heartbeat(interval: Duration, message: &str) -> HeartbeatHandle {
+    let message = message.to_string();
+    let mut beats: u64 = 0;
+    spawn_periodic("heartbeat", interval, move || {
+        beats += 1;
+        tracing::debug!(beats, "{message}");
+    })
+}
+
+/// 启动名为`name`的线程，每隔`interval`在调用时当前的 subscriber 下执行一次`tick`
+pub(crate) fn spawn_periodic(name: &str, interval: Duration, mut tick: impl FnMut() + Send + 'static) -> HeartbeatHandle {
+    let (stop, stopped) = mpsc::channel::<()>();
+    let dispatch = tracing::dispatcher::get_default(|dispatch| dispatch.clone());
+
+    let thread = std::thread::Builder::new()
+        .name(name.to_string())
+        .spawn(move || tracing::dispatcher::with_default(&dispatch, || {
+            while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                tick();
+            }
+        }))
+        .unwrap_or_else(|err| panic!("Failed to spawn {name} thread: {err}"));
+
+    HeartbeatHandle {
+        stop: Some(stop),
+        thread: Some(thread),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::log::{CustomFormatter, start_heartbeat};
+    use crate::log::tests::capture_custom;
+
+    #[test]
+    fn heartbeat_stops_on_drop() {
+        let heartbeat = start_heartbeat(Duration::from_secs(3600), "alive");
+        let start = Instant::now();
+        drop(heartbeat);
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn heartbeat_beats() {
+        let output = capture_custom(CustomFormatter::default(), || {
+            let heartbeat = start_heartbeat(Duration::from_millis(10), "alive");
+            std::thread::sleep(Duration::from_millis(100));
+            heartbeat.stop();
+        });
+        assert!(output.starts_with("DEBUG myutil::log::heartbeat: "), "{output}");
+        assert!(output.contains(" -> alive beats=1\n"), "{output}");
+    }
+}
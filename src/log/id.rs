@@ -0,0 +1,82 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// 非加密用途的随机数：每个`RandomState`的种子都不同，再混入计数器避免同一时刻重复
+pub(crate) fn random_u64() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.finish()
+}
+
+/// 生成 UUIDv4 字符串，如`0b5c7e4a-2f1d-4c38-9a6e-3d2c1b0a9f8e`
+pub(crate) fn uuid_v4() -> String {
+    let hi = (random_u64() & 0xffff_ffff_ffff_0fff) | 0x0000_0000_0000_4000; // version 4
+    let lo = (random_u64() & 0x3fff_ffff_ffff_ffff) | 0x8000_0000_0000_0000; // variant 10xx
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        hi >> 32,
+        (hi >> 16) & 0xffff,
+        hi & 0xffff,
+        lo >> 48,
+        lo & 0xffff_ffff_ffff,
+    )
+}
+
+/// 生成 11 位 base62 短 id，如`3kTMd9B0xGq`
+pub(crate) fn short_id() -> String {
+    const ALPHABET: &[u8; 62] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz";
+
+    // 62^11 > 2^64，11 位足够表示任意 u64
+    let mut value = random_u64();
+    let mut id = [b'0'; 11];
+    for c in id.iter_mut().rev() {
+        *c = ALPHABET[(value % 62) as usize];
+        value /= 62;
+    }
+    String::from_utf8_lossy(&id).into_owned()
+}
+
+/// 创建一个带新生成的`trace_id`（base62 短 id）字段的 info 级 span，用于关联一批工作的日志
+///
+/// tracing 的 span 名必须是常量，所以 span 名固定为`batch`，`name`记录在`batch`字段中。
+/// 由调用方决定何时进入和退出 span，span 内的日志都会带上`trace_id`。
+///
+/// # Example
+/// ```
+/// use myutil::log::batch_span;
+///
+/// let span = batch_span("import-users");
+/// let _entered = span.enter();
+/// tracing::info!("started"); // ... batch{batch="import-users" trace_id="3kTMd9B0xGq"}: started
+/// ```
+pub fn batch_span(name: &str) -> tracing::Span {
+    tracing::info_span!("batch", batch = name, trace_id = %short_id())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::log::{batch_span, CustomFormatter};
+    use crate::log::id::short_id;
+    use crate::log::tests::capture_custom;
+
+    #[test]
+    fn short_id_is_base62() {
+        let (a, b) = (short_id(), short_id());
+        assert_eq!(a.len(), 11);
+        assert!(a.bytes().all(|c| c.is_ascii_alphanumeric()), "{a}");
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn batch_span_carries_trace_id() {
+        let output = capture_custom(CustomFormatter::default(), || {
+            let _span = batch_span("import").entered();
+            tracing::info!("started");
+        });
+        assert!(output.contains(" -> batch{batch=\"import\" trace_id="), "{output}");
+        assert!(output.ends_with("}: started\n"), "{output}");
+    }
+}
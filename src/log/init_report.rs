@@ -0,0 +1,144 @@
+use std::sync::{Mutex, MutexGuard};
+
+use tracing_core::LevelFilter;
+
+use crate::log::json::json_string;
+use crate::log::LogReloadHandle;
+
+/// 安装时记录的状态，`init_report`在此基础上读取当前的过滤器
+#[derive(Default)]
+struct InitState {
+    mode: Option<&'static str>,
+    writer: Option<&'static str>,
+    reload: Option<LogReloadHandle>,
+    log_bridge: bool,
+    fallbacks: Vec<String>,
+}
+
+static STATE: Mutex<Option<InitState>> = Mutex::new(None);
+
+fn state() -> MutexGuard<'static, Option<InitState>> {
+    STATE.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// 全局 subscriber 的模式和输出位置，用于`init_report`
+pub(crate) struct Installed {
+    pub(crate) mode: &'static str,
+    pub(crate) writer: &'static str,
+}
+
+/// 记录已经设置的全局 subscriber
+pub(crate) fn record_installed(installed: Installed, reload: Option<LogReloadHandle>) {
+    let mut state = state();
+    let state = state.get_or_insert_with(InitState::default);
+    state.mode = Some(installed.mode);
+    state.writer = Some(installed.writer);
+    state.reload = reload;
+}
+
+/// 记录标准库`log`的桥接已经设置
+pub(crate) fn record_log_bridge() {
+    state().get_or_insert_with(InitState::default).log_bridge = true;
+}
+
+/// 记录初始化时因配置无效等原因采用的替代方案
+pub(crate) fn record_fallback(fallback: String) {
+    state().get_or_insert_with(InitState::default).fallbacks.push(fallback);
+}
+
+/// 日志和错误处理实际的初始化结果，见`init_report`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InitReport {
+    /// 全局日志的模式，如`general`；通过`LogConfig`安装时为`config`，没有初始化时为`None`
+    pub mode: Option<&'static str>,
+    /// 当前的过滤规则，运行时通过`LogReloadHandle`修改后也会反映出来；`LogMode::Minimal`和没有初始化时为`None`
+    pub filter: Option<String>,
+    /// 当前输出的最详细级别，如`info`，全部关闭时为`off`
+    pub max_level: String,
+    /// 输出位置：`stdout`、`non_blocking_stdout`或`custom`
+    pub writer: Option<&'static str>,
+    /// 是否接收标准库`log`的事件
+    pub log_bridge: bool,
+    /// 是否安装了 panic hook（`init_error_hook`）
+    pub panic_hook: bool,
+    /// 是否安装了 eyre hook；eyre 已经装上默认 hook 时为`false`，此时错误报告不经过本 crate 的过滤
+    pub eyre_hook: bool,
+    /// 初始化时采用的替代方案，例如环境变量中的过滤规则无效时使用默认级别
+    pub fallbacks: Vec<String>,
+}
+
+impl InitReport {
+    /// 单行 JSON，可以输出到日志或通过诊断接口返回
+    pub fn to_json(&self) -> String {
+        let optional = |value: Option<&str>| value.map_or("null".to_string(), json_string);
+        let fallbacks: Vec<String> = self.fallbacks.iter().map(|fallback| json_string(fallback)).collect();
+        format!(
+            r#"{{"mode":{},"filter":{},"max_level":{},"writer":{},"log_bridge":{},"panic_hook":{},"eyre_hook":{},"fallbacks":[{}]}}"#,
+            optional(self.mode),
+            optional(self.filter.as_deref()),
+            json_string(&self.max_level),
+            optional(self.writer),
+            self.log_bridge,
+            self.panic_hook,
+            self.eyre_hook,
+            fallbacks.join(","),
+        )
+    }
+}
+
+/// 报告`init_log`、`LogConfig::install`和`init_error_hook`等实际安装了什么，包括失败后的降级
+///
+/// # Example
+/// ```
+/// use myutil::log::{init_log, init_report, LogMode};
+///
+/// let _guard = init_log(LogMode::General, tracing::Level::INFO).unwrap();
+/// let report = init_report();
+/// assert_eq!(report.mode, Some("general"));
+/// assert_eq!(report.max_level, "info");
+/// tracing::info!(report = %report.to_json(), "logging initialized");
+/// ```
+pub fn init_report() -> InitReport {
+    #[cfg(feature = "error")]
+    let (panic_hook, eyre_hook) = crate::error::hooks_installed();
+    #[cfg(not(feature = "error"))]
+    let (panic_hook, eyre_hook) = (false, false);
+
+    let state = state();
+    let state = state.as_ref();
+    InitReport {
+        mode: state.and_then(|state| state.mode),
+        filter: state
+            .and_then(|state| state.reload.as_ref())
+            .and_then(|reload| reload.current_filter()),
+        max_level: LevelFilter::current().to_string().to_lowercase(),
+        writer: state.and_then(|state| state.writer),
+        log_bridge: state.is_some_and(|state| state.log_bridge),
+        panic_hook,
+        eyre_hook,
+        fallbacks: state.map(|state| state.fallbacks.clone()).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InitReport;
+
+    #[test]
+    fn report_json() {
+        let report = InitReport {
+            mode: Some("general"),
+            filter: Some("info".to_string()),
+            max_level: "info".to_string(),
+            writer: None,
+            log_bridge: true,
+            panic_hook: true,
+            eyre_hook: false,
+            fallbacks: vec!["invalid \"MYAPP_LOG\"".to_string()],
+        };
+        assert_eq!(
+            report.to_json(),
+            r#"{"mode":"general","filter":"info","max_level":"info","writer":null,"log_bridge":true,"panic_hook":true,"eyre_hook":false,"fallbacks":["invalid \"MYAPP_LOG\""]}"#,
+        );
+    }
+}
@@ -0,0 +1,764 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::time::Instant;
+
+use chrono::{Datelike, Timelike};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing_core::{Event, Subscriber};
+use tracing_log::NormalizeEvent;
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::fmt::{FmtContext, format, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use super::request_id::RequestId;
+use super::truncate_value;
+use super::{FieldFilter, TimeSourceTimer, uptime_ms};
+
+/// 把`value`写成带引号的 JSON 字符串
+pub(crate) fn write_json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < '\u{20}' => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// 带引号的 JSON 字符串
+pub(crate) fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    write_json_string(&mut out, value);
+    out
+}
+
+/// 收集字段为`(名称, JSON 值)`，值按`Debug`格式输出为字符串；`typed`时整数、浮点数和布尔值保留原类型
+struct JsonVisitor<'a> {
+    typed: bool,
+    max_value_len: Option<usize>,
+    field_filter: Option<&'a FieldFilter>,
+    fields: Vec<(&'static str, String)>,
+}
+
+impl<'a> JsonVisitor<'a> {
+    fn new(format: &'a JsonFields) -> Self {
+        JsonVisitor {
+            typed: format.typed,
+            max_value_len: format.max_value_len,
+            field_filter: format.field_filter.as_ref(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// 保存已经是 JSON 值的字段，被`field_filter`筛掉的跳过
+    fn push(&mut self, field: &Field, value: String) {
+        if self.field_filter.is_none_or(|filter| filter.allows(field.name())) {
+            self.fields.push((field.name(), value));
+        }
+    }
+
+    /// 以字符串保存，除消息外按`max_value_len`截断
+    fn push_string(&mut self, field: &Field, value: &str) {
+        let max_value_len = self.max_value_len.filter(|_| field.name() != "message");
+        self.push(field, json_string(&truncate_value(value, max_value_len)));
+    }
+
+    /// `"k":v,"k2":v2`，不带外层花括号
+    fn join(&self) -> String {
+        let mut out = String::new();
+        for (i, (name, value)) in self.fields.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_json_string(&mut out, name);
+            out.push(':');
+            out.push_str(value);
+        }
+        out
+    }
+}
+
+impl Visit for JsonVisitor<'_> {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        if self.typed {
+            self.push(field, value.to_string());
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        if self.typed {
+            self.push(field, value.to_string());
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        // NaN 和无穷大不是合法的 JSON 数字，仍输出为字符串
+        if self.typed && value.is_finite() {
+            self.push(field, value.to_string());
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        // `flush = true/false`只是给 FlushLayer 的指令
+        if field.name() == "flush" {
+            return;
+        }
+        if self.typed {
+            self.push(field, value.to_string());
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if !field.name().starts_with("log.") {
+            self.push_string(field, value);
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        // tracing_log 桥接时附带的 log.* 字段已体现在 target、filename 等键中
+        if !field.name().starts_with("log.") {
+            self.push_string(field, &format!("{value:?}"));
+        }
+    }
+}
+
+/// JSON 格式的字段，span 字段以此格式保存，供`JsonFormatter`嵌入为对象
+#[derive(Debug, Clone, Default)]
+pub struct JsonFields {
+    typed: bool,
+    max_value_len: Option<usize>,
+    field_filter: Option<FieldFilter>,
+    parent_pid: Option<u32>,
+}
+
+impl JsonFields {
+    /// 整数、浮点数和布尔值输出为 JSON 原生类型（`"count":5`），便于日志库做数值聚合；
+    /// 默认`false`即一律为字符串。以`%`（`Display`）或`?`（`Debug`）记录的字段仍为字符串。
+    /// 同时作用于事件字段和 span 字段。
+    pub fn with_typed_values(mut self, typed: bool) -> Self {
+        self.typed = typed;
+        self
+    }
+
+    /// 字符串字段的值最多保留`max_value_len`个字符，超出时截断并追加`…`，例如限制很大的`body`字段；
+    /// 默认`None`即不截断。消息和原生类型的值不截断，同时作用于事件字段和 span 字段。
+    pub fn with_max_field_value_len(mut self, max_value_len: Option<usize>) -> Self {
+        self.max_value_len = max_value_len;
+        self
+    }
+
+    /// 按`FieldFilter`筛选输出的字段，与文本格式使用同一个筛选规则；默认不筛选。同时作用于事件字段和 span 字段，`message`不受影响。
+    ///
+    /// `LogConfig::field_filter`同时设置文本和 JSON 格式。
+    pub fn with_field_filter(mut self, field_filter: FieldFilter) -> Self {
+        self.field_filter = Some(field_filter);
+        self
+    }
+
+    /// `LogConfig::with_parent_pid`设置的父进程 id，`JsonFormatter`输出为顶层字段
+    pub(crate) fn with_parent_pid(mut self, parent_pid: Option<u32>) -> Self {
+        self.parent_pid = parent_pid;
+        self
+    }
+}
+
+impl<'writer> FormatFields<'writer> for JsonFields {
+    fn format_fields<R: RecordFields>(&self, mut writer: format::Writer<'writer>, fields: R) -> std::fmt::Result {
+        let mut visitor = JsonVisitor::new(self);
+        fields.record(&mut visitor);
+        writer.write_str(&visitor.join())
+    }
+
+    fn add_fields(&self, current: &'writer mut FormattedFields<Self>, fields: &tracing_core::span::Record<'_>) -> std::fmt::Result {
+        let mut visitor = JsonVisitor::new(self);
+        fields.record(&mut visitor);
+        if visitor.fields.is_empty() {
+            return Ok(());
+        }
+        // 重复记录的字段覆盖原来的值，避免输出重复的键
+        let mut merged = String::new();
+        for (name, value) in split_fields(&current.fields) {
+            if !visitor.fields.iter().any(|(new_name, _)| *new_name == name) {
+                write!(merged, r#""{name}":{value},"#)?;
+            }
+        }
+        merged.push_str(&visitor.join());
+        current.fields = merged;
+        Ok(())
+    }
+}
+
+/// 拆开`JsonVisitor::join`的输出，得到`(名称, JSON 值)`；字段名来自 tracing 的元数据，不含需要转义的字符
+fn split_fields(joined: &str) -> Vec<(&str, &str)> {
+    /// 跳过从`start`开始的 JSON 字符串，返回结束引号之后的位置
+    fn skip_string(s: &str, start: usize) -> usize {
+        let bytes = s.as_bytes();
+        let mut i = start + 1;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\\' => i += 2,
+                b'"' => return i + 1,
+                _ => i += 1,
+            }
+        }
+        bytes.len()
+    }
+
+    let mut fields = Vec::new();
+    let mut i = 0;
+    while i < joined.len() {
+        let key_end = skip_string(joined, i);
+        let key = &joined[i + 1..key_end - 1];
+        let value_start = key_end + 1;
+        let value_end = if joined[value_start..].starts_with('"') {
+            skip_string(joined, value_start)
+        } else {
+            joined[value_start..].find(',').map_or(joined.len(), |end| value_start + end)
+        };
+        fields.push((key, &joined[value_start..value_end]));
+        i = value_end + 1;
+    }
+    fields
+}
+
+/// `JsonFormatter`中`timestamp`的格式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TimestampFormat {
+    /// RFC 3339 本地时间字符串，如`"2024-05-01T12:00:00.000+08:00"`，默认
+    #[default]
+    Rfc3339,
+    /// Unix 时间戳的毫秒数，为 JSON 数字，如`1714536000123`
+    EpochMillis,
+    /// Unix 时间戳的秒数，为带 3 位小数的 JSON 数字，如`1714536000.123`，保留毫秒精度
+    EpochSeconds,
+}
+
+impl TimestampFormat {
+    /// `now`按格式写成的 JSON 值；设置了`timer`时`Rfc3339`使用它生成的字符串
+    fn format(self, now: &chrono::DateTime<chrono::FixedOffset>, timer: Option<&TimeSourceTimer>) -> String {
+        match (self, timer) {
+            (TimestampFormat::Rfc3339, Some(timer)) => json_string(&timer.now_formatted()),
+            (TimestampFormat::Rfc3339, None) => json_string(&now.format("%Y-%m-%dT%H:%M:%S%.3f%:z").to_string()),
+            (TimestampFormat::EpochMillis, _) => now.timestamp_millis().to_string(),
+            (TimestampFormat::EpochSeconds, _) => {
+                let millis = now.timestamp_millis();
+                let sign = if millis < 0 { "-" } else { "" };
+                let millis = millis.unsigned_abs();
+                format!("{sign}{}.{:03}", millis / 1000, millis % 1000)
+            }
+        }
+    }
+}
+
+/// `LogMode::Json`使用的格式：每行一个 JSON 对象
+///
+/// 事件字段展开到顶层，`timestamp`默认为 RFC 3339 本地时间（见`with_timestamp_format`），所在 span 的字段以嵌套对象输出在`span`（当前 span）和`spans`（由外到内）中。
+/// 与内置键（`timestamp`、`level`、`target`、`span`等）同名的事件字段加上`fields.`前缀，如`fields.level`，避免出现重复的键。
+/// 需要配合`JsonFields`作为`fmt_fields`。字段值默认为字符串，保留数值类型见`JsonFields::with_typed_values`。
+///
+/// ```json
+/// {"timestamp":"2024-05-01T12:00:00.000+08:00","level":"INFO","target":"my_app","filename":"src/main.rs","line_number":9,"message":"login","user":"alice","span":{"name":"request","id":"1"},"spans":[{"name":"request","id":"1"}]}
+/// ```
+///
+/// # Example
+/// ```
+/// use myutil::log::{JsonFields, JsonFormatter};
+///
+/// let subscriber = tracing_subscriber::fmt()
+///     .fmt_fields(JsonFields::default())
+///     .event_format(JsonFormatter::default())
+///     .finish();
+/// tracing::subscriber::with_default(subscriber, || tracing::info!(user = "alice", "login"));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct JsonFormatter {
+    field_order: Vec<String>,
+    timestamp_format: TimestampFormat,
+    timer: Option<TimeSourceTimer>,
+    span_elapsed: bool,
+    date_buckets: bool,
+    uptime: bool,
+}
+
+impl JsonFormatter {
+    /// 顶层字段按`field_order`中的顺序输出在最前面，其余字段保持默认顺序跟在后面，便于阅读和逐行 diff；
+    /// 不存在的字段跳过。默认顺序为`timestamp`、`level`、`target`、`filename`、`line_number`，然后是事件字段。
+    ///
+    /// # Example
+    /// ```
+    /// use myutil::log::{JsonFields, JsonFormatter};
+    ///
+    /// // {"timestamp":"...","level":"INFO","message":"login","target":"my_app",...}
+    /// let formatter = JsonFormatter::default().with_field_order(&["timestamp", "level", "message", "target"]);
+    /// let subscriber = tracing_subscriber::fmt().fmt_fields(JsonFields::default()).event_format(formatter).finish();
+    /// tracing::subscriber::with_default(subscriber, || tracing::info!(user = "alice", "login"));
+    /// ```
+    pub fn with_field_order(mut self, field_order: &[&str]) -> Self {
+        self.field_order = field_order.iter().map(|name| name.to_string()).collect();
+        self
+    }
+
+    /// `timestamp`的格式，默认`TimestampFormat::Rfc3339`；日志库按数值排序或计算时可以用 Unix 时间戳
+    ///
+    /// `LogMode::Json`中通过`LogConfig::json_timestamp_format`设置
+    pub fn with_timestamp_format(mut self, timestamp_format: TimestampFormat) -> Self {
+        self.timestamp_format = timestamp_format;
+        self
+    }
+
+    /// `TimestampFormat::Rfc3339`时由`timer`生成`timestamp`字符串，例如 UTC 时间或`TimeSource`，默认为本地时间；
+    /// Unix 时间戳格式取`TimeSource::now`的时刻，默认为系统时间
+    ///
+    /// `LogMode::Json`中沿用`LogConfig::timer`、`time_format`和`utc`的设置
+    pub fn with_timer(mut self, timer: TimeSourceTimer) -> Self {
+        self.timer = Some(timer);
+        self
+    }
+
+    /// `span`和`spans`中的每个 span 对象带上从创建到现在的毫秒数`elapsed_ms`，便于计算各层 span 的耗时；默认关闭
+    ///
+    /// 创建时刻由`span_timing_layer`记录，没有这个 layer 时不输出。`LogMode::Json`中通过`LogConfig::json_span_elapsed`设置。
+    ///
+    /// # Example
+    /// ```
+    /// use tracing_subscriber::layer::SubscriberExt;
+    /// use myutil::log::{JsonFields, JsonFormatter, span_timing_layer};
+    ///
+    /// let subscriber = tracing_subscriber::fmt()
+    ///     .fmt_fields(JsonFields::default())
+    ///     .event_format(JsonFormatter::default().with_span_elapsed(true))
+    ///     .finish()
+    ///     .with(span_timing_layer());
+    /// tracing::subscriber::with_default(subscriber, || {
+    ///     let _span = tracing::info_span!("request").entered();
+    ///     tracing::info!("done"); // ...,"span":{"name":"request","elapsed_ms":0},...
+    /// });
+    /// ```
+    pub fn with_span_elapsed(mut self, span_elapsed: bool) -> Self {
+        self.span_elapsed = span_elapsed;
+        self
+    }
+
+    /// 在`timestamp`后输出日期分桶字段`year`、`month`、`day`、`hour`（JSON 数字），日志库按时间段聚合时不需要解析时间戳；默认关闭
+    ///
+    /// 与 Unix 时间戳一样取自`timer`的`TimeSource::now`，时区与其一致，例如`LogConfig::utc`时为 UTC；没有`timer`时为本地时间。
+    /// `LogMode::Json`中通过`LogConfig::json_date_buckets`设置。
+    pub fn with_date_buckets(mut self, date_buckets: bool) -> Self {
+        self.date_buckets = date_buckets;
+        self
+    }
+
+    /// 在`timestamp`后输出初始化以来的毫秒数`uptime_ms`（JSON 数字，见`uptime_ms`），与文本格式的`+123456ms`对应；默认关闭
+    ///
+    /// `LogMode::Json`中通过`LogConfig::json_uptime`设置
+    pub fn with_uptime(mut self, uptime: bool) -> Self {
+        self.uptime = uptime;
+        self
+    }
+
+    /// 把`field_order`中的字段移到最前面，其余字段的相对顺序不变
+    fn ordered<'a>(&self, mut entries: Vec<(Cow<'a, str>, String)>) -> Vec<(Cow<'a, str>, String)> {
+        let mut ordered = Vec::with_capacity(entries.len());
+        for name in &self.field_order {
+            if let Some(i) = entries.iter().position(|(entry, _)| entry == name) {
+                ordered.push(entries.remove(i));
+            }
+        }
+        ordered.append(&mut entries);
+        ordered
+    }
+}
+
+impl<S> FormatEvent<S, JsonFields> for JsonFormatter
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, JsonFields>,
+        mut writer: format::Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        // 桥接的 log 记录需要还原真实的 target 和位置
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+
+        let now = self.timer.as_ref().map_or_else(|| chrono::Local::now().fixed_offset(), TimeSourceTimer::now);
+        let mut entries = vec![("timestamp", self.timestamp_format.format(&now, self.timer.as_ref()))];
+        if self.uptime {
+            entries.push(("uptime_ms", uptime_ms().to_string()));
+        }
+        if self.date_buckets {
+            entries.push(("year", now.year().to_string()));
+            entries.push(("month", now.month().to_string()));
+            entries.push(("day", now.day().to_string()));
+            entries.push(("hour", now.hour().to_string()));
+        }
+        entries.push(("level", format!(r#""{}""#, metadata.level())));
+        entries.push(("target", json_string(metadata.target())));
+        if let Some(file) = metadata.file() {
+            entries.push(("filename", json_string(file)));
+        }
+        if let Some(line) = metadata.line() {
+            entries.push(("line_number", line.to_string()));
+        }
+
+        let mut visitor = JsonVisitor::new(ctx.field_format());
+        event.record(&mut visitor);
+        let has_field = |field: &str| visitor.fields.iter().any(|(name, _)| *name == field);
+
+        let mut extra = Vec::new();
+        if let Some(pid) = ctx.field_format().parent_pid {
+            if ctx.field_format().typed {
+                extra.push(("parent_pid", pid.to_string()));
+            } else {
+                extra.push(("parent_pid", format!(r#""{pid}""#)));
+            }
+        }
+
+        // request_id_layer 传下来的请求 id，事件自身带有同名字段时以事件的为准
+        if let Some(span) = ctx.event_scope().and_then(|mut scope| scope.next()) {
+            if let Some(RequestId { field, value }) = span.extensions().get::<RequestId>() {
+                if !has_field(field) {
+                    extra.push((*field, json_string(value)));
+                }
+            }
+        }
+
+        if let Some(scope) = ctx.event_scope() {
+            let mut spans = Vec::new();
+            for span in scope.from_root() {
+                let ext = span.extensions();
+                let fields = ext
+                    .get::<FormattedFields<JsonFields>>()
+                    .expect("will never be `None`");
+                let mut object = format!(r#"{{"name":{}"#, json_string(span.name()));
+                if !fields.is_empty() {
+                    object.push(',');
+                    object.push_str(fields);
+                }
+                if self.span_elapsed {
+                    // span 自身的同名字段优先
+                    if let Some(SpanOpened(opened)) = ext.get::<SpanOpened>() {
+                        if !split_fields(fields).iter().any(|(name, _)| *name == "elapsed_ms") {
+                            write!(object, r#","elapsed_ms":{}"#, opened.elapsed().as_millis())?;
+                        }
+                    }
+                }
+                object.push('}');
+                spans.push(object);
+            }
+            if let Some(current) = spans.last() {
+                extra.push(("span", current.clone()));
+                extra.push(("spans", format!("[{}]", spans.join(","))));
+            }
+        }
+
+        // 与内置键同名的事件字段改名，保证每个键只出现一次
+        let builtin: Vec<&str> = entries.iter().chain(&extra).map(|(name, _)| *name).collect();
+        let fields = visitor.fields.into_iter().map(|(name, value)| {
+            if builtin.contains(&name) {
+                (Cow::Owned(format!("fields.{name}")), value)
+            } else {
+                (Cow::Borrowed(name), value)
+            }
+        });
+        let mut entries: Vec<(Cow<str>, String)> = entries.into_iter().map(|(name, value)| (Cow::Borrowed(name), value)).collect();
+        let builtin_extra = extra.into_iter().map(|(name, value)| (Cow::Borrowed(name), value));
+        entries.extend(fields);
+        entries.extend(builtin_extra);
+
+        let mut out = String::from("{");
+        for (i, (name, value)) in self.ordered(entries).iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_json_string(&mut out, name);
+            out.push(':');
+            out.push_str(value);
+        }
+        out.push('}');
+        writeln!(writer, "{out}")
+    }
+}
+
+/// 保存在 span 扩展中的创建时刻
+struct SpanOpened(Instant);
+
+/// 在 span 创建时记录时刻，供`JsonFormatter::with_span_elapsed`输出`elapsed_ms`，见`span_timing_layer`
+pub struct SpanTimingLayer;
+
+/// 创建记录 span 创建时刻的 layer，需要使用`Registry`；只在需要 span 耗时时添加，不添加时没有额外开销
+pub fn span_timing_layer() -> SpanTimingLayer {
+    SpanTimingLayer
+}
+
+impl<S> Layer<S> for SpanTimingLayer
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(SpanOpened(Instant::now()));
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::time::Duration;
+
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::log::{FieldFilter, JsonFields, JsonFormatter, span_timing_layer, TimeSource, TimeSourceTimer, TimestampFormat};
+    use crate::log::tests::TestWriter;
+
+    use super::json_string;
+
+    pub(crate) fn capture_json(formatter: JsonFormatter, f: impl FnOnce()) -> String {
+        capture_json_with(JsonFields::default(), formatter, f)
+    }
+
+    fn capture_json_with(fields: JsonFields, formatter: JsonFormatter, f: impl FnOnce()) -> String {
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_writer(move || make_writer.clone())
+            .fmt_fields(fields)
+            .event_format(formatter)
+            .finish();
+        tracing::subscriber::with_default(subscriber, f);
+        writer.output()
+    }
+
+    #[test]
+    fn json_event() {
+        let output = capture_json(JsonFormatter::default(), || {
+            let span = tracing::info_span!("request", id = 7, path = "/a");
+            let _outer = span.enter();
+            span.record("path", "/b");
+            let _inner = tracing::debug_span!("db").entered();
+            tracing::info!(user = "alice", "login \"ok\"");
+        });
+        let line = output.trim_end();
+        assert!(line.starts_with(r#"{"timestamp":""#), "{line}");
+        let (timestamp, rest) = line[14..].split_once('"').unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(timestamp).is_ok(), "{timestamp}");
+        assert!(rest.starts_with(r#","level":"INFO","target":"myutil::log::json::tests","filename":"src/log/json.rs","line_number":"#), "{line}");
+        assert!(rest.ends_with(concat!(
+            r#","message":"login \"ok\"","user":"alice","span":{"name":"db"},"#,
+            r#""spans":[{"name":"request","id":"7","path":"/b"},{"name":"db"}]}"#,
+        )), "{line}");
+    }
+
+    #[test]
+    fn json_typed_values() {
+        let emit = || {
+            let _span = tracing::info_span!("job", attempt = 2).entered();
+            tracing::info!(count = 5, delta = -3, ratio = 0.5, ok = true, nan = f64::NAN, code = %404, "done");
+        };
+        let output = capture_json_with(JsonFields::default().with_typed_values(true), JsonFormatter::default(), emit);
+        assert!(output.contains(r#""message":"done","count":5,"delta":-3,"ratio":0.5,"ok":true,"nan":"NaN","code":"404""#), "{output}");
+        assert!(output.contains(r#""span":{"name":"job","attempt":2}"#), "{output}");
+
+        let output = capture_json(JsonFormatter::default(), emit);
+        assert!(output.contains(r#""count":"5","delta":"-3","ratio":"0.5","ok":"true""#), "{output}");
+    }
+
+    #[test]
+    fn json_max_field_value_len() {
+        let fields = JsonFields::default().with_max_field_value_len(Some(5));
+        let output = capture_json_with(fields, JsonFormatter::default(), || {
+            let _span = tracing::info_span!("upload", file = "report.pdf").entered();
+            tracing::info!(body = "0123456789", id = "abc", "a long message is kept");
+        });
+        assert!(output.contains(r#""message":"a long message is kept","body":"01234…","id":"abc""#), "{output}");
+        assert!(output.contains(r#""span":{"name":"upload","file":"repor…"}"#), "{output}");
+    }
+
+    #[test]
+    fn json_field_filter() {
+        let fields = JsonFields::default().with_field_filter(FieldFilter::default().exclude(["password", "token"]));
+        let output = capture_json_with(fields, JsonFormatter::default(), || {
+            let _span = tracing::info_span!("req", tenant = "a", token = "t").entered();
+            tracing::info!(user = "alice", password = "secret", attempts = 3, "login");
+        });
+        assert!(output.contains(r#""message":"login","user":"alice","attempts":"3","span":{"name":"req","tenant":"a"}"#), "{output}");
+        assert!(!output.contains("secret") && !output.contains("token"), "{output}");
+    }
+
+    #[test]
+    fn json_field_order() {
+        let formatter = JsonFormatter::default().with_field_order(&["level", "message", "missing", "timestamp", "span"]);
+        let output = capture_json(formatter, || {
+            let _request = tracing::info_span!("request").entered();
+            tracing::info!(user = "alice", "login");
+        });
+        assert!(output.starts_with(r#"{"level":"INFO","message":"login","timestamp":""#), "{output}");
+        assert!(output.contains(r#"","span":{"name":"request"},"target":"myutil::log::json::tests","filename":"#), "{output}");
+        assert!(output.trim_end().ends_with(r#","user":"alice","spans":[{"name":"request"}]}"#), "{output}");
+    }
+
+    #[test]
+    fn json_epoch_timestamps() {
+        let timestamp = |format| {
+            let output = capture_json(JsonFormatter::default().with_timestamp_format(format), || tracing::info!("tick"));
+            let value = output.strip_prefix(r#"{"timestamp":"#).unwrap().split(',').next().unwrap().to_string();
+            (value, output)
+        };
+        let before = chrono::Utc::now();
+        let (millis, output) = timestamp(TimestampFormat::EpochMillis);
+        let millis: i64 = millis.parse().unwrap_or_else(|_| panic!("{output}"));
+        assert!(before.timestamp_millis() <= millis && millis <= chrono::Utc::now().timestamp_millis(), "{output}");
+
+        let (seconds, output) = timestamp(TimestampFormat::EpochSeconds);
+        let (whole, fraction) = seconds.split_once('.').unwrap_or_else(|| panic!("{output}"));
+        assert_eq!(fraction.len(), 3, "{output}");
+        let millis: i64 = format!("{whole}{fraction}").parse().unwrap_or_else(|_| panic!("{output}"));
+        assert!(before.timestamp_millis() <= millis && millis <= chrono::Utc::now().timestamp_millis(), "{output}");
+
+        let (rfc3339, _) = timestamp(TimestampFormat::Rfc3339);
+        assert!(chrono::DateTime::parse_from_rfc3339(rfc3339.trim_matches('"')).is_ok(), "{rfc3339}");
+    }
+
+    #[test]
+    fn json_epoch_from_timer() {
+        struct FixedClock;
+
+        impl TimeSource for FixedClock {
+            fn now_formatted(&self) -> String {
+                "fixed".to_string()
+            }
+
+            fn now(&self) -> chrono::DateTime<chrono::FixedOffset> {
+                chrono::DateTime::parse_from_rfc3339("2024-05-01T12:00:00.042+08:00").unwrap()
+            }
+        }
+
+        let timestamp = |format| {
+            let formatter = JsonFormatter::default().with_timestamp_format(format).with_timer(TimeSourceTimer::new(FixedClock));
+            capture_json(formatter, || tracing::info!("tick"))
+        };
+        assert!(timestamp(TimestampFormat::EpochMillis).starts_with(r#"{"timestamp":1714536000042,"#));
+        assert!(timestamp(TimestampFormat::EpochSeconds).starts_with(r#"{"timestamp":1714536000.042,"#));
+        assert!(timestamp(TimestampFormat::Rfc3339).starts_with(r#"{"timestamp":"fixed","#));
+    }
+
+    #[test]
+    fn json_span_elapsed() {
+        let emit = || {
+            let _outer = tracing::info_span!("request", id = 7).entered();
+            std::thread::sleep(Duration::from_millis(20));
+            let _inner = tracing::info_span!("db").entered();
+            tracing::info!("done");
+        };
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(move || make_writer.clone())
+            .fmt_fields(JsonFields::default())
+            .event_format(JsonFormatter::default().with_span_elapsed(true))
+            .finish()
+            .with(span_timing_layer());
+        tracing::subscriber::with_default(subscriber, emit);
+        let output = writer.output();
+        let spans = output.split_once(r#""spans":["#).expect("spans").1;
+        let outer = spans.strip_prefix(r#"{"name":"request","id":"7","elapsed_ms":"#).unwrap_or_else(|| panic!("{output}"));
+        let outer_ms: u64 = outer.split_once('}').unwrap().0.parse().unwrap();
+        assert!(outer_ms >= 20, "{output}");
+        assert!(output.contains(r#""span":{"name":"db","elapsed_ms":"#), "{output}");
+
+        // 关闭时没有 elapsed_ms，即使记录了创建时刻
+        assert!(!capture_json(JsonFormatter::default(), emit).contains("elapsed_ms"));
+    }
+
+    #[test]
+    fn json_date_buckets() {
+        struct FixedClock;
+
+        impl TimeSource for FixedClock {
+            fn now_formatted(&self) -> String {
+                "2024-05-01T02:30:00+08:00".to_string()
+            }
+
+            fn now(&self) -> chrono::DateTime<chrono::FixedOffset> {
+                chrono::DateTime::parse_from_rfc3339("2024-05-01T02:30:00+08:00").unwrap()
+            }
+        }
+
+        let formatter = JsonFormatter::default().with_date_buckets(true).with_timer(TimeSourceTimer::new(FixedClock));
+        let output = capture_json(formatter, || tracing::info!("tick"));
+        // 按时钟自身的时区分桶，UTC 下是 4 月 30 日 18 时
+        assert!(output.starts_with(r#"{"timestamp":"2024-05-01T02:30:00+08:00","year":2024,"month":5,"day":1,"hour":2,"level":"INFO","#), "{output}");
+
+        let output = capture_json(JsonFormatter::default(), || tracing::info!("tick"));
+        assert!(!output.contains(r#""year":"#), "{output}");
+    }
+
+    #[test]
+    fn json_uptime() {
+        let output = capture_json(JsonFormatter::default().with_uptime(true), || tracing::info!("tick"));
+        let uptime = output.split_once(r#","uptime_ms":"#).unwrap_or_else(|| panic!("{output}")).1;
+        let (uptime, rest) = uptime.split_once(',').unwrap();
+        assert!(uptime.parse::<u64>().is_ok(), "{output}");
+        assert!(rest.starts_with(r#""level":"INFO""#), "{output}");
+
+        let output = capture_json(JsonFormatter::default(), || tracing::info!("tick"));
+        assert!(!output.contains("uptime_ms"), "{output}");
+    }
+
+    #[test]
+    fn json_builtin_key_collision() {
+        let output = capture_json(JsonFormatter::default(), || {
+            let _span = tracing::info_span!("request").entered();
+            tracing::info!(level = "x", target = "t", span = 1, user = "alice", "hi");
+        });
+        for key in ["level", "target", "span", "user", "fields.level", "fields.target", "fields.span"] {
+            assert_eq!(output.matches(&format!(r#""{key}":"#)).count(), 1, "{key}: {output}");
+        }
+        assert!(output.contains(r#""level":"INFO""#), "{output}");
+        assert!(output.contains(r#","message":"hi","fields.level":"x","fields.target":"t","fields.span":"1","user":"alice","#), "{output}");
+    }
+
+    #[test]
+    fn json_bridged_log_record() {
+        let output = capture_json(JsonFormatter::default(), || {
+            let record = tracing_log::log::Record::builder()
+                .args(format_args!("from log"))
+                .level(tracing_log::log::Level::Warn)
+                .target("legacy")
+                .file(Some("legacy.rs"))
+                .line(Some(3))
+                .build();
+            tracing_log::format_trace(&record).unwrap();
+        });
+        assert!(output.contains(r#""level":"WARN","target":"legacy","filename":"legacy.rs","line_number":3,"message":"from log"}"#), "{output}");
+    }
+
+    #[test]
+    fn json_string_escapes() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(json_string("a\"b\\c\nd\te\u{1}"), "\"a\\\"b\\\\c\\nd\\te\\u0001\"");
+        assert_eq!(json_string("中文"), "\"中文\"");
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use tracing_core::field::{Field, Visit};
+use tracing_core::{Event, Subscriber};
+use tracing_log::NormalizeEvent;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+use crate::log::{RollingFileAppender, Rotation};
+
+/// 默认最多同时打开的文件数
+const DEFAULT_MAX_OPEN_FILES: usize = 64;
+
+/// 已打开的文件，按最近一次写入的先后关闭
+struct OpenFiles {
+    files: HashMap<String, (RollingFileAppender, u64)>,
+    /// 每次写入递增，用于找出最久没有写入的文件
+    clock: u64,
+}
+
+/// 按事件中某个字段的值把日志写到不同文件的 layer，见`keyed_file_layer`
+pub struct KeyedFileLayer {
+    field: &'static str,
+    dir: PathBuf,
+    rotation: Rotation,
+    max_open_files: usize,
+    open: Arc<Mutex<OpenFiles>>,
+}
+
+/// `keyed_file_layer`返回的守卫，drop 时 flush 并关闭所有打开的文件
+///
+/// 需要保存到程序结束，例如`let _files = ...`。
+#[must_use = "dropping the guard closes all log files"]
+pub struct KeyedFilesGuard {
+    open: Arc<Mutex<OpenFiles>>,
+}
+
+impl Drop for KeyedFilesGuard {
+    fn drop(&mut self) {
+        let mut open = lock(&self.open);
+        for (file, _) in open.files.values_mut() {
+            let _ = file.flush();
+        }
+        open.files.clear();
+    }
+}
+
+fn lock(open: &Mutex<OpenFiles>) -> MutexGuard<'_, OpenFiles> {
+    open.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// 多租户等场景用：事件的`field`字段决定写入的文件，例如`tenant = "acme"`写入`dir/tenant-acme.log`
+///
+/// 每个文件按`rotation`各自切分，第一次出现某个值时才创建；没有该字段的事件不写入文件。
+/// 同时打开的文件数不超过`max_open_files`（默认 64），超出时关闭最久没有写入的文件，之后再写入时重新以追加方式打开，
+/// 避免租户很多时耗尽文件描述符。字段值中字母、数字、`-`、`_`以外的字符替换为`_`。
+///
+/// # Example
+/// ```no_run
+/// use tracing_subscriber::layer::SubscriberExt;
+/// use myutil::log::{keyed_file_layer, Rotation};
+///
+/// let (layer, _files) = keyed_file_layer("tenant", "logs", Rotation::Daily).expect("Failed to create log dir");
+/// let subscriber = tracing_subscriber::registry().with(layer.max_open_files(128));
+/// tracing::subscriber::set_global_default(subscriber).unwrap();
+///
+/// tracing::info!(tenant = "acme", "order created"); // logs/tenant-acme.log.2024-01-31
+/// ```
+pub fn keyed_file_layer(
+    field: &'static str,
+    dir: impl AsRef<Path>,
+    rotation: Rotation,
+) -> std::io::Result<(KeyedFileLayer, KeyedFilesGuard)> {
+    std::fs::create_dir_all(dir.as_ref())?;
+    let open = Arc::new(Mutex::new(OpenFiles {
+        files: HashMap::new(),
+        clock: 0,
+    }));
+    let layer = KeyedFileLayer {
+        field,
+        dir: dir.as_ref().to_path_buf(),
+        rotation,
+        max_open_files: DEFAULT_MAX_OPEN_FILES,
+        open: open.clone(),
+    };
+    Ok((layer, KeyedFilesGuard { open }))
+}
+
+impl KeyedFileLayer {
+    /// 最多同时打开的文件数，最小为 1
+    pub fn max_open_files(mut self, max_open_files: usize) -> Self {
+        self.max_open_files = max_open_files.max(1);
+        self
+    }
+
+    fn write_line(&self, key: &str, line: &str) -> std::io::Result<()> {
+        let mut open = lock(&self.open);
+        open.clock += 1;
+        let now = open.clock;
+        if !open.files.contains_key(key) {
+            if open.files.len() >= self.max_open_files {
+                let oldest = open.files.iter().min_by_key(|(_, (_, used))| *used).map(|(key, _)| key.clone());
+                if let Some((mut file, _)) = oldest.and_then(|oldest| open.files.remove(&oldest)) {
+                    let _ = file.flush();
+                }
+            }
+            let prefix = format!("{}-{key}.log", self.field);
+            let file = RollingFileAppender::new(self.rotation, &self.dir, prefix)?;
+            open.files.insert(key.to_string(), (file, now));
+        }
+        let (file, used) = open.files.get_mut(key).expect("file opened above");
+        *used = now;
+        file.write_all(line.as_bytes())
+    }
+}
+
+/// 只保留字母、数字、`-`和`_`，避免字段值变成其它目录下的路径
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect()
+}
+
+struct KeyedVisitor {
+    field: &'static str,
+    key: Option<String>,
+    message: String,
+    fields: String,
+}
+
+impl Visit for KeyedVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == self.field {
+            self.key = Some(sanitize(value));
+        }
+        self.record_debug(field, &value);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "message" => {
+                let _ = write!(self.message, "{value:?}");
+            }
+            // 桥接自标准库`log`的元数据字段
+            name if name.starts_with("log.") => {}
+            name => {
+                if name == self.field && self.key.is_none() {
+                    self.key = Some(sanitize(&format!("{value:?}")));
+                }
+                let _ = write!(self.fields, " {name}={value:?}");
+            }
+        }
+    }
+}
+
+impl<S: Subscriber> Layer<S> for KeyedFileLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = KeyedVisitor {
+            field: self.field,
+            key: None,
+            message: String::new(),
+            fields: String::new(),
+        };
+        event.record(&mut visitor);
+        let Some(key) = visitor.key else {
+            return;
+        };
+
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+        let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f %z");
+        let line = format!("{now} {} {}: {}{}\n", metadata.level(), metadata.target(), visitor.message, visitor.fields);
+        let _ = self.write_line(&key, &line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::sanitize;
+    use crate::log::{keyed_file_layer, Rotation};
+
+    #[test]
+    fn files_per_key() {
+        let dir = std::env::temp_dir().join(format!("myutil-keyed-{}", std::process::id()));
+        let (layer, guard) = keyed_file_layer("tenant", &dir, Rotation::Never).unwrap();
+        let layer = layer.max_open_files(2);
+        let open = layer.open.clone();
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(tenant = "acme", "first");
+            tracing::info!(tenant = "globex", "second");
+            tracing::info!(tenant = "initech", "third");
+            assert_eq!(super::lock(&open).files.len(), 2);
+            // acme 最久没有写入，已经被关闭，重新打开后追加
+            assert!(!super::lock(&open).files.contains_key("acme"));
+            tracing::info!(tenant = "acme", "fourth");
+            assert_eq!(super::lock(&open).files.len(), 2);
+            tracing::info!("no tenant");
+        });
+        drop(guard);
+
+        let acme = std::fs::read_to_string(dir.join("tenant-acme.log")).unwrap();
+        assert_eq!(acme.lines().count(), 2, "{acme}");
+        assert!(acme.lines().next().unwrap().ends_with(" INFO myutil::log::keyed::tests: first tenant=\"acme\""), "{acme}");
+        assert!(acme.ends_with(": fourth tenant=\"acme\"\n"), "{acme}");
+        assert!(std::fs::read_to_string(dir.join("tenant-globex.log")).unwrap().contains("second"));
+        assert!(std::fs::read_to_string(dir.join("tenant-initech.log")).unwrap().contains("third"));
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 3);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sanitized_keys() {
+        assert_eq!(sanitize("acme-1_b"), "acme-1_b");
+        assert_eq!(sanitize("../etc/passwd"), "___etc_passwd");
+    }
+}
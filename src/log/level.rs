@@ -0,0 +1,76 @@
+use tracing::Level;
+
+/// `parse_level`无法识别的级别
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseLevelError {
+    input: String,
+}
+
+impl std::fmt::Display for ParseLevelError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "unknown log level `{}`, expected one of trace, debug, info, warn, error", self.input)
+    }
+}
+
+impl std::error::Error for ParseLevelError {}
+
+/// 解析配置文件或环境变量中的日志级别，不区分大小写，忽略首尾空白
+///
+/// 除`trace` `debug` `info` `warn` `error`外还接受`warning`、`err`和`information`
+///
+/// # Example
+/// ```
+/// use myutil::log::parse_level;
+///
+/// assert_eq!(parse_level(" Warning "), Ok(tracing::Level::WARN));
+/// assert_eq!(parse_level("ERR"), Ok(tracing::Level::ERROR));
+/// assert!(parse_level("loud").is_err());
+/// ```
+pub fn parse_level(s: &str) -> Result<Level, ParseLevelError> {
+    match s.trim().to_ascii_lowercase().as_str() {
+        "trace" => Ok(Level::TRACE),
+        "debug" => Ok(Level::DEBUG),
+        "info" | "information" => Ok(Level::INFO),
+        "warn" | "warning" => Ok(Level::WARN),
+        "error" | "err" => Ok(Level::ERROR),
+        _ => Err(ParseLevelError { input: s.to_string() }),
+    }
+}
+
+/// 小写的级别名，与`parse_level`互逆，也是`RUST_LOG`中的写法
+pub fn level_to_string(level: Level) -> &'static str {
+    match level {
+        Level::TRACE => "trace",
+        Level::DEBUG => "debug",
+        Level::INFO => "info",
+        Level::WARN => "warn",
+        Level::ERROR => "error",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing::Level;
+
+    use crate::log::{level_to_string, parse_level};
+
+    #[test]
+    fn parse_aliases() {
+        assert_eq!(parse_level("TRACE"), Ok(Level::TRACE));
+        assert_eq!(parse_level("Debug"), Ok(Level::DEBUG));
+        assert_eq!(parse_level("information"), Ok(Level::INFO));
+        assert_eq!(parse_level("warning"), Ok(Level::WARN));
+        assert_eq!(parse_level("err"), Ok(Level::ERROR));
+
+        let err = parse_level("verbose").unwrap_err();
+        assert_eq!(err.to_string(), "unknown log level `verbose`, expected one of trace, debug, info, warn, error");
+        assert!(parse_level("").is_err());
+    }
+
+    #[test]
+    fn round_trip() {
+        for level in [Level::TRACE, Level::DEBUG, Level::INFO, Level::WARN, Level::ERROR] {
+            assert_eq!(parse_level(level_to_string(level)), Ok(level));
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use std::fmt::Write as _;
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracing_core::field::{Field, Visit};
+use tracing_core::span::{Attributes, Id, Record};
+use tracing_core::{Event, LevelFilter, Metadata, Subscriber};
+use tracing_log::NormalizeEvent;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// `LogMode::Minimal`使用的 subscriber，不经过`tracing-subscriber`的 fmt 和过滤器，每条事件输出一行`LEVEL target: message key=value`
+///
+/// 不输出时间和 span，只按最大级别过滤，适合不需要丰富格式的小工具
+///
+/// # Example
+/// ```
+/// use tracing_core::LevelFilter;
+/// use myutil::log::MinimalSubscriber;
+///
+/// tracing::subscriber::with_default(MinimalSubscriber::new(LevelFilter::INFO), || {
+///     tracing::info!(user = "alice", "login"); // INFO rust_out: login user="alice"
+///     tracing::debug!("hidden");
+/// });
+/// ```
+pub struct MinimalSubscriber<W = fn() -> std::io::Stdout> {
+    max_level: LevelFilter,
+    next_id: AtomicU64,
+    make_writer: W,
+}
+
+impl MinimalSubscriber {
+    /// 输出到 stdout，只输出`max_level`及以上的事件
+    pub fn new(max_level: LevelFilter) -> Self {
+        MinimalSubscriber::with_writer(max_level, std::io::stdout)
+    }
+}
+
+impl<W> MinimalSubscriber<W>
+    where
+        W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    /// 输出到`make_writer`
+    pub fn with_writer(max_level: LevelFilter, make_writer: W) -> Self {
+        MinimalSubscriber {
+            max_level,
+            next_id: AtomicU64::new(1),
+            make_writer,
+        }
+    }
+}
+
+#[derive(Default)]
+struct LineVisitor {
+    message: String,
+    fields: String,
+}
+
+impl Visit for LineVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "message" => {
+                let _ = write!(self.message, "{value:?}");
+            }
+            // 桥接自标准库`log`的元数据字段
+            name if name.starts_with("log.") => {}
+            name => {
+                let _ = write!(self.fields, " {name}={value:?}");
+            }
+        }
+    }
+}
+
+impl<W> Subscriber for MinimalSubscriber<W>
+    where
+        W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        *metadata.level() <= self.max_level
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(self.max_level)
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        // span 不输出，只需要分配不重复的非零 id
+        Id::from_u64(self.next_id.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut visitor = LineVisitor::default();
+        event.record(&mut visitor);
+
+        // 桥接自`log`的事件，真实的 target 记录在`log.target`字段中
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+        let line = format!("{} {}: {}{}\n", metadata.level(), metadata.target(), visitor.message, visitor.fields);
+        let _ = self.make_writer.make_writer_for(metadata).write_all(line.as_bytes());
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_core::LevelFilter;
+
+    use crate::log::MinimalSubscriber;
+    use crate::log::tests::TestWriter;
+
+    #[test]
+    fn minimal_lines() {
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = MinimalSubscriber::with_writer(LevelFilter::INFO, move || make_writer.clone());
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request", id = 7);
+            let _entered = span.enter();
+            tracing::info!(user = "alice", attempts = 2, "login");
+            tracing::debug!("hidden");
+            tracing::warn!("slow");
+        });
+        assert_eq!(writer.output(), "INFO myutil::log::minimal::tests: login user=\"alice\" attempts=2\nWARN myutil::log::minimal::tests: slow\n");
+    }
+}
@@ -0,0 +1,103 @@
+use std::sync::OnceLock;
+
+use tracing_subscriber::{Layer, Registry, reload};
+
+/// 可挂到镜像位置上的 layer
+pub type BoxedMirrorLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// 镜像位置，放在`Registry`之上，所以能收到经过全局过滤后的全部事件
+pub type MirrorLayer = reload::Layer<Option<BoxedMirrorLayer>, Registry>;
+
+static MIRROR: OnceLock<MirrorHandle> = OnceLock::new();
+
+/// 控制镜像位置上挂载的 layer
+#[derive(Clone)]
+pub struct MirrorHandle(reload::Handle<Option<BoxedMirrorLayer>, Registry>);
+
+impl MirrorHandle {
+    /// 创建一个空的镜像位置，把返回的 layer 加到`registry()`上使用
+    pub fn new() -> (MirrorLayer, MirrorHandle) {
+        let (layer, handle) = reload::Layer::new(None);
+        (layer, MirrorHandle(handle))
+    }
+
+    /// 挂载`layer`，已挂载的 layer 会被替换并 drop
+    pub fn attach<L>(&self, layer: L) -> Result<(), reload::Error>
+        where
+            L: Layer<Registry> + Send + Sync + 'static,
+    {
+        self.0.reload(Some(Box::new(layer) as BoxedMirrorLayer))
+    }
+
+    /// 卸下并 drop 当前挂载的 layer
+    pub fn detach(&self) -> Result<(), reload::Error> {
+        self.0.reload(None)
+    }
+}
+
+/// 在已初始化的日志上临时挂一个镜像 layer（例如捕获到内存或转发），不需要重新初始化
+///
+/// 只有`LogMode::Full`带有镜像位置，其它模式返回`false`。
+///
+/// 生命周期：layer 从挂载时开始接收事件（挂载前已打开的 span 对它不可见），
+/// 直到`remove_mirror_layer`或下一次`add_mirror_layer`时被 drop，需要 flush 的 layer 应在 drop 时完成。
+///
+/// # Example
+/// ```
+/// use myutil::log::{add_mirror_layer, init_log_or_panic, LogMode, remove_mirror_layer};
+///
+/// init_log_or_panic(LogMode::Full, tracing::Level::INFO);
+/// // 排查问题时开启
+/// add_mirror_layer(tracing_subscriber::fmt::layer().with_writer(std::io::stderr));
+/// // 排查结束后关闭
+/// remove_mirror_layer();
+/// ```
+pub fn add_mirror_layer<L>(layer: L) -> bool
+    where
+        L: Layer<Registry> + Send + Sync + 'static,
+{
+    MIRROR.get().is_some_and(|handle| handle.attach(layer).is_ok())
+}
+
+/// 卸下`add_mirror_layer`挂载的 layer
+pub fn remove_mirror_layer() -> bool {
+    MIRROR.get().is_some_and(|handle| handle.detach().is_ok())
+}
+
+/// 创建镜像位置并登记为全局使用的那一个
+pub(crate) fn global_mirror_layer() -> MirrorLayer {
+    let (layer, handle) = MirrorHandle::new();
+    let _ = MIRROR.set(handle);
+    layer
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::log::mirror::MirrorHandle;
+    use crate::log::tests::TestWriter;
+
+    #[test]
+    fn mirror_attach_detach() {
+        let (mirror, handle) = MirrorHandle::new();
+        let subscriber = tracing_subscriber::registry().with(mirror);
+        let writer = TestWriter::default();
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("before attach");
+
+            let make_writer = writer.clone();
+            handle.attach(tracing_subscriber::fmt::layer().with_ansi(false).with_writer(move || make_writer.clone())).unwrap();
+            tracing::info!("while attached");
+
+            handle.detach().unwrap();
+            tracing::info!("after detach");
+        });
+
+        let output = writer.output();
+        assert!(!output.contains("before attach"), "{output}");
+        assert!(output.contains("while attached"), "{output}");
+        assert!(!output.contains("after detach"), "{output}");
+    }
+}
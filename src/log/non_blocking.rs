@@ -0,0 +1,245 @@
+use std::io::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, Sender, SyncSender, TrySendError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+
+use tracing_subscriber::fmt::MakeWriter;
+
+enum Message {
+    Line(Vec<u8>),
+    /// 写完之前的内容并 flush 后回复
+    Flush(Sender<()>),
+    Shutdown,
+}
+
+/// `non_blocking`的队列最多缓存的日志条数
+pub const DEFAULT_BUFFERED_LINES_LIMIT: usize = 128_000;
+
+/// 把日志交给后台线程写入的 writer，由`non_blocking`创建
+///
+/// 输出日志的线程只负责把内容放进有界的队列，不会因为磁盘或管道慢而阻塞；队列满时丢弃新的日志并计数，见`dropped_lines`。
+/// 实现了`MakeWriter`，可以直接传给`with_writer`；`flush`会等待后台线程写完，配合`FlushLayer`使用。
+#[derive(Clone)]
+pub struct NonBlocking {
+    sender: SyncSender<Message>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl NonBlocking {
+    /// 因为队列已满而丢弃的日志条数
+    pub fn dropped_lines(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// `non_blocking`返回的守卫，drop 时写完剩余日志并结束后台线程
+///
+/// 需要保存到程序结束，提前 drop 之后的日志会被丢弃，且不会有任何提示。
+/// 例如`let _worker = ...`，写成`let _ = ...`会立即 drop。
+#[must_use = "dropping the guard stops writing log output"]
+pub struct WorkerGuard {
+    sender: SyncSender<Message>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for WorkerGuard {
+    fn drop(&mut self) {
+        let _ = self.sender.send(Message::Shutdown);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// 启动后台线程写入`writer`，返回`NonBlocking`和需要保存的`WorkerGuard`；队列最多缓存`DEFAULT_BUFFERED_LINES_LIMIT`条日志
+///
+/// # Example
+/// ```
+/// use myutil::log::non_blocking;
+///
+/// let (writer, _guard) = non_blocking(std::io::stderr());
+/// let subscriber = tracing_subscriber::fmt().with_writer(writer).finish();
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::info!("written by the worker thread");
+/// });
+/// ```
+pub fn non_blocking<W: Write + Send + 'static>(writer: W) -> (NonBlocking, WorkerGuard) {
+    non_blocking_with_capacity(writer, DEFAULT_BUFFERED_LINES_LIMIT)
+}
+
+/// 与`non_blocking`相同，但队列最多缓存`capacity`条日志（至少 1 条）
+///
+/// 写入跟不上时内存占用不超过`capacity`条日志，超出的日志被丢弃而不是让输出日志的线程等待。
+///
+/// # Example
+/// ```
+/// use myutil::log::non_blocking_with_capacity;
+///
+/// let (writer, _guard) = non_blocking_with_capacity(std::io::stderr(), 1024);
+/// let dropped = writer.clone();
+/// let subscriber = tracing_subscriber::fmt().with_writer(writer).finish();
+/// tracing::subscriber::with_default(subscriber, || tracing::info!("hello"));
+/// println!("dropped {} lines", dropped.dropped_lines());
+/// ```
+pub fn non_blocking_with_capacity<W: Write + Send + 'static>(mut writer: W, capacity: usize) -> (NonBlocking, WorkerGuard) {
+    let (sender, receiver) = mpsc::sync_channel::<Message>(capacity.max(1));
+
+    let thread = std::thread::Builder::new()
+        .name("log-writer".to_string())
+        .spawn(move || {
+            for message in receiver {
+                match message {
+                    Message::Line(line) => {
+                        let _ = writer.write_all(&line);
+                    }
+                    Message::Flush(done) => {
+                        let _ = writer.flush();
+                        let _ = done.send(());
+                    }
+                    Message::Shutdown => break,
+                }
+            }
+            let _ = writer.flush();
+        })
+        .expect("Failed to spawn log writer thread");
+
+    let guard = WorkerGuard {
+        sender: sender.clone(),
+        thread: Some(thread),
+    };
+    (NonBlocking { sender, dropped: Arc::new(AtomicU64::new(0)) }, guard)
+}
+
+impl Write for NonBlocking {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.sender.try_send(Message::Line(buf.to_vec())) {
+            Ok(()) => Ok(buf.len()),
+            Err(TrySendError::Full(_)) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                Ok(buf.len())
+            }
+            Err(TrySendError::Disconnected(_)) => {
+                Err(std::io::Error::new(std::io::ErrorKind::BrokenPipe, "log writer thread stopped"))
+            }
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let (done, wait) = mpsc::channel();
+        if self.sender.send(Message::Flush(done)).is_ok() {
+            let _ = wait.recv();
+        }
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for NonBlocking {
+    type Writer = NonBlocking;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::sync::mpsc;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, Instant};
+
+    use crate::log::{LogConfig, non_blocking, non_blocking_with_capacity};
+    use crate::log::tests::TestWriter;
+
+    /// 每次写入都很慢的 writer，模拟磁盘或管道拥塞
+    struct SlowWriter(TestWriter);
+
+    impl Write for SlowWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            std::thread::sleep(Duration::from_millis(20));
+            self.0.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn emitting_does_not_block() {
+        let output = TestWriter::default();
+        let (writer, guard) = non_blocking(SlowWriter(output.clone()));
+        let subscriber = LogConfig::new().writer(writer).ansi(false).build();
+
+        let start = Instant::now();
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..20 {
+                tracing::info!("event {i}");
+            }
+        });
+        // 同步写入至少需要 20 * 20ms
+        assert!(start.elapsed() < Duration::from_millis(200), "{:?}", start.elapsed());
+
+        drop(guard);
+        assert_eq!(output.output().lines().count(), 20);
+    }
+
+    /// 第一次写入时通知测试，然后等待测试放行，模拟卡住的管道
+    struct BlockedWriter {
+        output: TestWriter,
+        started: mpsc::Sender<()>,
+        gate: Arc<Mutex<()>>,
+    }
+
+    impl Write for BlockedWriter {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            let _ = self.started.send(());
+            let _open = self.gate.lock().unwrap();
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn full_queue_drops_lines() {
+        let output = TestWriter::default();
+        let (started, wait_started) = mpsc::channel();
+        let gate = Arc::new(Mutex::new(()));
+        let writer = BlockedWriter { output: output.clone(), started, gate: gate.clone() };
+        let (mut writer, guard) = non_blocking_with_capacity(writer, 2);
+        // 在 guard 之后声明，断言失败时先放行写入线程，guard 的 drop 才不会一直等待
+        let closed = gate.lock().unwrap();
+
+        writer.write_all(b"line 0\n").unwrap();
+        // 后台线程取走第一行后卡在写入上，队列中还能放 2 行
+        wait_started.recv().unwrap();
+        for i in 1..10 {
+            writer.write_all(format!("line {i}\n").as_bytes()).unwrap();
+        }
+        assert_eq!(writer.dropped_lines(), 7);
+
+        drop(closed);
+        drop(guard);
+        assert_eq!(output.output(), "line 0\nline 1\nline 2\n");
+    }
+
+    #[test]
+    fn guard_drop_writes_remaining() {
+        let output = TestWriter::default();
+        let (mut writer, guard) = non_blocking(output.clone());
+        for i in 0..100 {
+            writeln!(writer, "line {i}").unwrap();
+        }
+        drop(guard);
+
+        let text = output.output();
+        assert_eq!(text.lines().count(), 100, "{text}");
+        assert!(text.ends_with("line 99\n"), "{text}");
+        // 守卫 drop 后写入报错而不是阻塞
+        assert!(writeln!(writer, "late").is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::field::{Field, Visit};
+use tracing::Level;
+use tracing_core::{Event, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use super::json::json_string;
+
+/// 把事件按 OTLP/JSON 日志格式写入文件，每行一个完整的`ExportLogsServiceRequest`，见`otlp_json_layer`
+pub struct OtlpJsonLayer {
+    file: Mutex<File>,
+    resource: String,
+}
+
+/// 创建把事件写成 OTLP/JSON 的 layer，不需要运行 collector，之后可以逐行上传：
+///
+/// ```text
+/// while read -r line; do
+///     curl -X POST -H 'Content-Type: application/json' -d "$line" http://collector:4318/v1/logs
+/// done < app.otlp.jsonl
+/// ```
+///
+/// 每行的结构为`{"resourceLogs":[{"resource":{...},"scopeLogs":[{"scope":{...},"logRecords":[{...}]}]}]}`，
+/// `service.name`放在 resource 上，scope 为事件的 target，字段和 span 名（`span.name`，由外到内以`:`连接）放在 attributes 中。
+/// 文件以追加方式打开。
+///
+/// # Example
+/// ```no_run
+/// use tracing_subscriber::layer::SubscriberExt;
+/// use myutil::log::otlp_json_layer;
+///
+/// let layer = otlp_json_layer("app.otlp.jsonl", "my-app").expect("Failed to open otlp file");
+/// tracing::subscriber::set_global_default(tracing_subscriber::registry().with(layer)).unwrap();
+/// ```
+pub fn otlp_json_layer(path: impl AsRef<Path>, service_name: &str) -> std::io::Result<OtlpJsonLayer> {
+    let file = File::options().create(true).append(true).open(path)?;
+    Ok(OtlpJsonLayer {
+        file: Mutex::new(file),
+        resource: format!(r#"{{"attributes":[{}]}}"#, attribute("service.name", "stringValue", &json_string(service_name))),
+    })
+}
+
+/// OTLP 的日志级别编号
+fn severity_number(level: &Level) -> u8 {
+    match *level {
+        Level::TRACE => 1,
+        Level::DEBUG => 5,
+        Level::INFO => 9,
+        Level::WARN => 13,
+        Level::ERROR => 17,
+    }
+}
+
+/// `{"key":..,"value":{<kind>:<value>}}`，`value`须已是 JSON
+fn attribute(key: &str, kind: &str, value: &str) -> String {
+    format!(r#"{{"key":{},"value":{{"{kind}":{value}}}}}"#, json_string(key))
+}
+
+#[derive(Default)]
+struct OtlpVisitor {
+    body: Option<String>,
+    attributes: Vec<String>,
+}
+
+impl Visit for OtlpVisitor {
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        // int64 在 OTLP/JSON 中以字符串表示
+        self.attributes.push(attribute(field.name(), "intValue", &json_string(&value.to_string())));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.attributes.push(attribute(field.name(), "intValue", &json_string(&value.to_string())));
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        if value.is_finite() {
+            self.attributes.push(attribute(field.name(), "doubleValue", &value.to_string()));
+        } else {
+            self.record_debug(field, &value);
+        }
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.attributes.push(attribute(field.name(), "boolValue", &value.to_string()));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.body = Some(value.to_string());
+        } else if !field.name().starts_with("log.") {
+            self.attributes.push(attribute(field.name(), "stringValue", &json_string(value)));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        match field.name() {
+            "message" => self.body = Some(format!("{value:?}")),
+            name if name.starts_with("log.") => {}
+            name => self.attributes.push(attribute(name, "stringValue", &json_string(&format!("{value:?}")))),
+        }
+    }
+}
+
+impl<S> Layer<S> for OtlpJsonLayer
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        let mut visitor = OtlpVisitor::default();
+        event.record(&mut visitor);
+        if let Some(scope) = ctx.event_scope(event) {
+            let names: Vec<&str> = scope.from_root().map(|span| span.name()).collect();
+            visitor.attributes.push(attribute("span.name", "stringValue", &json_string(&names.join(":"))));
+        }
+
+        let time_unix_nano = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+        let record = format!(
+            r#"{{"timeUnixNano":"{time_unix_nano}","severityNumber":{},"severityText":"{}","body":{{"stringValue":{}}},"attributes":[{}]}}"#,
+            severity_number(metadata.level()),
+            metadata.level(),
+            json_string(visitor.body.as_deref().unwrap_or_default()),
+            visitor.attributes.join(","),
+        );
+        let line = format!(
+            r#"{{"resourceLogs":[{{"resource":{},"scopeLogs":[{{"scope":{{"name":{}}},"logRecords":[{record}]}}]}}]}}"#,
+            self.resource,
+            json_string(metadata.target()),
+        );
+
+        let mut file = self.file.lock().unwrap_or_else(|err| err.into_inner());
+        let _ = writeln!(file, "{line}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::log::otlp_json_layer;
+
+    #[test]
+    fn otlp_json_structure() {
+        let path = std::env::temp_dir().join(format!("myutil-otlp-{}.jsonl", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let layer = otlp_json_layer(&path, "test-app").unwrap();
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            let _span = tracing::info_span!("request").entered();
+            tracing::warn!(target: "app::db", rows = 3, ok = true, table = "users", "slow \"query\"");
+        });
+
+        let output = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let line = output.lines().next().unwrap();
+        assert!(line.starts_with(r#"{"resourceLogs":[{"resource":{"attributes":[{"key":"service.name","value":{"stringValue":"test-app"}}]},"scopeLogs":[{"scope":{"name":"app::db"},"logRecords":[{"timeUnixNano":""#), "{line}");
+        assert!(line.ends_with(concat!(
+            r#"","severityNumber":13,"severityText":"WARN","body":{"stringValue":"slow \"query\""},"attributes":["#,
+            r#"{"key":"rows","value":{"intValue":"3"}},{"key":"ok","value":{"boolValue":true}},"#,
+            r#"{"key":"table","value":{"stringValue":"users"}},{"key":"span.name","value":{"stringValue":"request"}}]}]}]}]}"#,
+        )), "{line}");
+    }
+}
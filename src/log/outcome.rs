@@ -0,0 +1,115 @@
+use std::time::Instant;
+
+use tracing::span::{Attributes, Id};
+use tracing::Span;
+use tracing_core::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::{LookupSpan, Registry};
+use tracing_subscriber::Layer;
+
+/// 保存在 span 扩展中的创建时刻
+struct Started(Instant);
+
+/// 保存在 span 扩展中的结果
+struct Outcome(&'static str);
+
+/// 记录 span 所代表的操作是否成功，配合`span_outcome_layer`在 span 关闭时输出`operation[ok, 12ms]`
+///
+/// span 声明了`outcome`字段（`outcome = tracing::field::Empty`）时也会写入该字段，值为`"ok"`或`"err"`。
+///
+/// # Example
+/// ```
+/// use tracing_subscriber::layer::SubscriberExt;
+/// use myutil::log::{record_span_outcome, span_outcome_layer};
+///
+/// let subscriber = tracing_subscriber::registry()
+///     .with(tracing_subscriber::fmt::layer())
+///     .with(span_outcome_layer());
+/// tracing::subscriber::with_default(subscriber, || {
+///     let span = tracing::info_span!("load_user");
+///     let result: Result<u32, String> = span.in_scope(|| Ok(42));
+///     record_span_outcome(&span, &result);
+/// }); // INFO load_user[ok, 0ms]
+/// ```
+pub fn record_span_outcome<T, E>(span: &Span, result: &Result<T, E>) {
+    let outcome = if result.is_ok() { "ok" } else { "err" };
+    span.record("outcome", outcome);
+    span.with_subscriber(|(id, dispatch)| {
+        if let Some(span) = dispatch.downcast_ref::<Registry>().and_then(|registry| registry.span(id)) {
+            span.extensions_mut().replace(Outcome(outcome));
+        }
+    });
+}
+
+/// 在记录过结果的 span 关闭时输出结果和耗时，见`span_outcome_layer`
+pub struct SpanOutcomeLayer;
+
+/// 创建输出 span 结果的 layer，需要使用`Registry`
+///
+/// 只有调用过`record_span_outcome`的 span 在关闭时以 span 自身的级别输出`name[ok, 12ms]`或`name[err, 12ms]`，耗时从创建 span 开始计算
+pub fn span_outcome_layer() -> SpanOutcomeLayer {
+    SpanOutcomeLayer
+}
+
+impl<S> Layer<S> for SpanOutcomeLayer
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Started(Instant::now()));
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+        let (outcome, elapsed_ms) = {
+            let extensions = span.extensions();
+            let Some(Outcome(outcome)) = extensions.get::<Outcome>() else {
+                return;
+            };
+            let elapsed_ms = extensions.get::<Started>().map_or(0, |Started(start)| start.elapsed().as_millis());
+            (*outcome, elapsed_ms)
+        };
+        event_at!(*span.metadata().level(), "{}[{outcome}, {elapsed_ms}ms]", span.name());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::log::{CustomFormatter, record_span_outcome, span_outcome_layer};
+    use crate::log::tests::TestWriter;
+
+    #[test]
+    fn outcome_on_close() {
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_subscriber::fmt::layer().with_ansi(false).with_writer(move || make_writer.clone()).event_format(CustomFormatter::default()))
+            .with(span_outcome_layer());
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("load_user", outcome = tracing::field::Empty);
+            record_span_outcome(&span, &Ok::<_, ()>(42));
+            drop(span);
+
+            let span = tracing::warn_span!("save_user");
+            record_span_outcome(&span, &Err::<(), _>("disk full"));
+            drop(span);
+
+            // 没有记录结果的 span 不输出
+            drop(tracing::info_span!("plain"));
+        });
+
+        let output = writer.output();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2, "{output}");
+        assert!(lines[0].starts_with("INFO myutil::log::outcome: "), "{output}");
+        assert!(lines[0].ends_with(" -> load_user[ok, 0ms]"), "{output}");
+        assert!(lines[1].starts_with("WARN "), "{output}");
+        assert!(lines[1].ends_with(" -> save_user[err, 0ms]"), "{output}");
+    }
+}
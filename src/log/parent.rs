@@ -0,0 +1,105 @@
+use std::sync::OnceLock;
+
+use tracing_core::{Event, Subscriber};
+use tracing_subscriber::fmt::{FmtContext, format, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+
+/// 父进程通过这个环境变量把自己的 pid 传给子进程，优先于操作系统的父进程 id
+pub const PARENT_PID_ENV: &str = "LOG_PARENT_PID";
+
+static PARENT_PID: OnceLock<Option<u32>> = OnceLock::new();
+
+/// 父进程 id，用于把多进程流水线中子进程的日志关联到发起它的父进程，进程内只计算一次
+///
+/// 优先读取环境变量`PARENT_PID_ENV`，没有设置或无法解析时在 Unix 上使用`getppid()`，其它平台返回`None`。
+/// 经过`sh -c`等中间进程启动时`getppid()`是中间进程，这时由父进程设置环境变量：
+///
+/// ```no_run
+/// use myutil::log::PARENT_PID_ENV;
+///
+/// std::process::Command::new("worker")
+///     .env(PARENT_PID_ENV, std::process::id().to_string())
+///     .spawn()
+///     .unwrap();
+/// ```
+pub fn parent_pid() -> Option<u32> {
+    *PARENT_PID.get_or_init(|| parent_pid_from(std::env::var(PARENT_PID_ENV).ok().as_deref()))
+}
+
+fn parent_pid_from(env: Option<&str>) -> Option<u32> {
+    if let Some(pid) = env.and_then(|env| env.trim().parse().ok()) {
+        return Some(pid);
+    }
+    #[cfg(unix)]
+    {
+        Some(std::os::unix::process::parent_id())
+    }
+    #[cfg(not(unix))]
+    {
+        None
+    }
+}
+
+/// 在每条文本日志的开头输出固定的字段，如`parent_pid=42 `，其余部分由`inner`格式化
+pub(crate) struct WithStaticFields<F> {
+    pub(crate) fields: String,
+    pub(crate) inner: F,
+}
+
+impl<S, N, F> FormatEvent<S, N> for WithStaticFields<F>
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+        N: for<'a> FormatFields<'a> + 'static,
+        F: FormatEvent<S, N>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: format::Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        write!(writer, "{} ", self.fields)?;
+        self.inner.format_event(ctx, writer, event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::log::{JsonFields, LogConfig, LogFormat};
+    use crate::log::tests::TestWriter;
+
+    use super::parent_pid_from;
+
+    #[test]
+    fn parent_pid_sources() {
+        assert_eq!(parent_pid_from(Some("4242")), Some(4242));
+        #[cfg(unix)]
+        {
+            assert_eq!(parent_pid_from(None), Some(std::os::unix::process::parent_id()));
+            assert_eq!(parent_pid_from(Some("not-a-pid")), Some(std::os::unix::process::parent_id()));
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn parent_pid_field() {
+        let pid = crate::log::parent_pid().unwrap();
+        let capture = |format: LogFormat| {
+            let writer = TestWriter::default();
+            let make_writer = writer.clone();
+            let subscriber = LogConfig::new()
+                .writer(move || make_writer.clone())
+                .ansi(false)
+                .with_parent_pid(true)
+                .format(format)
+                .build();
+            tracing::subscriber::with_default(subscriber, || tracing::info!("child started"));
+            writer.output()
+        };
+
+        let output = capture(LogFormat::Compact);
+        assert!(output.starts_with(&format!("parent_pid={pid} ")), "{output}");
+        let output = capture(LogFormat::Json(JsonFields::default()));
+        assert!(output.contains(&format!(r#""message":"child started","parent_pid":"{pid}""#)), "{output}");
+    }
+}
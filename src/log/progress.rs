@@ -0,0 +1,166 @@
+use std::io::{IsTerminal, StdoutLock, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use tracing_subscriber::fmt::MakeWriter;
+
+struct ProgressState {
+    /// 终端上正在原地刷新的进度行
+    line: Option<String>,
+    /// 非终端时上一次输出的`(label, 百分比档位)`
+    last_step: Option<(String, u64)>,
+}
+
+static STATE: Mutex<ProgressState> = Mutex::new(ProgressState { line: None, last_step: None });
+
+/// 是否在终端上输出过进度行，之前`ProgressStdout`直接写 stdout，不经过 STATE
+static USED: AtomicBool = AtomicBool::new(false);
+
+fn state() -> MutexGuard<'static, ProgressState> {
+    STATE.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// 输出进度`label: 50% (5/10)`
+///
+/// stdout 是终端时用`\r`原地刷新，完成后换行；否则每跨过 10% 输出一条 info 日志。
+/// 日志需要使用`ProgressStdout`作为 writer（各`LogMode`默认即是），这样日志行会先清掉进度行、输出后再重绘，不会交错。
+/// 没有调用过`progress`时`ProgressStdout`与普通的 stdout 相同。
+///
+/// # Example
+/// ```
+/// use myutil::log::progress;
+///
+/// for done in 0..=10 {
+///     progress(done, 10, "downloading");
+/// }
+/// ```
+pub fn progress(done: u64, total: u64, label: &str) {
+    let percent = (done.min(total) * 100).checked_div(total).unwrap_or(100);
+    let line = format!("{label}: {percent:>3}% ({done}/{total})");
+
+    if std::io::stdout().is_terminal() {
+        USED.store(true, Ordering::Relaxed);
+        let mut state = state();
+        let mut stdout = std::io::stdout().lock();
+        if percent >= 100 {
+            state.line = None;
+            let _ = writeln!(stdout, "\r{line}");
+        } else {
+            let _ = write!(stdout, "\r{line}");
+            state.line = Some(line);
+        }
+        let _ = stdout.flush();
+        return;
+    }
+
+    let step = percent / 10;
+    let changed = {
+        let mut state = state();
+        let changed = !matches!(&state.last_step, Some((last_label, last_step)) if last_label == label && *last_step == step);
+        state.last_step = Some((label.to_string(), step));
+        changed
+    };
+    // 输出日志时不能持有 STATE，ProgressStdout 也会加锁
+    if changed {
+        tracing::info!("{line}");
+    }
+}
+
+/// 与`progress`协同的 stdout writer
+///
+/// 在终端上输出过进度行后，写日志前先清掉进度行、写完后重绘；设置了`NO_COLOR`时用空格覆盖进度行，不输出控制序列。
+/// 没有调用过`progress`时直接写 stdout。
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ProgressStdout;
+
+pub struct ProgressStdoutWriter {
+    /// 没有使用过进度行时为`None`
+    state: Option<MutexGuard<'static, ProgressState>>,
+    stdout: StdoutLock<'static>,
+    cleared: bool,
+}
+
+impl<'a> MakeWriter<'a> for ProgressStdout {
+    type Writer = ProgressStdoutWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        // 加锁顺序与 progress 一致：先 STATE 再 stdout
+        ProgressStdoutWriter {
+            state: USED.load(Ordering::Relaxed).then(state),
+            stdout: std::io::stdout().lock(),
+            cleared: false,
+        }
+    }
+}
+
+impl Write for ProgressStdoutWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if !self.cleared {
+            self.cleared = true;
+            if let Some(line) = self.state.as_ref().and_then(|state| state.line.as_ref()) {
+                if self.stdout.is_terminal() {
+                    let clear = clear_sequence(line, std::env::var_os("NO_COLOR").as_deref());
+                    self.stdout.write_all(clear.as_bytes())?;
+                }
+            }
+        }
+        self.stdout.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.stdout.flush()
+    }
+}
+
+impl Drop for ProgressStdoutWriter {
+    fn drop(&mut self) {
+        if self.cleared {
+            if let Some(line) = self.state.as_ref().and_then(|state| state.line.as_ref()) {
+                let _ = write!(self.stdout, "\r{line}");
+                let _ = self.stdout.flush();
+            }
+        }
+    }
+}
+
+/// 清掉进度行的输出：`NO_COLOR`为非空时用与进度行等宽的空格覆盖，否则用`ESC[2K`清除整行
+fn clear_sequence(line: &str, no_color: Option<&std::ffi::OsStr>) -> String {
+    if no_color.is_some_and(|value| !value.is_empty()) {
+        format!("\r{}\r", " ".repeat(line.chars().count()))
+    } else {
+        "\r\x1b[2K".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsStr;
+
+    use crate::log::CustomFormatter;
+    use crate::log::progress;
+    use crate::log::tests::capture_custom;
+
+    use super::clear_sequence;
+
+    #[test]
+    fn clear_without_escapes_under_no_color() {
+        assert_eq!(clear_sequence("dl:  50%", None), "\r\x1b[2K");
+        assert_eq!(clear_sequence("dl:  50%", Some(OsStr::new(""))), "\r\x1b[2K");
+        assert_eq!(clear_sequence("dl:  50%", Some(OsStr::new("1"))), "\r        \r");
+    }
+
+    #[test]
+    fn progress_logs_every_ten_percent() {
+        // 测试时 stdout 不是终端
+        let output = capture_custom(CustomFormatter::default(), || {
+            for done in 0..=40 {
+                progress(done, 40, "test-progress");
+            }
+        });
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 11, "{output}");
+        assert!(lines[0].ends_with("test-progress:   0% (0/40)"), "{output}");
+        assert!(lines[5].ends_with("test-progress:  50% (20/40)"), "{output}");
+        assert!(lines[10].ends_with("test-progress: 100% (40/40)"), "{output}");
+    }
+}
@@ -0,0 +1,225 @@
+use tracing::level_filters::LevelFilter;
+use tracing::Level;
+use tracing_log::AsLog;
+use tracing_subscriber::filter::ParseError;
+use tracing_subscriber::layer::Layered;
+use tracing_subscriber::{EnvFilter, Registry};
+
+use crate::log::{level_to_string, MirrorLayer};
+
+/// 过滤器下面的 subscriber
+pub(crate) type FilterBase = Layered<Option<MirrorLayer>, Registry>;
+
+/// 运行时修改日志级别的句柄，可以 clone 后交给管理接口等使用，见`LogGuard::reload_handle`
+///
+/// 设置了`LogConfig::floor_level`时，每次修改都不能低于这个级别：低于它的规则会提高到它，保证审计等必需的日志不会被关掉。
+/// 最低级别在初始化时确定，之后不能修改。
+///
+/// # Example
+/// ```
+/// use tracing::Level;
+/// use myutil::log::LogConfig;
+///
+/// let guard = LogConfig::new().level(Level::INFO).floor_level(Level::INFO).install().unwrap();
+/// let handle = guard.reload_handle().unwrap();
+/// handle.set_level(Level::DEBUG).unwrap(); // 临时输出 debug
+/// handle.set_filter("warn,my_app=trace").unwrap(); // 实际为 info,my_app=trace
+/// ```
+#[derive(Clone)]
+pub struct LogReloadHandle {
+    handle: tracing_subscriber::reload::Handle<EnvFilter, FilterBase>,
+    floor: Option<Level>,
+}
+
+impl LogReloadHandle {
+    pub(crate) fn new(handle: tracing_subscriber::reload::Handle<EnvFilter, FilterBase>, floor: Option<Level>) -> Self {
+        LogReloadHandle { handle, floor }
+    }
+
+    /// 只输出`level`及以上的日志，替换之前所有的规则
+    pub fn set_level(&self, level: Level) -> Result<(), LogReloadError> {
+        self.set_filter(level.as_str())
+    }
+
+    /// 使用`RUST_LOG`格式的规则，如`info,my_app=debug`，替换之前所有的规则；无法解析时保留原来的过滤器
+    ///
+    /// 同时更新标准库`log`的最大级别，提高级别后`log`库的 debug 等记录也会经过桥接输出
+    pub fn set_filter(&self, directives: &str) -> Result<(), LogReloadError> {
+        let directives = match self.floor {
+            Some(floor) => clamp_directives(directives, floor),
+            None => directives.to_string(),
+        };
+        let filter = EnvFilter::builder().parse(directives)?;
+        let max_level = filter.max_level_hint().unwrap_or(LevelFilter::TRACE);
+        self.handle.reload(filter)?;
+        // LogTracer 初始化时的最大级别不会随过滤器变化，高于它的 log 记录在 log 库中就被丢弃
+        tracing_log::log::set_max_level(max_level.as_log());
+        Ok(())
+    }
+
+    /// 当前使用的过滤规则，subscriber 已经不存在时返回`None`
+    pub(crate) fn current_filter(&self) -> Option<String> {
+        self.handle.with_current(|filter| filter.to_string()).ok()
+    }
+}
+
+/// 把每条规则中低于`floor`的级别提高到`floor`，并在最前面加上`floor`作为默认级别
+fn clamp_directives(directives: &str, floor: Level) -> String {
+    let floor_filter = LevelFilter::from_level(floor);
+    let mut clamped = vec![level_to_string(floor).to_string()];
+    for directive in directives.split(',').map(str::trim).filter(|directive| !directive.is_empty()) {
+        // `target=level`、`target[span]=level`或只有`level`；只有 target 时表示 trace，不需要处理
+        let (prefix, level) = match directive.rsplit_once('=') {
+            // `[span{field=value}]`中的`=`不是级别的分隔符
+            Some((prefix, level)) if !level.contains(['}', ']']) => (Some(prefix), level),
+            _ => (None, directive),
+        };
+        match level.parse::<LevelFilter>() {
+            Ok(level) if level < floor_filter => {
+                // 只有级别的规则由最前面的`floor`代替
+                if let Some(prefix) = prefix {
+                    clamped.push(format!("{prefix}={}", level_to_string(floor)));
+                }
+            }
+            _ => clamped.push(directive.to_string()),
+        }
+    }
+    clamped.join(",")
+}
+
+/// 运行时修改日志级别失败的原因
+#[derive(Debug)]
+pub enum LogReloadError {
+    /// 规则无法解析
+    Parse(ParseError),
+    /// subscriber 已经不存在
+    Reload(tracing_subscriber::reload::Error),
+}
+
+impl std::fmt::Display for LogReloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            LogReloadError::Parse(err) => write!(f, "invalid log filter: {err}"),
+            LogReloadError::Reload(err) => write!(f, "failed to reload log filter: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for LogReloadError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LogReloadError::Parse(err) => Some(err),
+            LogReloadError::Reload(err) => Some(err),
+        }
+    }
+}
+
+impl From<ParseError> for LogReloadError {
+    fn from(err: ParseError) -> Self {
+        LogReloadError::Parse(err)
+    }
+}
+
+impl From<tracing_subscriber::reload::Error> for LogReloadError {
+    fn from(err: tracing_subscriber::reload::Error) -> Self {
+        LogReloadError::Reload(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Mutex, MutexGuard};
+
+    use tracing::Level;
+
+    use crate::log::LogConfig;
+    use crate::log::tests::TestWriter;
+
+    use super::clamp_directives;
+
+    /// 修改过滤器会改变全局的`log`最大级别，这些测试依次执行
+    fn serial() -> MutexGuard<'static, ()> {
+        static LOCK: Mutex<()> = Mutex::new(());
+        LOCK.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    #[test]
+    fn reload_raises_log_max_level() {
+        let _serial = serial();
+        // 其它测试可能已经设置了全局 logger，同样是 LogTracer
+        let _ = tracing_log::LogTracer::init();
+        tracing_log::log::set_max_level(tracing_log::log::LevelFilter::Info);
+
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let (subscriber, handle) = LogConfig::new()
+            .level(Level::INFO)
+            .writer(move || make_writer.clone())
+            .ansi(false)
+            .build_with_reload();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing_log::log::debug!(target: "legacy", "hidden");
+            handle.set_level(Level::DEBUG).unwrap();
+            assert_eq!(tracing_log::log::max_level(), tracing_log::log::LevelFilter::Debug);
+            tracing_log::log::debug!(target: "legacy", "from log");
+        });
+
+        let output = writer.output();
+        assert_eq!(output.lines().count(), 1, "{output}");
+        assert!(output.contains("DEBUG legacy: from log"), "{output}");
+    }
+
+    #[test]
+    fn reload_to_trace() {
+        let _serial = serial();
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let (subscriber, handle) = LogConfig::new()
+            .level(Level::INFO)
+            .writer(move || make_writer.clone())
+            .ansi(false)
+            .build_with_reload();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!("before");
+            tracing::trace!("hidden");
+            handle.set_level(Level::TRACE).unwrap();
+            tracing::trace!("after");
+            assert!(handle.set_filter("my_app=loud").is_err());
+            tracing::trace!("still trace");
+        });
+
+        let output = writer.output();
+        assert_eq!(output.lines().count(), 3, "{output}");
+        assert!(!output.contains("hidden"), "{output}");
+        assert!(output.contains("TRACE myutil::log::reload::tests: after"), "{output}");
+    }
+
+    #[test]
+    fn floor_clamps_reload() {
+        let _serial = serial();
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let (subscriber, handle) = LogConfig::new()
+            .writer(move || make_writer.clone())
+            .ansi(false)
+            .floor_level(Level::INFO)
+            .build_with_reload();
+        tracing::subscriber::with_default(subscriber, || {
+            handle.set_level(Level::ERROR).unwrap();
+            tracing::info!("audit kept");
+            handle.set_filter("myutil=off").unwrap();
+            tracing::info!("still kept");
+            tracing::debug!("hidden");
+        });
+
+        let output = writer.output();
+        assert_eq!(output.lines().count(), 2, "{output}");
+    }
+
+    #[test]
+    fn clamped_directives() {
+        assert_eq!(clamp_directives("warn,my_app=trace,hyper=off", Level::INFO), "info,my_app=trace,hyper=info");
+        assert_eq!(clamp_directives("my_app,debug", Level::INFO), "info,my_app,debug");
+        assert_eq!(clamp_directives("[request{id=1}]=error", Level::WARN), "warn,[request{id=1}]=warn");
+    }
+}
@@ -0,0 +1,278 @@
+use std::cell::Cell;
+
+use eyre::Report;
+use tracing::Level;
+use tracing_error::{SpanTrace, SpanTraceStatus};
+
+use crate::error::{ErrorCategory, numbered_chain, panic_message};
+
+use super::json::json_string;
+
+/// 错误分类对应的日志级别
+pub fn category_level(category: ErrorCategory) -> Level {
+    match category {
+        ErrorCategory::NotFound
+        | ErrorCategory::InvalidInput
+        | ErrorCategory::PermissionDenied
+        | ErrorCategory::Timeout => Level::WARN,
+        ErrorCategory::Unavailable | ErrorCategory::Internal => Level::ERROR,
+    }
+}
+
+/// 按错误分类自动选择级别输出错误：NotFound -> warn，Internal -> error 等，见`category_level`
+///
+/// 配置了`ErrorRoutes`时同时写到该分类的路由位置。输出编号的错误链，安装了`tracing_error::ErrorLayer`（`LogMode::Full`）时附带 span trace
+///
+/// # Example
+/// ```
+/// use myutil::error::ErrorCategory;
+/// use myutil::log::log_error_auto;
+///
+/// let err = ErrorCategory::NotFound.error("user 42 not found");
+/// log_error_auto(&err); // WARN ... category=not_found
+/// ```
+pub fn log_error_auto(err: &Report) {
+    let category = ErrorCategory::of(err);
+    let message = render_report(err);
+    let level = category_level(category);
+    event_at!(level, category = %category, "{message}");
+    super::route::route_error(category, level, &message);
+}
+
+/// 以 error 级别输出错误链，并在`hint`字段中给出处理建议，方便看板单独展示
+///
+/// # Example
+/// ```
+/// use myutil::log::log_error_with_hint;
+///
+/// let err = eyre::eyre!("connection refused");
+/// log_error_with_hint(&err, "check network connectivity"); // ERROR ... hint="check network connectivity"
+/// ```
+pub fn log_error_with_hint(err: &Report, hint: &str) {
+    let message = render_report(err);
+    tracing::error!(hint, "{message}");
+    super::route::route_error(ErrorCategory::of(err), Level::ERROR, &message);
+}
+
+thread_local! {
+    /// 当前线程嵌套的`log_boundary`层数
+    static BOUNDARY_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// 在边界处统一输出错误：执行`f`，出错时以`log_error_auto`输出一次完整报告后原样返回
+///
+/// 嵌套调用时只有最外层输出，内层直接返回错误，避免同一个错误在每一层都被记录。
+/// 只统计当前线程的嵌套层数。
+///
+/// # Example
+/// ```
+/// use myutil::log::log_boundary;
+///
+/// fn load() -> eyre::Result<()> {
+///     log_boundary(|| Err(eyre::eyre!("disk full"))) // 内层不输出
+/// }
+///
+/// let result = log_boundary(load); // 只在这里输出一次
+/// assert!(result.is_err());
+/// ```
+pub fn log_boundary<T>(f: impl FnOnce() -> eyre::Result<T>) -> eyre::Result<T> {
+    struct Depth;
+    impl Drop for Depth {
+        fn drop(&mut self) {
+            BOUNDARY_DEPTH.set(BOUNDARY_DEPTH.get() - 1);
+        }
+    }
+
+    let outermost = BOUNDARY_DEPTH.get() == 0;
+    let result = {
+        BOUNDARY_DEPTH.set(BOUNDARY_DEPTH.get() + 1);
+        // panic 时也要恢复层数
+        let _depth = Depth;
+        f()
+    };
+    if let Err(err) = &result {
+        if outermost {
+            log_error_auto(err);
+        }
+    }
+    result
+}
+
+/// FFI 边界：执行`f`，错误和 panic 都不会越过边界，输出日志后返回`default`（例如错误码）
+///
+/// 错误用`log_error_auto`输出，panic 以 error 级别输出 panic 消息；panic hook 仍会照常执行。
+///
+/// # Example
+/// ```
+/// use myutil::log::ffi_guard;
+///
+/// #[no_mangle]
+/// pub extern "C" fn parse_port(value: u32) -> i32 {
+///     ffi_guard(-1, || {
+///         let port = u16::try_from(value)?;
+///         Ok(i32::from(port))
+///     })
+/// }
+/// # assert_eq!(parse_port(80), 80);
+/// # assert_eq!(parse_port(70000), -1);
+/// ```
+pub fn ffi_guard<T>(default: T, f: impl FnOnce() -> eyre::Result<T> + std::panic::UnwindSafe) -> T {
+    match std::panic::catch_unwind(f) {
+        Ok(Ok(value)) => value,
+        Ok(Err(err)) => {
+            log_error_auto(&err);
+            default
+        }
+        Err(payload) => {
+            tracing::error!("panic at FFI boundary: {}", panic_message(&*payload));
+            default
+        }
+    }
+}
+
+/// 批处理程序的入口：开始时输出`starting`，结束时输出带`elapsed_ms`的`finished`，出错时以 error 级别输出错误报告
+///
+/// 返回闭包的结果，退出前 flush stdout，保证最后的耗时记录不丢失
+///
+/// # Example
+/// ```
+/// use myutil::log::main_scope;
+///
+/// fn main() -> eyre::Result<()> {
+///     main_scope(|| {
+///         // ...
+///         Ok(())
+///     })
+/// }
+/// ```
+pub fn main_scope(f: impl FnOnce() -> eyre::Result<()>) -> eyre::Result<()> {
+    let start = std::time::Instant::now();
+    tracing::info!("starting");
+
+    let result = f();
+
+    let elapsed_ms = start.elapsed().as_millis() as u64;
+    match &result {
+        Ok(()) => tracing::info!(elapsed_ms, "finished"),
+        Err(err) => tracing::error!(elapsed_ms, "failed: {}", render_report(err)),
+    }
+    let _ = std::io::Write::flush(&mut std::io::stdout());
+
+    result
+}
+
+/// 生成返回给 HTTP 客户端的错误响应体`{"error_code":"...","message":"..."}`，同时在服务端以`log_error_auto`输出完整错误
+///
+/// `message`只取最外层的 context，不包含内部的错误链和 backtrace，避免把实现细节泄露给客户端；
+/// 所以最外层的 context 应当是可以给用户看的描述。
+///
+/// # Example
+/// ```
+/// use eyre::WrapErr;
+/// use myutil::log::http_error_body;
+///
+/// let err = eyre::eyre!("connection to 10.0.0.5:5432 refused").wrap_err("failed to load order");
+/// let body = http_error_body(&err, "ORDER_UNAVAILABLE");
+/// assert_eq!(body, r#"{"error_code":"ORDER_UNAVAILABLE","message":"failed to load order"}"#);
+/// ```
+pub fn http_error_body(err: &Report, code: &str) -> String {
+    log_error_auto(err);
+    format!(r#"{{"error_code":{},"message":{}}}"#, json_string(code), json_string(&err.to_string()))
+}
+
+/// 错误消息 + 编号错误链 + span trace（如果有）
+fn render_report(err: &Report) -> String {
+    let mut message = format!("{err}\n{}", numbered_chain(err));
+    let span_trace = SpanTrace::capture();
+    if span_trace.status() == SpanTraceStatus::CAPTURED {
+        message.push_str(&format!("\n{span_trace}"));
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorCategory;
+    use crate::log::{CustomFormatter, ffi_guard, http_error_body, log_boundary, log_error_auto, log_error_with_hint, main_scope};
+    use crate::log::tests::capture_custom;
+
+    #[test]
+    fn log_error_auto_level() {
+        let output = capture_custom(CustomFormatter::default(), || {
+            log_error_auto(&ErrorCategory::NotFound.error("user 42").wrap_err("load user"));
+            log_error_auto(&eyre::eyre!("boom"));
+        });
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].starts_with("WARN myutil::log::report: "), "{output}");
+        assert!(lines[0].ends_with(" -> load user"), "{output}");
+        assert_eq!(lines[1], "   0: load user");
+        assert_eq!(lines[2], "   1: user 42 category=not_found");
+        assert!(lines[3].starts_with("ERROR myutil::log::report: "), "{output}");
+        assert_eq!(lines[4], "   0: boom category=internal");
+    }
+
+    #[test]
+    fn http_body_hides_chain() {
+        let mut body = String::new();
+        let output = capture_custom(CustomFormatter::default(), || {
+            let err = eyre::eyre!("password \"hunter2\" rejected by db").wrap_err("login \"failed\"");
+            body = http_error_body(&err, "AUTH_FAILED");
+        });
+        assert_eq!(body, r#"{"error_code":"AUTH_FAILED","message":"login \"failed\""}"#);
+        // 服务端日志仍有完整的错误链
+        assert!(output.contains("   1: password \"hunter2\" rejected by db category=internal"), "{output}");
+    }
+
+    #[test]
+    fn log_error_hint_field() {
+        let output = capture_custom(CustomFormatter::default(), || {
+            log_error_with_hint(&eyre::eyre!("connection refused"), "check network connectivity");
+        });
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].starts_with("ERROR ") && lines[0].ends_with(" -> connection refused"), "{output}");
+        assert_eq!(lines[1], "   0: connection refused hint=\"check network connectivity\"");
+    }
+
+    #[test]
+    fn log_boundary_logs_once() {
+        let output = capture_custom(CustomFormatter::default(), || {
+            let result: eyre::Result<()> = log_boundary(|| {
+                log_boundary(|| Err(eyre::eyre!("disk full"))).map_err(|err| err.wrap_err("save"))
+            });
+            assert!(result.is_err());
+            assert_eq!(log_boundary(|| Ok(1)).unwrap(), 1);
+            assert!(log_boundary(|| Err::<(), _>(eyre::eyre!("again"))).is_err());
+        });
+        let events: Vec<&str> = output.lines().filter(|line| line.starts_with("ERROR ")).collect();
+        assert_eq!(events.len(), 2, "{output}");
+        assert!(events[0].ends_with(" -> save"), "{output}");
+        assert!(events[1].ends_with(" -> again"), "{output}");
+    }
+
+    #[test]
+    fn ffi_guard_returns_default() {
+        let output = capture_custom(CustomFormatter::default(), || {
+            assert_eq!(ffi_guard(-1, || Ok(7)), 7);
+            assert_eq!(ffi_guard(-1, || Err(eyre::eyre!("bad input"))), -1);
+            assert_eq!(ffi_guard(-2, || panic!("boom")), -2);
+        });
+        let events: Vec<&str> = output.lines().filter(|line| line.starts_with("ERROR ")).collect();
+        assert_eq!(events.len(), 2, "{output}");
+        assert!(events[0].ends_with(" -> bad input"), "{output}");
+        assert!(events[1].ends_with(" -> panic at FFI boundary: boom"), "{output}");
+    }
+
+    #[test]
+    fn main_scope_logs_runtime() {
+        let output = capture_custom(CustomFormatter::default(), || {
+            assert!(main_scope(|| Ok(())).is_ok());
+            assert!(main_scope(|| Err(eyre::eyre!("boom"))).is_err());
+        });
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].ends_with(" -> starting"), "{output}");
+        assert!(lines[1].contains(" -> finished elapsed_ms="), "{output}");
+        assert!(lines[2].ends_with(" -> starting"), "{output}");
+        assert!(lines[3].starts_with("ERROR ") && lines[3].ends_with(" -> failed: boom"), "{output}");
+        assert!(lines[4].starts_with("   0: boom elapsed_ms="), "{output}");
+    }
+}
@@ -0,0 +1,146 @@
+use tracing::span::{Attributes, Id, Record};
+use tracing_core::field::{Field, Visit};
+use tracing_core::Subscriber;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::{LookupSpan, Registry};
+use tracing_subscriber::Layer;
+
+/// 保存在 span 扩展中的请求 id，来自 span 自身或最近的带有该字段的祖先
+#[derive(Clone)]
+pub(crate) struct RequestId {
+    pub(crate) field: &'static str,
+    pub(crate) value: String,
+}
+
+/// 把请求 id 从祖先 span 传给子孙 span 的 layer，见`request_id_layer`
+pub struct RequestIdLayer {
+    field: &'static str,
+}
+
+/// 创建传递请求 id 的 layer，需要使用`Registry`
+///
+/// 创建 span 时，span 自身带有`field`字段则使用它的值，否则沿用父 span 的值，保存在 span 的扩展中，
+/// 嵌套很深的 span 不需要重复声明这个字段。`JsonFormatter`在事件所在的 span 有请求 id 时输出顶层的`field`字段，
+/// 其它格式可以在`FormatEvent`中通过`current_request_id`读取。
+/// 用`tracing::field::Empty`声明、之后再`record`的值只传给之后创建的子 span。
+///
+/// # Example
+/// ```
+/// use tracing_subscriber::layer::SubscriberExt;
+/// use myutil::log::{current_request_id, request_id_layer};
+///
+/// let subscriber = tracing_subscriber::registry().with(request_id_layer("request_id"));
+/// tracing::subscriber::with_default(subscriber, || {
+///     let _request = tracing::info_span!("request", request_id = "r-42").entered();
+///     let _db = tracing::info_span!("db").entered();
+///     assert_eq!(current_request_id().as_deref(), Some("r-42"));
+/// });
+/// ```
+pub fn request_id_layer(field: &'static str) -> RequestIdLayer {
+    RequestIdLayer { field }
+}
+
+/// 当前 span 的请求 id，没有安装`request_id_layer`或不在带请求 id 的 span 中时返回`None`
+pub fn current_request_id() -> Option<String> {
+    tracing::Span::current().with_subscriber(|(id, dispatch)| {
+        let span = dispatch.downcast_ref::<Registry>()?.span(id)?;
+        let value = span.extensions().get::<RequestId>()?.value.clone();
+        Some(value)
+    }).flatten()
+}
+
+/// 找出`field`字段的值，字符串不带引号
+struct FieldValue {
+    field: &'static str,
+    value: Option<String>,
+}
+
+impl Visit for FieldValue {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == self.field {
+            self.value = Some(value.to_string());
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == self.field {
+            self.value = Some(format!("{value:?}"));
+        }
+    }
+}
+
+impl<S> Layer<S> for RequestIdLayer
+    where
+        S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut visitor = FieldValue { field: self.field, value: None };
+        attrs.record(&mut visitor);
+        let request_id = match visitor.value {
+            Some(value) => Some(RequestId { field: self.field, value }),
+            None => span.parent().and_then(|parent| parent.extensions().get::<RequestId>().cloned()),
+        };
+        if let Some(request_id) = request_id {
+            span.extensions_mut().insert(request_id);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let mut visitor = FieldValue { field: self.field, value: None };
+        values.record(&mut visitor);
+        if let (Some(value), Some(span)) = (visitor.value, ctx.span(id)) {
+            span.extensions_mut().replace(RequestId { field: self.field, value });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::log::{JsonFields, JsonFormatter, request_id_layer};
+    use crate::log::tests::TestWriter;
+
+    #[test]
+    fn nested_events_carry_request_id() {
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = tracing_subscriber::registry()
+            .with(request_id_layer("request_id"))
+            .with(tracing_subscriber::fmt::layer()
+                .with_writer(move || make_writer.clone())
+                .fmt_fields(JsonFields::default())
+                .event_format(JsonFormatter::default()));
+        tracing::subscriber::with_default(subscriber, || {
+            let request = tracing::info_span!("request", request_id = "r-42");
+            let _request = request.enter();
+            let _handler = tracing::info_span!("handler").entered();
+            let _db = tracing::info_span!("db", table = "users").entered();
+            tracing::info!("query");
+
+            let later = tracing::info_span!("later", request_id = tracing::field::Empty);
+            later.record("request_id", "r-43");
+            let _later = later.enter();
+            let _child = tracing::info_span!("child").entered();
+            tracing::info!("retry");
+        });
+
+        let output = writer.output();
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].contains(r#","message":"query","request_id":"r-42","span":{"name":"db","table":"users"}"#), "{output}");
+        assert!(lines[1].contains(r#","message":"retry","request_id":"r-43","span":{"name":"child"}"#), "{output}");
+    }
+
+    #[test]
+    fn no_request_id_outside_spans() {
+        let subscriber = tracing_subscriber::registry().with(request_id_layer("request_id"));
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(crate::log::current_request_id(), None);
+            let _plain = tracing::info_span!("plain").entered();
+            assert_eq!(crate::log::current_request_id(), None);
+        });
+    }
+}
@@ -0,0 +1,142 @@
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local};
+
+use crate::log::{InitLogError, LogConfig, LogGuard, TimeSourceTimer};
+
+/// 日志文件的切分周期，按本地时间计算
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    /// `<prefix>.2024-01-31`
+    Daily,
+    /// `<prefix>.2024-01-31-13`
+    Hourly,
+    /// 始终写入`<prefix>`
+    Never,
+}
+
+impl Rotation {
+    fn file_name(self, prefix: &str, now: &DateTime<Local>) -> String {
+        match self {
+            Rotation::Daily => format!("{prefix}.{}", now.format("%Y-%m-%d")),
+            Rotation::Hourly => format!("{prefix}.{}", now.format("%Y-%m-%d-%H")),
+            Rotation::Never => prefix.to_string(),
+        }
+    }
+}
+
+/// 按`Rotation`切换文件的 writer，跨过周期后的第一次写入会打开新文件，旧文件保留
+///
+/// 文件以追加方式打开，重启后继续写入同一周期的文件
+pub struct RollingFileAppender {
+    rotation: Rotation,
+    dir: PathBuf,
+    prefix: String,
+    current: Option<(String, File)>,
+}
+
+impl RollingFileAppender {
+    /// 创建目录并打开当前周期的文件
+    pub fn new(rotation: Rotation, dir: impl AsRef<Path>, prefix: impl Into<String>) -> std::io::Result<Self> {
+        let mut appender = RollingFileAppender {
+            rotation,
+            dir: dir.as_ref().to_path_buf(),
+            prefix: prefix.into(),
+            current: None,
+        };
+        std::fs::create_dir_all(&appender.dir)?;
+        appender.file_at(&Local::now())?;
+        Ok(appender)
+    }
+
+    fn file_at(&mut self, now: &DateTime<Local>) -> std::io::Result<&mut File> {
+        let name = self.rotation.file_name(&self.prefix, now);
+        if !matches!(&self.current, Some((current, _)) if *current == name) {
+            let file = OpenOptions::new().create(true).append(true).open(self.dir.join(&name))?;
+            self.current = Some((name, file));
+        }
+        Ok(&mut self.current.as_mut().expect("file opened above").1)
+    }
+
+    fn write_at(&mut self, buf: &[u8], now: &DateTime<Local>) -> std::io::Result<usize> {
+        self.file_at(now)?.write(buf)
+    }
+}
+
+impl Write for RollingFileAppender {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.write_at(buf, &Local::now())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match &mut self.current {
+            Some((_, file)) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// 长时间运行的服务用：日志写入`dir`下按`rotation`切分的文件，由后台线程写入
+///
+/// 格式与`LogMode::General`相同，不含颜色。返回的`LogGuard`持有后台线程，需要保存到程序结束，drop 时写完剩余的日志；
+/// 运行时可通过`LogGuard::reload_handle`修改级别。
+///
+/// # Example
+/// ```no_run
+/// use myutil::log::{init_log_to_file, Rotation};
+///
+/// let _guard = init_log_to_file("logs", "app.log", Rotation::Daily, tracing::Level::INFO).expect("Failed to initialize log");
+/// tracing::info!("written to logs/app.log.2024-01-31");
+/// ```
+pub fn init_log_to_file(
+    dir: impl AsRef<Path>,
+    prefix: &str,
+    rotation: Rotation,
+    log_level: tracing::Level,
+) -> Result<LogGuard, InitLogError> {
+    let appender = RollingFileAppender::new(rotation, dir, prefix)?;
+    LogConfig::new()
+        .level(log_level)
+        .with_line_number(true)
+        .timer(TimeSourceTimer::default())
+        .non_blocking_writer(appender)
+        .ansi(false)
+        .install()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use chrono::{Local, TimeZone};
+
+    use super::{RollingFileAppender, Rotation};
+
+    #[test]
+    fn rolls_over_daily() {
+        let dir = std::env::temp_dir().join(format!("myutil-rolling-{}", std::process::id()));
+        let mut appender = RollingFileAppender::new(Rotation::Daily, &dir, "app.log").unwrap();
+
+        let day1 = Local.with_ymd_and_hms(2024, 1, 31, 23, 59, 0).unwrap();
+        let day2 = Local.with_ymd_and_hms(2024, 2, 1, 0, 1, 0).unwrap();
+        appender.write_at(b"first\n", &day1).unwrap();
+        appender.write_at(b"second\n", &day2).unwrap();
+        appender.write_at(b"third\n", &day2).unwrap();
+        appender.flush().unwrap();
+
+        assert_eq!(std::fs::read_to_string(dir.join("app.log.2024-01-31")).unwrap(), "first\n");
+        assert_eq!(std::fs::read_to_string(dir.join("app.log.2024-02-01")).unwrap(), "second\nthird\n");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn file_names() {
+        let now = Local.with_ymd_and_hms(2024, 1, 31, 13, 5, 0).unwrap();
+        assert_eq!(Rotation::Daily.file_name("app", &now), "app.2024-01-31");
+        assert_eq!(Rotation::Hourly.file_name("app", &now), "app.2024-01-31-13");
+        assert_eq!(Rotation::Never.file_name("app", &now), "app");
+    }
+}
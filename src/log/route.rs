@@ -0,0 +1,91 @@
+use std::io::Write;
+use std::sync::OnceLock;
+
+use tracing::Level;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::error::ErrorCategory;
+
+static ROUTES: OnceLock<ErrorRoutes> = OnceLock::new();
+
+/// 按`ErrorCategory`把错误额外写到其它位置的路由表，例如 Internal 错误再写一份到错误文件用于告警
+///
+/// 进程内只配置一次，`log_error_auto`、`log_error_with_hint`和`log_boundary`输出错误时都会按它分发；
+/// 事件本身照常经过全局 subscriber，没有配置路由的分类只输出到那里。
+///
+/// # Example
+/// ```no_run
+/// use std::sync::Arc;
+/// use myutil::error::ErrorCategory;
+/// use myutil::log::ErrorRoutes;
+///
+/// let file = Arc::new(std::fs::File::create("errors.log").unwrap());
+/// ErrorRoutes::new()
+///     .route(ErrorCategory::Internal, file.clone())
+///     .route(ErrorCategory::Unavailable, file)
+///     .install();
+/// ```
+#[derive(Default)]
+pub struct ErrorRoutes {
+    routes: Vec<(ErrorCategory, BoxMakeWriter)>,
+}
+
+impl ErrorRoutes {
+    pub fn new() -> Self {
+        ErrorRoutes::default()
+    }
+
+    /// `category`的错误额外写入`make_writer`，同一分类可以配置多个位置
+    pub fn route<W>(mut self, category: ErrorCategory, make_writer: W) -> Self
+        where
+            W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+    {
+        self.routes.push((category, BoxMakeWriter::new(make_writer)));
+        self
+    }
+
+    /// 设置为进程内的路由表，已经设置过时返回`false`并保留原来的配置
+    pub fn install(self) -> bool {
+        ROUTES.set(self).is_ok()
+    }
+}
+
+/// 按路由表把一条错误写到`category`对应的位置，格式为`时间 级别 category=分类: 错误链`
+pub(crate) fn route_error(category: ErrorCategory, level: Level, message: &str) {
+    let Some(routes) = ROUTES.get() else {
+        return;
+    };
+    let mut line = None;
+    for (_, make_writer) in routes.routes.iter().filter(|(routed, _)| *routed == category) {
+        let line = line.get_or_insert_with(|| {
+            let now = chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f %z");
+            format!("{now} {level} category={category}: {message}\n")
+        });
+        let _ = make_writer.make_writer().write_all(line.as_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::ErrorCategory;
+    use crate::log::{ErrorRoutes, log_error_auto};
+    use crate::log::tests::TestWriter;
+
+    #[test]
+    fn routes_by_category() {
+        let errors = TestWriter::default();
+        let make_writer = errors.clone();
+        assert!(ErrorRoutes::new().route(ErrorCategory::Internal, move || make_writer.clone()).install());
+        assert!(!ErrorRoutes::new().install());
+
+        log_error_auto(&ErrorCategory::Internal.error("routed database corrupted"));
+        log_error_auto(&ErrorCategory::NotFound.error("routed user missing"));
+
+        // 其它测试输出的 Internal 错误也会写到这里，只检查本测试的两条
+        let output = errors.output();
+        assert!(output.contains(" ERROR category=internal: "), "{output}");
+        assert!(output.contains("routed database corrupted"), "{output}");
+        assert!(!output.contains("routed user missing"), "{output}");
+    }
+}
@@ -0,0 +1,126 @@
+use std::cell::Cell;
+
+use tracing::Level;
+use tracing_core::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::Layer;
+
+use crate::log::{InitLogError, LogGuard, LogMode};
+use crate::log::id::random_u64;
+use crate::log::init_report::Installed;
+
+thread_local! {
+    /// 每个线程独立的 xorshift64* 状态，不需要加锁
+    static RNG: Cell<u64> = Cell::new(random_u64() | 1);
+}
+
+/// 非加密用途的快速随机数
+fn next_random() -> u64 {
+    RNG.with(|state| {
+        let mut x = state.get();
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        state.set(x);
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    })
+}
+
+/// 按比例抽样 debug 和 trace 事件的 layer，info 及以上全部保留，见`sampling_layer`
+#[derive(Debug, Clone)]
+pub struct SamplingLayer {
+    /// 随机数小于它时保留，`u64::MAX`表示全部保留
+    threshold: u64,
+}
+
+impl SamplingLayer {
+    fn keep(&self, random: u64) -> bool {
+        self.threshold == u64::MAX || random < self.threshold
+    }
+}
+
+/// 创建抽样 layer：每条 debug/trace 事件以`debug_sample_rate`（`0.0`到`1.0`，超出范围时取边界值）的概率保留，
+/// info 及以上的事件全部保留
+///
+/// 在事件记录之前决定是否丢弃，每条事件只需要一次线程本地的随机数，被丢弃的事件不会格式化。
+/// 丢弃的事件对所有 layer 都不可见，只作用于事件，不影响 span。
+///
+/// # Example
+/// ```
+/// use tracing_subscriber::layer::SubscriberExt;
+/// use myutil::log::sampling_layer;
+///
+/// let subscriber = tracing_subscriber::registry()
+///     .with(sampling_layer(0.01))
+///     .with(tracing_subscriber::fmt::layer());
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::debug!("about 1% of these are logged");
+///     tracing::warn!("always logged");
+/// });
+/// ```
+pub fn sampling_layer(debug_sample_rate: f64) -> SamplingLayer {
+    let rate = if debug_sample_rate.is_nan() { 0.0 } else { debug_sample_rate.clamp(0.0, 1.0) };
+    let threshold = if rate >= 1.0 { u64::MAX } else { (rate * u64::MAX as f64) as u64 };
+    SamplingLayer { threshold }
+}
+
+impl<S: Subscriber> Layer<S> for SamplingLayer {
+    fn event_enabled(&self, event: &Event<'_>, _ctx: Context<'_, S>) -> bool {
+        *event.metadata().level() <= Level::INFO || self.keep(next_random())
+    }
+}
+
+/// 与`init_log`相同，但 debug 和 trace 事件只按`debug_sample_rate`的比例保留，info 及以上全部输出，见`sampling_layer`
+///
+/// 生产环境中可以保留少量 debug 日志用于排查问题，而不输出全部。`log_level`仍然决定最详细的级别，
+/// 需要抽样 debug 日志时应设置为`DEBUG`或`TRACE`。
+///
+/// # Example
+/// ```
+/// use myutil::log::{init_log_sampled_by_level, LogMode};
+///
+/// let _guard = init_log_sampled_by_level(LogMode::Json, tracing::Level::DEBUG, 0.01).unwrap();
+/// tracing::debug!("kept about 1% of the time");
+/// ```
+pub fn init_log_sampled_by_level(log_mode: LogMode, log_level: Level, debug_sample_rate: f64) -> Result<LogGuard, InitLogError> {
+    let filter = super::default_filter(&log_mode, log_level);
+    let installed = Installed { mode: log_mode.name(), writer: log_mode.writer_name() };
+    let (subscriber, reload) = super::build(log_mode, filter, None)?;
+    super::install(Box::new(subscriber.with(sampling_layer(debug_sample_rate))), reload, installed)
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::log::sampling_layer;
+    use crate::log::tests::TestWriter;
+
+    fn count_kept(debug_sample_rate: f64) -> (usize, usize) {
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = tracing_subscriber::registry()
+            .with(sampling_layer(debug_sample_rate))
+            .with(tracing_subscriber::fmt::layer().with_writer(move || make_writer.clone()).with_ansi(false));
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..2000 {
+                tracing::debug!(i, "sampled");
+                if i % 10 == 0 {
+                    tracing::warn!(i, "kept");
+                }
+            }
+        });
+        let output = writer.output();
+        (output.matches("sampled").count(), output.matches("kept").count())
+    }
+
+    #[test]
+    fn debug_events_sampled() {
+        assert_eq!(count_kept(0.0), (0, 200));
+        assert_eq!(count_kept(1.0), (2000, 200));
+        assert_eq!(count_kept(f64::NAN), (0, 200));
+        let (sampled, kept) = count_kept(0.5);
+        assert!((800..1200).contains(&sampled), "{sampled}");
+        assert_eq!(kept, 200);
+    }
+}
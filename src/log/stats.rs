@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use tracing::Level;
+use tracing_core::{Event, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+use super::heartbeat::spawn_periodic;
+use super::HeartbeatHandle;
+
+/// 汇总行中输出的 target 个数
+const TOP_TARGETS: usize = 3;
+
+#[derive(Default)]
+struct Stats {
+    /// 按`TRACE` `DEBUG` `INFO` `WARN` `ERROR`的顺序
+    levels: [u64; 5],
+    targets: HashMap<String, u64>,
+}
+
+static STATS: Mutex<Option<Stats>> = Mutex::new(None);
+
+fn stats() -> MutexGuard<'static, Option<Stats>> {
+    STATS.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+fn level_index(level: &Level) -> usize {
+    match *level {
+        Level::TRACE => 0,
+        Level::DEBUG => 1,
+        Level::INFO => 2,
+        Level::WARN => 3,
+        Level::ERROR => 4,
+    }
+}
+
+/// 按级别和 target 统计事件，见`stats_layer`
+pub struct StatsLayer;
+
+/// 创建统计事件的 layer，配合`start_periodic_stats`定期输出汇总
+///
+/// 只统计通过了过滤器的事件，放在过滤器之后
+pub fn stats_layer() -> StatsLayer {
+    StatsLayer
+}
+
+impl<S: Subscriber> Layer<S> for StatsLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let metadata = event.metadata();
+        // 不统计汇总行本身
+        if metadata.target() == module_path!() {
+            return;
+        }
+        let mut stats = stats();
+        let stats = stats.get_or_insert_with(Stats::default);
+        stats.levels[level_index(metadata.level())] += 1;
+        *stats.targets.entry(metadata.target().to_string()).or_insert(0) += 1;
+    }
+}
+
+/// 启动一个线程，每隔`interval`以 info 级别输出一行自上一次以来的事件统计，返回的句柄 drop 时线程退出
+///
+/// 输出各级别事件数、`error_rate`（error 占比）和事件最多的 3 个 target，需要同时安装`stats_layer`。线程使用调用时当前的 subscriber。
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use tracing_subscriber::layer::SubscriberExt;
+/// use myutil::log::{start_periodic_stats, stats_layer};
+///
+/// let subscriber = tracing_subscriber::registry()
+///     .with(tracing_subscriber::fmt::layer())
+///     .with(stats_layer());
+/// tracing::subscriber::set_global_default(subscriber).unwrap();
+///
+/// let _stats = start_periodic_stats(Duration::from_secs(60));
+/// // log stats total=1520 trace=0 debug=0 info=1480 warn=30 error=10 error_rate=0.007 top_targets="app::http=1200, app::db=300, sqlx=20"
+/// ```
+pub fn start_periodic_stats(interval: Duration) -> HeartbeatHandle {
+    spawn_periodic("log-stats", interval, log_stats)
+}
+
+/// 取出并清空当前统计，输出一行汇总
+fn log_stats() {
+    let Stats { levels, targets } = stats().take().unwrap_or_default();
+    let total: u64 = levels.iter().sum();
+    let error_rate = if total == 0 { 0.0 } else { levels[4] as f64 / total as f64 };
+
+    let mut targets: Vec<(String, u64)> = targets.into_iter().collect();
+    targets.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let top_targets = targets
+        .iter()
+        .take(TOP_TARGETS)
+        .map(|(target, count)| format!("{target}={count}"))
+        .collect::<Vec<_>>()
+        .join(", ");
+
+    tracing::info!(
+        total,
+        trace = levels[0],
+        debug = levels[1],
+        info = levels[2],
+        warn = levels[3],
+        error = levels[4],
+        error_rate = %format!("{error_rate:.3}"),
+        top_targets = %top_targets,
+        "log stats"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::log::{CustomFormatter, stats_layer};
+    use crate::log::tests::TestWriter;
+
+    use super::log_stats;
+
+    #[test]
+    fn stats_line_resets() {
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_subscriber::fmt::layer().with_ansi(false).with_writer(move || make_writer.clone()).event_format(CustomFormatter::default()))
+            .with(stats_layer());
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "app::http", "a");
+            tracing::info!(target: "app::http", "b");
+            tracing::warn!(target: "app::db", "c");
+            tracing::error!(target: "app::db", "d");
+            log_stats();
+            log_stats();
+        });
+
+        let output = writer.output();
+        let lines: Vec<&str> = output.lines().filter(|line| line.contains("log stats")).collect();
+        assert_eq!(lines.len(), 2, "{output}");
+        assert!(lines[0].ends_with("total=4 trace=0 debug=0 info=2 warn=1 error=1 error_rate=0.250 top_targets=app::db=2, app::http=2"), "{output}");
+        assert!(lines[1].ends_with("total=0 trace=0 debug=0 info=0 warn=0 error=0 error_rate=0.000 top_targets="), "{output}");
+    }
+}
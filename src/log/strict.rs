@@ -0,0 +1,79 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use tracing::Level;
+use tracing_core::{Event, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+static ERRORS: AtomicU64 = AtomicU64::new(0);
+
+/// 严格模式：统计 error 级别的事件，让 CI 中任何 error 日志都导致失败，见`strict_layer`
+pub struct StrictLayer {
+    exit_immediately: bool,
+}
+
+/// 创建严格模式 layer，放在输出事件的 layer 之后（外层），保证退出前该条 error 已经输出
+///
+/// `exit_immediately`为`true`时输出 error 后立即以退出码 1 退出，否则只计数，由`exit_if_errors`在安全的位置退出。
+///
+/// # Example
+/// ```
+/// use tracing_subscriber::layer::SubscriberExt;
+/// use myutil::log::{exit_if_errors, strict_layer};
+///
+/// let subscriber = tracing_subscriber::registry()
+///     .with(tracing_subscriber::fmt::layer())
+///     .with(strict_layer(false));
+/// tracing::subscriber::set_global_default(subscriber).unwrap();
+/// // ...
+/// exit_if_errors();
+/// ```
+pub fn strict_layer(exit_immediately: bool) -> StrictLayer {
+    StrictLayer { exit_immediately }
+}
+
+/// 严格模式 layer 统计到的 error 事件数
+pub fn error_count() -> u64 {
+    ERRORS.load(Ordering::Relaxed)
+}
+
+/// 出现过 error 事件时 flush stdout 并以退出码 1 退出
+pub fn exit_if_errors() {
+    let errors = error_count();
+    if errors > 0 {
+        eprintln!("strict mode: {errors} error(s) logged, exiting");
+        let _ = std::io::Write::flush(&mut std::io::stdout());
+        std::process::exit(1);
+    }
+}
+
+impl<S: Subscriber> Layer<S> for StrictLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if *event.metadata().level() != Level::ERROR {
+            return;
+        }
+        ERRORS.fetch_add(1, Ordering::Relaxed);
+        if self.exit_immediately {
+            exit_if_errors();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use crate::log::{error_count, strict_layer};
+
+    #[test]
+    fn strict_counts_errors() {
+        let before = error_count();
+        let subscriber = tracing_subscriber::registry().with(strict_layer(false));
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!("not counted");
+            tracing::error!("counted");
+            tracing::error!("counted");
+        });
+        assert_eq!(error_count() - before, 2);
+    }
+}
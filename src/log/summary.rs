@@ -0,0 +1,102 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use tracing::Level;
+use tracing_core::{Event, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+use super::EventFieldVisitor;
+
+static SUMMARY: Mutex<Option<HashMap<String, u64>>> = Mutex::new(None);
+
+fn summary() -> MutexGuard<'static, Option<HashMap<String, u64>>> {
+    SUMMARY.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// 统计 error 级别日志的消息，见`error_summary_layer`
+pub struct ErrorSummaryLayer;
+
+/// 创建统计 error 日志的 layer，批处理结束时用`error_summary`输出“480× timeout, 20× not found”这样的汇总
+///
+/// 按消息的第一行统计，数字替换为`#`、UUID 替换为`<uuid>`，让只有 id 不同的消息归为一类。
+///
+/// # Example
+/// ```
+/// use tracing_subscriber::layer::SubscriberExt;
+/// use myutil::log::{error_summary, error_summary_layer};
+///
+/// let subscriber = tracing_subscriber::registry().with(error_summary_layer());
+/// tracing::subscriber::with_default(subscriber, || {
+///     tracing::error!("timeout after 30s");
+///     tracing::error!("timeout after 31s");
+/// });
+/// assert_eq!(error_summary(), vec![("timeout after #s".to_string(), 2)]);
+/// ```
+pub fn error_summary_layer() -> ErrorSummaryLayer {
+    ErrorSummaryLayer
+}
+
+/// 记录一条错误消息，用于没有输出日志但已处理的错误
+pub fn record_error_message(message: &str) {
+    let key = normalize(message.lines().next().unwrap_or_default());
+    *summary().get_or_insert_with(HashMap::new).entry(key).or_insert(0) += 1;
+}
+
+/// 按次数从多到少返回各类错误消息及其次数
+pub fn error_summary() -> Vec<(String, u64)> {
+    let mut counts: Vec<(String, u64)> = summary().iter().flatten().map(|(message, count)| (message.clone(), *count)).collect();
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    counts
+}
+
+/// 是否为`8-4-4-4-12`格式的 UUID
+fn is_uuid(s: &str) -> bool {
+    s.len() == 36
+        && s.char_indices().all(|(i, c)| match i {
+            8 | 13 | 18 | 23 => c == '-',
+            _ => c.is_ascii_hexdigit(),
+        })
+}
+
+/// 把数字替换为`#`、UUID 替换为`<uuid>`
+fn normalize(message: &str) -> String {
+    let mut out = String::with_capacity(message.len());
+    let mut rest = message;
+    while let Some(c) = rest.chars().next() {
+        if rest.get(..36).is_some_and(is_uuid) {
+            out.push_str("<uuid>");
+            rest = &rest[36..];
+        } else if c.is_ascii_digit() {
+            out.push('#');
+            rest = rest.trim_start_matches(|c: char| c.is_ascii_digit());
+        } else {
+            out.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    out
+}
+
+impl<S: Subscriber> Layer<S> for ErrorSummaryLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        if *event.metadata().level() != Level::ERROR {
+            return;
+        }
+        let mut visitor = EventFieldVisitor::default();
+        event.record(&mut visitor);
+        record_error_message(visitor.message.as_deref().unwrap_or_default());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::normalize;
+
+    #[test]
+    fn normalize_variable_parts() {
+        assert_eq!(normalize("user 42 not found"), "user # not found");
+        assert_eq!(normalize("request 0b5c7e4a-2f1d-4c38-9a6e-3d2c1b0a9f8e failed after 3 retries"), "request <uuid> failed after # retries");
+        assert_eq!(normalize("超时 30 秒"), "超时 # 秒");
+    }
+}
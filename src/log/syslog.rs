@@ -0,0 +1,214 @@
+use std::io::Write;
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use tracing::Level;
+use tracing_core::{Event, Subscriber};
+use tracing_subscriber::fmt::{FmtContext, format, FormatEvent, FormatFields, MakeWriter};
+use tracing_subscriber::registry::LookupSpan;
+
+/// 依次尝试的本地 syslog socket：Linux、macOS、FreeBSD
+const SYSLOG_PATHS: [&str; 3] = ["/dev/log", "/var/run/syslog", "/var/run/log"];
+
+/// syslog 的 facility，决定 syslog 守护进程把日志写到哪个文件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyslogFacility {
+    Kern = 0,
+    User = 1,
+    Mail = 2,
+    Daemon = 3,
+    Auth = 4,
+    Syslog = 5,
+    Lpr = 6,
+    News = 7,
+    Uucp = 8,
+    Cron = 9,
+    Authpriv = 10,
+    Ftp = 11,
+    Local0 = 16,
+    Local1 = 17,
+    Local2 = 18,
+    Local3 = 19,
+    Local4 = 20,
+    Local5 = 21,
+    Local6 = 22,
+    Local7 = 23,
+}
+
+/// 级别对应的 syslog severity：error→err(3)、warn→warning(4)、info→info(6)、debug/trace→debug(7)
+fn severity(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        Level::DEBUG | Level::TRACE => 7,
+    }
+}
+
+/// `LogMode::Syslog`使用的 RFC 3164 格式：`<PRI>May  1 12:00:00 ident[pid]: target: message fields`
+#[derive(Debug, Clone)]
+pub struct SyslogFormatter {
+    facility: SyslogFacility,
+    ident: String,
+    pid: u32,
+}
+
+impl SyslogFormatter {
+    /// `ident`一般为程序名，syslog 用它区分日志来源
+    pub fn new(facility: SyslogFacility, ident: impl Into<String>) -> Self {
+        SyslogFormatter { facility, ident: ident.into(), pid: std::process::id() }
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for SyslogFormatter
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+        N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, N>,
+        mut writer: format::Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        let metadata = event.metadata();
+        let priority = self.facility as u8 * 8 + severity(metadata.level());
+        let timestamp = chrono::Local::now().format("%b %e %H:%M:%S");
+        write!(writer, "<{priority}>{timestamp} {}[{}]: {}: ", self.ident, self.pid, metadata.target())?;
+        ctx.field_format().format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
+
+/// 发送一条 syslog 消息，测试时可以替换
+pub(crate) trait SyslogTransport: Send + Sync + 'static {
+    fn send(&self, message: &[u8]) -> std::io::Result<()>;
+}
+
+/// 本地 syslog 的 Unix datagram socket，syslog 守护进程重启后自动重连一次
+struct UnixTransport {
+    path: PathBuf,
+    socket: Mutex<UnixDatagram>,
+}
+
+impl UnixTransport {
+    fn connect(path: &Path) -> std::io::Result<UnixDatagram> {
+        let socket = UnixDatagram::unbound()?;
+        socket.connect(path)?;
+        Ok(socket)
+    }
+}
+
+impl SyslogTransport for UnixTransport {
+    fn send(&self, message: &[u8]) -> std::io::Result<()> {
+        let mut socket = self.socket.lock().unwrap_or_else(|err| err.into_inner());
+        if socket.send(message).is_ok() {
+            return Ok(());
+        }
+        *socket = UnixTransport::connect(&self.path)?;
+        socket.send(message).map(|_| ())
+    }
+}
+
+/// 把每条日志作为一个 datagram 发给本地 syslog 守护进程的 writer，见`LogMode::Syslog`
+#[derive(Clone)]
+pub struct SyslogWriter {
+    transport: Arc<dyn SyslogTransport>,
+}
+
+impl SyslogWriter {
+    /// 连接本地 syslog（依次尝试`/dev/log`、`/var/run/syslog`和`/var/run/log`），都无法连接时返回最后一个错误
+    pub fn connect() -> std::io::Result<Self> {
+        let mut last_err = None;
+        for path in SYSLOG_PATHS {
+            match SyslogWriter::connect_to(path) {
+                Ok(writer) => return Ok(writer),
+                Err(err) => last_err = Some(err),
+            }
+        }
+        Err(last_err.expect("at least one syslog path"))
+    }
+
+    /// 连接指定的 syslog socket
+    pub fn connect_to(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let socket = UnixTransport::connect(&path)?;
+        Ok(SyslogWriter::with_transport(UnixTransport { path, socket: Mutex::new(socket) }))
+    }
+
+    pub(crate) fn with_transport(transport: impl SyslogTransport) -> Self {
+        SyslogWriter { transport: Arc::new(transport) }
+    }
+}
+
+impl<'a> MakeWriter<'a> for SyslogWriter {
+    type Writer = SyslogMessage<'a>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        SyslogMessage(self.transport.as_ref())
+    }
+}
+
+/// `SyslogWriter`为每条日志创建的 writer，每次`write`发送一条消息，去掉末尾的换行
+pub struct SyslogMessage<'a>(&'a dyn SyslogTransport);
+
+impl Write for SyslogMessage<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.send(buf.strip_suffix(b"\n").unwrap_or(buf))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::log::{SyslogFacility, SyslogFormatter, SyslogWriter};
+
+    use super::SyslogTransport;
+
+    #[derive(Clone, Default)]
+    struct Captured(Arc<Mutex<Vec<String>>>);
+
+    impl SyslogTransport for Captured {
+        fn send(&self, message: &[u8]) -> std::io::Result<()> {
+            self.0.lock().unwrap().push(String::from_utf8(message.to_vec()).unwrap());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn severity_mapping() {
+        let captured = Captured::default();
+        let subscriber = tracing_subscriber::fmt()
+            .with_max_level(tracing::Level::TRACE)
+            .with_ansi(false)
+            .with_writer(SyslogWriter::with_transport(captured.clone()))
+            .event_format(SyslogFormatter::new(SyslogFacility::Local0, "my-app"))
+            .finish();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::error!(code = 7, "failed");
+            tracing::warn!("slow");
+            tracing::info!("started");
+            tracing::debug!("detail");
+            tracing::trace!("noise");
+        });
+
+        let messages = captured.0.lock().unwrap();
+        let priorities: Vec<&str> = messages.iter().map(|message| message.split('>').next().unwrap()).collect();
+        // local0 = 16，优先级为 16 * 8 + severity
+        assert_eq!(priorities, ["<131", "<132", "<134", "<135", "<135"]);
+        let suffix = format!(" my-app[{}]: myutil::log::syslog::tests: failed code=7", std::process::id());
+        assert!(messages[0].ends_with(&suffix), "{}", messages[0]);
+    }
+
+    #[test]
+    fn missing_socket() {
+        assert!(SyslogWriter::connect_to("/nonexistent/log").is_err());
+    }
+}
@@ -0,0 +1,96 @@
+use std::fmt::{Debug, Display};
+use std::panic::Location;
+
+use tracing::Level;
+
+/// 以给定的调用位置输出日志，供代替调用方记录日志的工具函数使用
+///
+/// 位置记录在`caller.file`和`caller.line`字段中，`CustomFormatter`会用它们代替工具函数自身的`filename`。
+///
+/// # Example
+/// ```
+/// use std::panic::Location;
+/// use myutil::log::log_with_location;
+///
+/// #[track_caller]
+/// fn audit(action: &str) {
+///     log_with_location(tracing::Level::INFO, Location::caller(), format_args!("audit: {action}"));
+/// }
+///
+/// audit("login"); // 位置是这一行，而不是 audit 内部
+/// ```
+pub fn log_with_location(level: Level, location: &Location<'_>, message: impl Display) {
+    event_at!(level, caller.file = location.file(), caller.line = location.line(), "{message}");
+}
+
+/// 与`log_with_location`相同，位置取自`#[track_caller]`的调用方
+#[track_caller]
+pub fn log_caller(level: Level, message: impl Display) {
+    log_with_location(level, Location::caller(), message);
+}
+
+/// 记录值并原样返回，用于在表达式链中查看中间值，类似`dbg!`但输出到 tracing
+///
+/// 方法带有`#[track_caller]`，调用位置记录在`caller.file`和`caller.line`字段中
+///
+/// # Example
+/// ```
+/// use myutil::log::LogTap;
+///
+/// let total: i32 = vec![1, 2, 3]
+///     .into_iter()
+///     .map(|x| x * 2)
+///     .collect::<Vec<_>>()
+///     .log_dbg("doubled") // DEBUG doubled = [2, 4, 6]
+///     .iter()
+///     .sum();
+/// assert_eq!(total, 12);
+/// ```
+pub trait LogTap: Debug + Sized {
+    /// 以 debug 级别输出`{label} = {self:?}`
+    #[track_caller]
+    fn log_dbg(self, label: &str) -> Self {
+        self.log_val(Level::DEBUG, label)
+    }
+
+    /// 以指定级别输出`{label} = {self:?}`
+    #[track_caller]
+    fn log_val(self, level: Level, label: &str) -> Self {
+        log_with_location(level, Location::caller(), format_args!("{label} = {self:?}"));
+        self
+    }
+}
+
+impl<T: Debug> LogTap for T {}
+
+#[cfg(test)]
+mod tests {
+    use crate::log::{CustomFormatter, log_caller, LogTap};
+    use crate::log::tests::capture_custom;
+
+    #[test]
+    fn log_tap() {
+        let output = capture_custom(CustomFormatter::default(), || {
+            let value = vec![1, 2].log_dbg("list").len().log_val(tracing::Level::WARN, "len");
+            assert_eq!(value, 2);
+        });
+        let lines: Vec<&str> = output.lines().collect();
+        assert!(lines[0].starts_with("DEBUG "), "{output}");
+        let line = line!() - 5;
+        assert!(lines[0].ends_with(&format!(" filename=tap.rs:{line} -> list = [1, 2]")), "{output}");
+        assert!(lines[1].starts_with("WARN "), "{output}");
+        assert!(lines[1].ends_with(" -> len = 2"), "{output}");
+    }
+
+    #[track_caller]
+    fn helper() {
+        log_caller(tracing::Level::INFO, "from helper");
+    }
+
+    #[test]
+    fn log_caller_location() {
+        let output = capture_custom(CustomFormatter::default(), || helper());
+        let line = line!() - 1;
+        assert!(output.ends_with(&format!(" filename=tap.rs:{line} -> from helper\n")), "{output}");
+    }
+}
@@ -0,0 +1,90 @@
+use std::backtrace::{Backtrace, BacktraceStatus};
+use std::cell::{Cell, RefCell};
+use std::panic::AssertUnwindSafe;
+use std::sync::Once;
+use std::thread::JoinHandle;
+
+use crate::error::panic_message;
+
+thread_local! {
+    /// 当前线程是否由`spawn_logged`启动
+    static LOGGED: Cell<bool> = const { Cell::new(false) };
+    /// panic hook 在 panic 处捕获的 backtrace，catch_unwind 之后已经拿不到现场
+    static BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+static CAPTURE_HOOK: Once = Once::new();
+
+/// 在原有 panic hook 之外包一层，为`spawn_logged`的线程记录 backtrace
+fn install_capture_hook() {
+    CAPTURE_HOOK.call_once(|| {
+        let previous = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |panic_info| {
+            if LOGGED.get() {
+                BACKTRACE.set(Some(Backtrace::capture()));
+            }
+            previous(panic_info);
+        }));
+    });
+}
+
+/// 启动一个命名线程，线程内的 panic 以 error 级别输出到日志，用于不会被 join 的后台线程
+///
+/// panic 时输出 panic 消息，设置了`RUST_BACKTRACE`时附带 backtrace，线程结果为`None`。
+/// 线程使用调用时当前的 subscriber。需要在`init_error_hook`之后调用，否则 backtrace 捕获会被新的 panic hook 覆盖。
+///
+/// # Example
+/// ```
+/// use myutil::log::spawn_logged;
+///
+/// let worker = spawn_logged("worker", || panic!("boom")); // ERROR ... thread="worker" thread panicked: boom
+/// assert_eq!(worker.join().unwrap(), None);
+/// ```
+pub fn spawn_logged<F, T>(name: impl Into<String>, f: F) -> JoinHandle<Option<T>>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+{
+    install_capture_hook();
+    let name = name.into();
+    let dispatch = tracing::dispatcher::get_default(|dispatch| dispatch.clone());
+
+    std::thread::Builder::new()
+        .name(name.clone())
+        .spawn(move || tracing::dispatcher::with_default(&dispatch, || {
+            LOGGED.set(true);
+            match std::panic::catch_unwind(AssertUnwindSafe(f)) {
+                Ok(value) => Some(value),
+                Err(payload) => {
+                    let mut message = format!("thread panicked: {}", panic_message(&*payload));
+                    if let Some(backtrace) = BACKTRACE.take() {
+                        if backtrace.status() == BacktraceStatus::Captured {
+                            message.push_str(&format!("\n{backtrace}"));
+                        }
+                    }
+                    tracing::error!(thread = %name, "{message}");
+                    None
+                }
+            }
+        }))
+        .expect("Failed to spawn thread")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::log::{CustomFormatter, spawn_logged};
+    use crate::log::tests::capture_custom;
+
+    #[test]
+    fn spawn_logged_logs_panic() {
+        let output = capture_custom(CustomFormatter::default(), || {
+            assert_eq!(spawn_logged("ok-worker", || 42).join().unwrap(), Some(42));
+            assert_eq!(spawn_logged("bad-worker", || panic!("boom")).join().unwrap(), None::<()>);
+        });
+        assert!(output.starts_with("ERROR myutil::log::thread: "), "{output}");
+        // 设置了 RUST_BACKTRACE 时 backtrace 在消息和字段之间
+        assert!(output.contains(" -> thread panicked: boom"), "{output}");
+        assert!(output.contains("thread=bad-worker"), "{output}");
+        assert_eq!(output.lines().filter(|line| line.starts_with("ERROR")).count(), 1, "{output}");
+    }
+}
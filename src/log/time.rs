@@ -0,0 +1,178 @@
+use std::sync::{Arc, OnceLock};
+use std::time::Instant;
+
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::{ChronoLocal, ChronoUtc, FormatTime, SystemTime};
+
+/// 日志时间戳的来源，例如混合逻辑时钟（HLC）或测试用的模拟时钟
+///
+/// 每条日志调用一次`now_formatted`，返回的字符串原样输出。`ChronoLocal`、`ChronoUtc`和`SystemTime`已实现该 trait。
+/// JSON 格式的 Unix 时间戳取自`now`，模拟时钟等需要同时覆盖它。
+///
+/// # Example
+/// ```
+/// use myutil::log::{init_log_with_time_source, LogMode, TimeSource};
+///
+/// struct Tick;
+///
+/// impl TimeSource for Tick {
+///     fn now_formatted(&self) -> String {
+///         "tick-1".to_string()
+///     }
+/// }
+///
+/// let _guard = init_log_with_time_source(LogMode::General, tracing::Level::INFO, Tick).unwrap();
+/// tracing::info!("hello"); // tick-1  INFO rust_out:9: hello
+/// ```
+pub trait TimeSource: Send + Sync + 'static {
+    fn now_formatted(&self) -> String;
+
+    /// 当前时刻及其时区，默认为本地时区的系统时间
+    fn now(&self) -> chrono::DateTime<chrono::FixedOffset> {
+        chrono::Local::now().fixed_offset()
+    }
+}
+
+fn format_to_string(timer: &impl FormatTime) -> String {
+    let mut out = String::new();
+    let _ = timer.format_time(&mut Writer::new(&mut out));
+    out
+}
+
+impl TimeSource for ChronoLocal {
+    fn now_formatted(&self) -> String {
+        format_to_string(self)
+    }
+}
+
+impl TimeSource for ChronoUtc {
+    fn now_formatted(&self) -> String {
+        format_to_string(self)
+    }
+
+    fn now(&self) -> chrono::DateTime<chrono::FixedOffset> {
+        chrono::Utc::now().fixed_offset()
+    }
+}
+
+impl TimeSource for SystemTime {
+    fn now_formatted(&self) -> String {
+        format_to_string(self)
+    }
+
+    /// 与输出的时间一样是 UTC
+    fn now(&self) -> chrono::DateTime<chrono::FixedOffset> {
+        chrono::Utc::now().fixed_offset()
+    }
+}
+
+/// `LogMode::General`和`LogMode::Full`默认的时间格式
+pub(crate) const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S%.3f %z";
+
+/// `LogConfig::utc`在 JSON 格式中没有设置`time_format`时使用的 RFC 3339 格式
+pub(crate) const RFC3339_UTC_FORMAT: &str = "%Y-%m-%dT%H:%M:%S%.3fZ";
+
+/// 把`TimeSource`用作`tracing_subscriber`的`FormatTime`，可传给`with_timer`
+#[derive(Clone)]
+pub struct TimeSourceTimer(Arc<dyn TimeSource>);
+
+impl TimeSourceTimer {
+    pub fn new(source: impl TimeSource) -> Self {
+        TimeSourceTimer(Arc::new(source))
+    }
+
+    pub(crate) fn now_formatted(&self) -> String {
+        self.0.now_formatted()
+    }
+
+    pub(crate) fn now(&self) -> chrono::DateTime<chrono::FixedOffset> {
+        self.0.now()
+    }
+}
+
+impl std::fmt::Debug for TimeSourceTimer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("TimeSourceTimer(..)")
+    }
+}
+
+impl Default for TimeSourceTimer {
+    /// `LogMode::General`和`LogMode::Full`默认使用的本地时间格式
+    fn default() -> Self {
+        TimeSourceTimer::new(ChronoLocal::new(DEFAULT_TIME_FORMAT.to_string()))
+    }
+}
+
+impl FormatTime for TimeSourceTimer {
+    fn format_time(&self, w: &mut Writer<'_>) -> std::fmt::Result {
+        w.write_str(&self.0.now_formatted())
+    }
+}
+
+static START: OnceLock<Instant> = OnceLock::new();
+
+/// 记录初始化时刻，`uptime_ms`从这里开始计算
+pub(crate) fn mark_start() {
+    START.get_or_init(Instant::now);
+}
+
+/// 日志初始化以来的毫秒数（单调时钟），尚未初始化时从第一次调用开始计算
+pub fn uptime_ms() -> u64 {
+    START.get_or_init(Instant::now).elapsed().as_millis() as u64
+}
+
+/// 在时间戳后追加单调的`+123456ms`（`uptime_ms`），便于与只有相对时间的性能数据对齐
+///
+/// # Example
+/// ```
+/// use tracing_subscriber::fmt::time::ChronoLocal;
+/// use myutil::log::{init_log_with_time_source, LogMode, WithUptime};
+///
+/// let timer = ChronoLocal::new("%H:%M:%S%.3f".to_string());
+/// let _guard = init_log_with_time_source(LogMode::General, tracing::Level::INFO, WithUptime(timer)).unwrap();
+/// tracing::info!("hello"); // 12:00:00.000 +15ms  INFO rust_out:8: hello
+/// ```
+pub struct WithUptime<T>(pub T);
+
+impl<T: TimeSource> TimeSource for WithUptime<T> {
+    fn now_formatted(&self) -> String {
+        format!("{} +{}ms", self.0.now_formatted(), uptime_ms())
+    }
+
+    fn now(&self) -> chrono::DateTime<chrono::FixedOffset> {
+        self.0.now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::log::tests::TestWriter;
+    use crate::log::{TimeSource, TimeSourceTimer, WithUptime};
+
+    struct FixedClock;
+
+    impl TimeSource for FixedClock {
+        fn now_formatted(&self) -> String {
+            "hlc-42".to_string()
+        }
+    }
+
+    #[test]
+    fn time_source_timer() {
+        let writer = TestWriter::default();
+        let make_writer = writer.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || make_writer.clone())
+            .with_timer(TimeSourceTimer::new(FixedClock))
+            .finish();
+        tracing::subscriber::with_default(subscriber, || tracing::info!("hello"));
+        assert!(writer.output().starts_with("hlc-42  INFO "), "{}", writer.output());
+
+        let with_uptime = WithUptime(FixedClock).now_formatted();
+        assert!(with_uptime.starts_with("hlc-42 +") && with_uptime.ends_with("ms"), "{with_uptime}");
+
+        let utc = tracing_subscriber::fmt::time::ChronoUtc::new("%Y".to_string()).now_formatted();
+        assert_eq!(utc.len(), 4, "{utc}");
+    }
+}
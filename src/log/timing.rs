@@ -0,0 +1,489 @@
+use std::collections::HashMap;
+use std::fmt::{Debug, Display};
+use std::panic::Location;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use tracing::Level;
+use tracing_core::callsite::{Callsite, Identifier};
+use tracing_core::field::{Field, FieldSet, Value};
+use tracing_core::metadata::Kind;
+use tracing_core::subscriber::Interest;
+use tracing_core::{Event, Metadata};
+
+/// 作用域计时器，drop 时以 debug 级别输出耗时
+///
+/// 放在函数开头即可统计到作用域结束的耗时，drop 前可以用`field`追加字段。
+/// 日志的位置是创建计时器的地方；用`timed!`宏创建时 target 为调用处的模块，否则为`myutil::log::timing`。
+///
+/// # Example
+/// ```
+/// fn load() {
+///     let mut timed = myutil::timed!("load");
+///     // ...
+///     timed.field("rows", 42);
+/// } // drop 时输出: load rows=42 elapsed_ms=0
+/// ```
+pub struct Timed {
+    label: String,
+    start: Instant,
+    target: &'static str,
+    location: &'static Location<'static>,
+    fields: Vec<(&'static str, String)>,
+    /// 超出`MAX_FIELDS`没有输出的字段数
+    dropped: usize,
+}
+
+impl Timed {
+    #[track_caller]
+    pub fn start(label: impl Into<String>) -> Self {
+        Timed::start_with_target(module_path!(), label)
+    }
+
+    /// 与`start`相同，日志的 target 为`target`，通常用`timed!`宏传入调用处的`module_path!()`
+    #[track_caller]
+    pub fn start_with_target(target: &'static str, label: impl Into<String>) -> Self {
+        Timed {
+            label: label.into(),
+            start: Instant::now(),
+            target,
+            location: Location::caller(),
+            fields: Vec::new(),
+            dropped: 0,
+        }
+    }
+
+    /// 追加一个字段，随耗时一起输出；最多输出 29 个字段，之后追加的字段不输出，个数记在`dropped_fields`中
+    ///
+    /// 字段名与调用处一起决定一个常驻内存的 callsite，所以只接受`&'static str`
+    pub fn field(&mut self, name: &'static str, value: impl Display) -> &mut Self {
+        if self.fields.len() < MAX_FIELDS {
+            self.fields.push((name, value.to_string()));
+        } else {
+            self.dropped += 1;
+        }
+        self
+    }
+
+    /// 从`start`到现在的耗时
+    pub fn elapsed(&self) -> std::time::Duration {
+        self.start.elapsed()
+    }
+}
+
+impl Drop for Timed {
+    fn drop(&mut self) {
+        let elapsed_ms = self.start.elapsed().as_millis() as u64;
+        let names: Vec<&'static str> = self.fields.iter().map(|(name, _)| *name).collect();
+        let dropped = (self.dropped > 0).then_some(("dropped_fields", self.dropped));
+        let metadata = event_metadata("timed", self.target, self.location, &names, "", dropped.map(|(name, _)| name));
+        let fields: Vec<_> = self.fields.iter().map(|(_, value)| tracing::field::display(value)).collect();
+        let values: Vec<&dyn Value> = fields.iter().map(|value| value as &dyn Value).collect();
+        dispatch_event(metadata, self.label.as_str(), &values, elapsed_ms, dropped.map(|(_, count)| count));
+    }
+}
+
+/// 以调用处的模块为 target 创建`Timed`
+///
+/// # Example
+/// ```
+/// let _timed = myutil::timed!("load");
+/// ```
+#[macro_export]
+macro_rules! timed {
+    ($label:expr) => {
+        $crate::log::Timed::start_with_target(module_path!(), $label)
+    };
+}
+
+/// tracing 一个事件最多能记录的字段数
+const MAX_EVENT_FIELDS: usize = 32;
+
+/// `Timed`最多输出的字段数和`PhaseTimer`最多输出的阶段数，另外 3 个字段留给`message`、`elapsed_ms`和丢弃的个数
+const MAX_FIELDS: usize = MAX_EVENT_FIELDS - 3;
+
+/// 作用域计时器，记录各个阶段的耗时，结束时以 debug 级别输出一条日志，每个阶段一个字段
+///
+/// `phase`开始一个新阶段并结束上一个阶段，`finish`或 drop 时结束最后一个阶段，输出`{阶段}_ms`字段和总耗时`elapsed_ms`。
+/// 不需要为每个阶段创建 span，就能在一行日志中看到一次请求的时间花在了哪里。同名的阶段耗时累加；
+/// 第一次`phase`之前的时间只计入总耗时；最多输出 29 个不同的阶段，之后的新阶段只计入总耗时，没有输出的阶段次数记在`dropped_phases`中。
+/// 与`Timed`一样，用`phase_timer!`宏创建时 target 为调用处的模块。
+///
+/// # Example
+/// ```
+/// fn handle_request() {
+///     let mut timer = myutil::phase_timer!("handle_request");
+///     timer.phase("parse");
+///     // 解析 ...
+///     timer.phase("validate");
+///     // 校验 ...
+///     timer.finish();
+/// } // 输出: handle_request parse_ms=3 validate_ms=1 elapsed_ms=4
+/// ```
+pub struct PhaseTimer {
+    label: String,
+    start: Instant,
+    target: &'static str,
+    location: &'static Location<'static>,
+    /// 当前阶段及其开始时间
+    current: Option<(&'static str, Instant)>,
+    phases: Vec<(&'static str, Duration)>,
+    /// 超出`MAX_FIELDS`没有输出的阶段次数
+    dropped: usize,
+    finished: bool,
+}
+
+impl PhaseTimer {
+    #[track_caller]
+    pub fn start(label: impl Into<String>) -> Self {
+        PhaseTimer::start_with_target(module_path!(), label)
+    }
+
+    /// 与`start`相同，日志的 target 为`target`，通常用`phase_timer!`宏传入调用处的`module_path!()`
+    #[track_caller]
+    pub fn start_with_target(target: &'static str, label: impl Into<String>) -> Self {
+        PhaseTimer {
+            label: label.into(),
+            start: Instant::now(),
+            target,
+            location: Location::caller(),
+            current: None,
+            phases: Vec::new(),
+            dropped: 0,
+            finished: false,
+        }
+    }
+
+    /// 结束当前阶段，开始名为`name`的阶段
+    ///
+    /// 阶段名是字段名的一部分，每组阶段名对应一个常驻内存的 callsite，所以只接受`&'static str`
+    pub fn phase(&mut self, name: &'static str) -> &mut Self {
+        let now = Instant::now();
+        self.end_phase(now);
+        self.current = Some((name, now));
+        self
+    }
+
+    /// 到目前为止各阶段的耗时，按第一次出现的先后，不包括还没有结束的阶段
+    pub fn phases(&self) -> &[(&'static str, Duration)] {
+        &self.phases
+    }
+
+    /// 结束最后一个阶段并输出日志，不调用时在 drop 时输出
+    pub fn finish(mut self) {
+        self.emit();
+    }
+
+    fn end_phase(&mut self, now: Instant) {
+        let Some((name, started)) = self.current.take() else {
+            return;
+        };
+        let elapsed = now - started;
+        if let Some((_, total)) = self.phases.iter_mut().find(|(phase, _)| *phase == name) {
+            *total += elapsed;
+        } else if self.phases.len() < MAX_FIELDS {
+            self.phases.push((name, elapsed));
+        } else {
+            self.dropped += 1;
+        }
+    }
+
+    fn emit(&mut self) {
+        if self.finished {
+            return;
+        }
+        self.finished = true;
+        let now = Instant::now();
+        self.end_phase(now);
+
+        let names: Vec<&'static str> = self.phases.iter().map(|(name, _)| *name).collect();
+        let dropped = (self.dropped > 0).then_some(("dropped_phases", self.dropped));
+        let metadata = event_metadata("phase_timer", self.target, self.location, &names, "_ms", dropped.map(|(name, _)| name));
+        let phase_ms: Vec<u64> = self.phases.iter().map(|(_, elapsed)| elapsed.as_millis() as u64).collect();
+        let values: Vec<&dyn Value> = phase_ms.iter().map(|ms| ms as &dyn Value).collect();
+        let elapsed_ms = (now - self.start).as_millis() as u64;
+        dispatch_event(metadata, self.label.as_str(), &values, elapsed_ms, dropped.map(|(_, count)| count));
+    }
+}
+
+impl Drop for PhaseTimer {
+    fn drop(&mut self) {
+        self.emit();
+    }
+}
+
+/// 以调用处的模块为 target 创建`PhaseTimer`
+///
+/// # Example
+/// ```
+/// let mut timer = myutil::phase_timer!("handle_request");
+/// timer.phase("parse");
+/// ```
+#[macro_export]
+macro_rules! phase_timer {
+    ($label:expr) => {
+        $crate::log::PhaseTimer::start_with_target(module_path!(), $label)
+    };
+}
+
+/// 输出字段依次为`message`、`values`、`elapsed_ms`和`dropped`（有时）的事件，字段与`event_metadata`创建的一一对应
+fn dispatch_event(metadata: &'static Metadata<'static>, message: &str, values: &[&dyn Value], elapsed_ms: u64, dropped: Option<usize>) {
+    let enabled = tracing::dispatcher::get_default(|dispatch| dispatch.enabled(metadata));
+    if !enabled {
+        return;
+    }
+
+    let fields: Vec<Field> = metadata.fields().iter().collect();
+    let mut all: Vec<Option<&dyn Value>> = vec![Some(&message)];
+    all.extend(values.iter().map(|value| Some(*value)));
+    all.push(Some(&elapsed_ms));
+    if let Some(dropped) = &dropped {
+        all.push(Some(dropped));
+    }
+    assert_eq!(all.len(), fields.len(), "dynamic event values must match its fields");
+    // value_set 只接受定长数组：按最大字段数分配，多出的位置重复第一个字段且不带值，不会被记录
+    let entries: [(&Field, Option<&dyn Value>); MAX_EVENT_FIELDS] =
+        std::array::from_fn(|i| (fields.get(i).unwrap_or(&fields[0]), all.get(i).copied().flatten()));
+    Event::dispatch(metadata, &metadata.fields().value_set(&entries));
+}
+
+/// 字段名在运行时才确定的事件的 callsite，`metadata`在注册前设置
+struct DynamicCallsite {
+    metadata: OnceLock<Metadata<'static>>,
+}
+
+impl Callsite for DynamicCallsite {
+    fn set_interest(&self, _interest: Interest) {}
+
+    fn metadata(&self) -> &Metadata<'_> {
+        self.metadata.get().expect("dynamic callsite metadata set before registration")
+    }
+}
+
+/// 区分 callsite 的`(名称, target, 位置, 字段名, 丢弃个数的字段名)`，都是`'static`的，个数受限于代码中的调用处和字段名
+type CallsiteKey = (&'static str, &'static str, &'static Location<'static>, Vec<&'static str>, Option<&'static str>);
+
+/// 每个调用处（target、文件、行）的每组字段名对应一个 callsite，第一次出现时创建并注册，之后复用
+///
+/// 字段依次为`message`、`names`各自加上`suffix`、`elapsed_ms`和`dropped`，总数不能超过`MAX_EVENT_FIELDS`
+fn event_metadata(
+    name: &'static str,
+    target: &'static str,
+    location: &'static Location<'static>,
+    names: &[&'static str],
+    suffix: &str,
+    dropped: Option<&'static str>,
+) -> &'static Metadata<'static> {
+    static CALLSITES: Mutex<Option<HashMap<CallsiteKey, &'static Metadata<'static>>>> = Mutex::new(None);
+
+    assert!(names.len() + 3 <= MAX_EVENT_FIELDS, "too many fields for one event: {}", names.len());
+    let key = (name, target, location, names.to_vec(), dropped);
+    let mut callsites = CALLSITES.lock().unwrap_or_else(|err| err.into_inner());
+    let callsites = callsites.get_or_insert_with(HashMap::new);
+    if let Some(metadata) = callsites.get(&key) {
+        return metadata;
+    }
+
+    let mut field_names: Vec<&'static str> = vec!["message"];
+    if suffix.is_empty() {
+        field_names.extend_from_slice(names);
+    } else {
+        field_names.extend(names.iter().map(|name| &*Box::leak(format!("{name}{suffix}").into_boxed_str())));
+    }
+    field_names.push("elapsed_ms");
+    field_names.extend(dropped);
+    let field_names: &'static [&'static str] = Box::leak(field_names.into_boxed_slice());
+    let callsite: &'static DynamicCallsite = Box::leak(Box::new(DynamicCallsite { metadata: OnceLock::new() }));
+    let metadata = callsite.metadata.get_or_init(|| Metadata::new(
+        name,
+        target,
+        Level::DEBUG,
+        Some(location.file()),
+        Some(location.line()),
+        Some(target),
+        FieldSet::new(field_names, Identifier(callsite)),
+        Kind::EVENT,
+    ));
+    tracing_core::callsite::register(callsite);
+    callsites.insert(key, metadata);
+    metadata
+}
+
+/// 慢查询日志中 SQL 的最大字符数
+const MAX_STATEMENT_CHARS: usize = 1000;
+
+/// `elapsed`超过`threshold`时以 warn 级别输出统一格式的慢查询日志，返回是否输出
+///
+/// 字段为`db.statement`、`db.elapsed_ms`、`db.slow=true`和`db.params`，超过 1000 个字符的 SQL 会被截断并以`...`结尾。
+/// 没有参数时不输出`db.params`；参数可能包含敏感数据时使用`log_slow_query_redacted`。
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use myutil::log::log_slow_query;
+///
+/// let start = std::time::Instant::now();
+/// // 执行查询 ...
+/// log_slow_query("SELECT * FROM users WHERE id = ?", &[&42], start.elapsed(), Duration::from_millis(200));
+/// ```
+pub fn log_slow_query(sql: &str, params: &[&dyn Debug], elapsed: Duration, threshold: Duration) -> bool {
+    slow_query(sql, params, false, elapsed, threshold)
+}
+
+/// 与`log_slow_query`相同，但不输出参数的值：`db.params`为`***`，另以`db.param_count`输出参数个数
+///
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use myutil::log::log_slow_query_redacted;
+///
+/// let password = "secret";
+/// // db.params=*** db.param_count=2
+/// log_slow_query_redacted("UPDATE users SET password = ? WHERE id = ?", &[&password, &42], Duration::from_millis(300), Duration::from_millis(200));
+/// ```
+pub fn log_slow_query_redacted(sql: &str, params: &[&dyn Debug], elapsed: Duration, threshold: Duration) -> bool {
+    slow_query(sql, params, true, elapsed, threshold)
+}
+
+fn slow_query(sql: &str, params: &[&dyn Debug], redact: bool, elapsed: Duration, threshold: Duration) -> bool {
+    if elapsed <= threshold {
+        return false;
+    }
+
+    let statement = match sql.char_indices().nth(MAX_STATEMENT_CHARS) {
+        Some((end, _)) => format!("{}...", &sql[..end]),
+        None => sql.to_string(),
+    };
+    let elapsed_ms = elapsed.as_millis() as u64;
+    if params.is_empty() {
+        tracing::warn!(db.statement = %statement, db.elapsed_ms = elapsed_ms, db.slow = true, "slow query");
+    } else if redact {
+        tracing::warn!(db.statement = %statement, db.elapsed_ms = elapsed_ms, db.slow = true, db.params = %"***", db.param_count = params.len(), "slow query");
+    } else {
+        tracing::warn!(db.statement = %statement, db.elapsed_ms = elapsed_ms, db.slow = true, db.params = ?params, "slow query");
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use crate::log::{CustomFormatter, JsonFormatter, log_slow_query, log_slow_query_redacted, PhaseTimer};
+    use crate::log::json::tests::capture_json;
+    use crate::log::tests::capture_custom;
+    use crate::log::Timed;
+
+    #[test]
+    fn timed_logs_on_drop() {
+        let output = capture_custom(CustomFormatter::default(), || {
+            let mut timed = Timed::start("load");
+            timed.field("rows", 42).field("table", "users");
+        });
+        assert!(output.starts_with("DEBUG myutil::log::timing: "), "{output}");
+        assert!(output.contains(" -> load rows=42 table=users elapsed_ms="), "{output}");
+    }
+
+    #[test]
+    fn timed_caller_target_and_fields() {
+        let mut line = 0;
+        let output = capture_json(JsonFormatter::default(), || {
+            line = line!() + 1;
+            let mut timed = crate::timed!("load");
+            timed.field("rows", 42);
+        });
+        let expected = format!(r#""target":"myutil::log::timing::tests","filename":"src/log/timing.rs","line_number":{line},"#);
+        assert!(output.contains(&expected), "{output}");
+        assert!(output.contains(r#""message":"load","rows":"42","elapsed_ms":""#), "{output}");
+    }
+
+    #[test]
+    fn timed_reports_dropped_fields() {
+        const NAMES: [&str; 31] = [
+            "f0", "f1", "f2", "f3", "f4", "f5", "f6", "f7", "f8", "f9", "f10", "f11", "f12", "f13", "f14", "f15",
+            "f16", "f17", "f18", "f19", "f20", "f21", "f22", "f23", "f24", "f25", "f26", "f27", "f28", "f29", "f30",
+        ];
+        let output = capture_custom(CustomFormatter::default(), || {
+            let mut timed = Timed::start("wide");
+            for (i, name) in NAMES.iter().enumerate() {
+                timed.field(name, i);
+            }
+        });
+        assert!(output.contains(" f28=28 elapsed_ms="), "{output}");
+        assert!(!output.contains("f29="), "{output}");
+        assert!(output.trim_end().ends_with(" dropped_fields=2"), "{output}");
+    }
+
+    #[test]
+    fn phase_timer_reports_dropped_phases() {
+        const NAMES: [&str; 30] = [
+            "p0", "p1", "p2", "p3", "p4", "p5", "p6", "p7", "p8", "p9", "p10", "p11", "p12", "p13", "p14",
+            "p15", "p16", "p17", "p18", "p19", "p20", "p21", "p22", "p23", "p24", "p25", "p26", "p27", "p28", "p29",
+        ];
+        let output = capture_custom(CustomFormatter::default(), || {
+            let mut timer = PhaseTimer::start("wide");
+            for name in NAMES {
+                timer.phase(name);
+            }
+            timer.phase("p28");
+            assert_eq!(timer.phases().len(), 29);
+        });
+        assert!(output.contains(" p28_ms="), "{output}");
+        assert!(!output.contains("p29_ms"), "{output}");
+        assert!(output.trim_end().ends_with(" dropped_phases=1"), "{output}");
+    }
+
+    #[test]
+    fn phase_fields() {
+        let output = capture_custom(CustomFormatter::default(), || {
+            let mut timer = crate::phase_timer!("handle_request");
+            timer.phase("parse");
+            std::thread::sleep(Duration::from_millis(20));
+            timer.phase("validate").phase("parse");
+            assert_eq!(timer.phases().len(), 2);
+            timer.finish();
+            let mut dropped = PhaseTimer::start("empty");
+            dropped.phase("only");
+        });
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2, "{output}");
+        assert!(lines[0].starts_with("DEBUG myutil::log::timing::tests: "), "{output}");
+        let fields = lines[0].split_once(" -> handle_request ").expect("message").1;
+        let value = |name: &str| -> u64 {
+            let field = fields.split(' ').find_map(|field| field.strip_prefix(&format!("{name}="))).expect(name);
+            field.parse().unwrap()
+        };
+        assert!(value("parse_ms") >= 20, "{output}");
+        assert!(value("elapsed_ms") >= value("parse_ms") + value("validate_ms"), "{output}");
+        assert!(lines[1].contains(" -> empty only_ms=") && lines[1].contains(" elapsed_ms="), "{output}");
+    }
+
+    #[test]
+    fn slow_query_fields() {
+        let threshold = Duration::from_millis(100);
+        let long_sql = format!("SELECT {} FROM t", "c,".repeat(600));
+        let output = capture_custom(CustomFormatter::default(), || {
+            assert!(!log_slow_query("SELECT 1", &[], Duration::from_millis(50), threshold));
+            assert!(log_slow_query("SELECT * FROM users WHERE id = ?", &[&42, &"x"], Duration::from_millis(250), threshold));
+            assert!(log_slow_query(&long_sql, &[], Duration::from_millis(150), threshold));
+        });
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2, "{output}");
+        assert!(lines[0].starts_with("WARN "), "{output}");
+        assert!(lines[0].ends_with(" -> slow query db.statement=SELECT * FROM users WHERE id = ? db.elapsed_ms=250 db.slow=true db.params=[42, \"x\"]"), "{output}");
+        assert!(lines[1].contains(&format!("db.statement={}... db.elapsed_ms=150 db.slow=true", &long_sql[..1000])), "{output}");
+        assert!(!lines[1].contains("db.params"), "{output}");
+    }
+
+    #[test]
+    fn slow_query_redacted() {
+        let threshold = Duration::from_millis(100);
+        let output = capture_custom(CustomFormatter::default(), || {
+            assert!(!log_slow_query_redacted("SELECT 1", &[&"hidden"], Duration::from_millis(50), threshold));
+            assert!(log_slow_query_redacted("UPDATE users SET password = ? WHERE id = ?", &[&"hunter2", &987654], Duration::from_millis(250), threshold));
+        });
+        assert_eq!(output.lines().count(), 1, "{output}");
+        assert!(output.trim_end().ends_with(" db.elapsed_ms=250 db.slow=true db.params=*** db.param_count=2"), "{output}");
+        assert!(!output.contains("hunter2") && !output.contains("987654"), "{output}");
+    }
+}
@@ -0,0 +1,192 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use tracing_core::{Event, Subscriber};
+use tracing_subscriber::fmt::{FmtContext, format, FormatEvent};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::log::{InitLogError, JsonFields, JsonFormatter, LogConfig, LogFormat, LogGuard};
+
+/// 连接不上 Vector 时最多缓存的事件数，超出时丢弃最早的事件
+const MAX_BUFFERED: usize = 10_000;
+
+/// 两次连接尝试之间的最短间隔
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// 按 Vector `native_json`编码输出事件，每行一个`{"log":{...}}`，`log`中的内容与`JsonFormatter`相同
+///
+/// 对应 Vector 的配置：
+///
+/// ```toml
+/// [sources.app]
+/// type = "socket"
+/// mode = "tcp"
+/// address = "0.0.0.0:9000"
+/// decoding.codec = "native_json"
+/// framing.method = "newline_delimited"
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct VectorFormatter {
+    json: JsonFormatter,
+}
+
+impl<S> FormatEvent<S, JsonFields> for VectorFormatter
+    where
+        S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn format_event(
+        &self,
+        ctx: &FmtContext<'_, S, JsonFields>,
+        mut writer: format::Writer<'_>,
+        event: &Event<'_>,
+    ) -> std::fmt::Result {
+        let mut log = String::new();
+        self.json.format_event(ctx, format::Writer::new(&mut log), event)?;
+        writeln!(writer, r#"{{"log":{}}}"#, log.trim_end())
+    }
+}
+
+/// 通过 TCP 把日志行写给 Vector 的 writer，连接断开（如 Vector 重启）后自动重连
+///
+/// 写入永远不会因为 Vector 不可用而失败：连接不上时事件缓存在内存中（最多 10000 条，超出时丢弃最早的），
+/// 每秒最多尝试重连一次，连上后按顺序补发。通常由`non_blocking`在后台线程中使用，见`init_log_vector`。
+pub struct VectorWriter {
+    addr: String,
+    stream: Option<TcpStream>,
+    pending: VecDeque<Vec<u8>>,
+    last_attempt: Option<Instant>,
+    reconnect_interval: Duration,
+}
+
+impl VectorWriter {
+    /// 第一次写入时才连接`addr`，例如`127.0.0.1:9000`
+    pub fn new(addr: impl Into<String>) -> Self {
+        VectorWriter {
+            addr: addr.into(),
+            stream: None,
+            pending: VecDeque::new(),
+            last_attempt: None,
+            reconnect_interval: RECONNECT_INTERVAL,
+        }
+    }
+
+    fn connect(&mut self) -> Option<&mut TcpStream> {
+        if self.stream.is_none() {
+            if self.last_attempt.is_some_and(|last| last.elapsed() < self.reconnect_interval) {
+                return None;
+            }
+            self.last_attempt = Some(Instant::now());
+            let addrs = self.addr.to_socket_addrs().ok()?;
+            self.stream = addrs.into_iter().find_map(|addr| TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).ok());
+        }
+        self.stream.as_mut()
+    }
+
+    /// 按顺序发送缓存的行，失败时断开连接并保留未发送的行，下次写入时重试
+    fn send_pending(&mut self) {
+        while let Some(line) = self.pending.front() {
+            let line = line.clone();
+            let Some(stream) = self.connect() else {
+                return;
+            };
+            if stream.write_all(&line).is_err() {
+                self.stream = None;
+                return;
+            }
+            self.pending.pop_front();
+        }
+    }
+}
+
+impl Write for VectorWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.pending.len() >= MAX_BUFFERED {
+            self.pending.pop_front();
+        }
+        self.pending.push_back(buf.to_vec());
+        self.send_pending();
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.send_pending();
+        if let Some(stream) = &mut self.stream {
+            if stream.flush().is_err() {
+                self.stream = None;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 把日志发送给监听在`addr`上的 Vector（`socket` source，`native_json`编码，按行分隔），由后台线程写入
+///
+/// 格式见`VectorFormatter`，整数、浮点数和布尔字段保留原类型。启动时不要求 Vector 已经运行，断线重连和缓存见`VectorWriter`。
+/// 返回的`LogGuard`持有后台线程，需要保存到程序结束，drop 时尽量发送完剩余的日志。
+///
+/// # Example
+/// ```no_run
+/// use myutil::log::init_log_vector;
+///
+/// let _guard = init_log_vector("127.0.0.1:9000", tracing::Level::INFO).expect("Failed to initialize log");
+/// tracing::info!(user = "alice", "login"); // {"log":{"timestamp":"...","level":"INFO",...,"message":"login","user":"alice"}}
+/// ```
+pub fn init_log_vector(addr: &str, log_level: tracing::Level) -> Result<LogGuard, InitLogError> {
+    LogConfig::new()
+        .level(log_level)
+        .non_blocking_writer(VectorWriter::new(addr))
+        .ansi(false)
+        .format(LogFormat::Vector)
+        .install()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::time::Duration;
+
+    use crate::log::{LogConfig, LogFormat, non_blocking};
+
+    use super::VectorWriter;
+
+    #[test]
+    fn native_json_lines() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let (writer, guard) = non_blocking(VectorWriter::new(listener.local_addr().unwrap().to_string()));
+        let subscriber = LogConfig::new().writer(writer).ansi(false).format(LogFormat::Vector).build();
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(rows = 3, "loaded");
+        });
+        drop(guard);
+
+        let (stream, _) = listener.accept().unwrap();
+        let line = BufReader::new(stream).lines().next().unwrap().unwrap();
+        assert!(line.starts_with(r#"{"log":{"timestamp":""#), "{line}");
+        assert!(line.ends_with(r#","message":"loaded","rows":3}}"#), "{line}");
+    }
+
+    #[test]
+    fn buffers_until_reconnected() {
+        // 先占用一个端口再释放，得到一个暂时没有监听的地址
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let mut writer = VectorWriter::new(addr.to_string());
+        writer.reconnect_interval = Duration::ZERO;
+        writer.write_all(b"first\n").unwrap();
+        assert_eq!(writer.pending.len(), 1);
+
+        let listener = TcpListener::bind(addr).unwrap();
+        writer.write_all(b"second\n").unwrap();
+        writer.flush().unwrap();
+        assert!(writer.pending.is_empty());
+        drop(writer);
+
+        let (stream, _) = listener.accept().unwrap();
+        let lines: Vec<String> = BufReader::new(stream).lines().map(Result::unwrap).collect();
+        assert_eq!(lines, ["first", "second"]);
+    }
+}
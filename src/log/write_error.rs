@@ -0,0 +1,125 @@
+use std::io::Write;
+
+use tracing_core::Metadata;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// 日志写入失败（例如文件所在磁盘已满）时的处理方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WriteErrorPolicy {
+    /// 忽略，与 tracing 默认行为相同
+    #[default]
+    Ignore,
+    /// 在 stderr 输出一条警告
+    Stderr,
+    /// panic
+    Panic,
+}
+
+/// 包装`MakeWriter`，写入失败时按`WriteErrorPolicy`处理，而不是静默丢弃日志
+///
+/// # Example
+/// ```
+/// use myutil::log::{OnWriteError, WriteErrorPolicy};
+///
+/// let file = std::fs::File::create(std::env::temp_dir().join("app.log")).unwrap();
+/// let subscriber = tracing_subscriber::fmt()
+///     .with_writer(OnWriteError::new(std::sync::Mutex::new(file), WriteErrorPolicy::Stderr))
+///     .finish();
+/// ```
+pub struct OnWriteError<M> {
+    make_writer: M,
+    policy: WriteErrorPolicy,
+}
+
+impl<M> OnWriteError<M> {
+    pub fn new(make_writer: M, policy: WriteErrorPolicy) -> Self {
+        OnWriteError { make_writer, policy }
+    }
+}
+
+impl<'a, M: MakeWriter<'a>> MakeWriter<'a> for OnWriteError<M> {
+    type Writer = OnWriteErrorWriter<M::Writer>;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        OnWriteErrorWriter {
+            inner: self.make_writer.make_writer(),
+            policy: self.policy,
+        }
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        OnWriteErrorWriter {
+            inner: self.make_writer.make_writer_for(meta),
+            policy: self.policy,
+        }
+    }
+}
+
+pub struct OnWriteErrorWriter<W> {
+    inner: W,
+    policy: WriteErrorPolicy,
+}
+
+impl<W> OnWriteErrorWriter<W> {
+    fn check<T>(&self, result: std::io::Result<T>) -> std::io::Result<T> {
+        if let Err(err) = &result {
+            match self.policy {
+                WriteErrorPolicy::Ignore => {}
+                WriteErrorPolicy::Stderr => eprintln!("failed to write log: {err}"),
+                WriteErrorPolicy::Panic => panic!("failed to write log: {err}"),
+            }
+        }
+        result
+    }
+}
+
+impl<W: Write> Write for OnWriteErrorWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let result = self.inner.write(buf);
+        self.check(result)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> std::io::Result<()> {
+        let result = self.inner.write_all(buf);
+        self.check(result)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        let result = self.inner.flush();
+        self.check(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use crate::log::{OnWriteError, WriteErrorPolicy};
+
+    struct FullDisk;
+
+    impl Write for FullDisk {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("no space left on device"))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn log_with(policy: WriteErrorPolicy) {
+        let subscriber = tracing_subscriber::fmt()
+            .with_writer(OnWriteError::new(|| FullDisk, policy))
+            .finish();
+        tracing::subscriber::with_default(subscriber, || tracing::info!("lost"));
+    }
+
+    #[test]
+    fn write_error_policy() {
+        log_with(WriteErrorPolicy::Ignore);
+        log_with(WriteErrorPolicy::Stderr);
+        let err = std::panic::catch_unwind(|| log_with(WriteErrorPolicy::Panic)).unwrap_err();
+        assert_eq!(err.downcast_ref::<String>().unwrap(), "failed to write log: no space left on device");
+    }
+}
@@ -1,5 +1,3 @@
-use myutil;
-
 #[test]
 fn simple() {
     let num = myutil::add(1,2);